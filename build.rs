@@ -0,0 +1,4 @@
+// sqlx::migrate! 在编译时读取 migrations，修改后需要重新编译
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -10,4 +10,31 @@ $(document).ready(function(){
           )
         }
     });
+
+    // 标签自动完成，只补全最后一个标签
+    $(".tag-input").on("input", function(){
+        var input = $(this);
+        var box = input.siblings(".tag-suggest");
+        var last = input.val().split(/[,，;；、\s]+/).pop();
+        if (last == "") {
+            box.empty();
+            return;
+        }
+        $.getJSON('/my/tag/suggest', {q: last}, function(names){
+            box.empty();
+            $.each(names, function(i, name){
+                $('<a href="#" class="list-group-item list-group-item-action"></a>')
+                    .text(name)
+                    .appendTo(box);
+            });
+        });
+    });
+    $(".tag-suggest").on("click", "a", function(e){
+        e.preventDefault();
+        var box = $(this).parent();
+        var input = box.siblings(".tag-input");
+        var value = input.val().replace(/[^,，;；、\s]*$/, "");
+        input.val(value + $(this).text() + " ").focus();
+        box.empty();
+    });
 });
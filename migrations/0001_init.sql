@@ -1,5 +1,18 @@
+-- 初始表结构，和原来的 pgsql/setup.sql 相同。
+-- 可以重复执行，已经用 setup.sql 建好的数据库也可以直接迁移。
+
 create extension if not exists "uuid-ossp";
-create extension pg_jieba;
+-- 没有安装 pg_jieba 时，jiebacfg 用 simple 代替，中文分词效果差一些
+do $$
+begin
+    if exists (select 1 from pg_available_extensions where name = 'pg_jieba') then
+        create extension if not exists pg_jieba;
+    elsif not exists (select 1 from pg_ts_config where cfgname = 'jiebacfg') then
+        raise notice 'pg_jieba is not available, jiebacfg falls back to simple';
+        create text search configuration jiebacfg (copy = simple);
+    end if;
+end
+$$;
 
 create or replace function set_updated_at()
     returns trigger as
@@ -14,6 +27,10 @@ create or replace function trigger_updated_at(tablename regclass)
     returns void as
 $$
 begin
+    if exists (select 1 from pg_trigger
+               where tgrelid = tablename and tgname = 'set_updated_at') then
+        return;
+    end if;
     execute format('CREATE TRIGGER set_updated_at
         BEFORE UPDATE
         ON %s
@@ -23,10 +40,10 @@ begin
 end;
 $$ language plpgsql;
 
-create collation case_insensitive (provider = icu, locale = 'und-u-ks-level2', deterministic = false);
+create collation if not exists case_insensitive (provider = icu, locale = 'und-u-ks-level2', deterministic = false);
 
 -- 文章
-CREATE TABLE hu (
+CREATE TABLE IF NOT EXISTS hu (
   hu_id serial PRIMARY KEY,
   user_id integer NOT NULL,
   user_name text not null,
@@ -54,7 +71,7 @@ CREATE TABLE hu (
 );
 select trigger_updated_at('hu');
 
-alter table hu add column
+alter table hu add column if not exists
   search_ti tsvector GENERATED ALWAYS AS (
       to_tsvector('jiebacfg',
            coalesce(title, '')
@@ -63,10 +80,10 @@ alter table hu add column
            || coalesce(body, '')
            || coalesce(body2, '')
       )) STORED;
-CREATE INDEX hu_search_idx ON hu USING GIN(search_ti);
+CREATE INDEX IF NOT EXISTS hu_search_idx ON hu USING GIN(search_ti);
 
 --文章评论
-CREATE TABLE hu_comment (
+CREATE TABLE IF NOT EXISTS hu_comment (
   id serial PRIMARY KEY,
   user_id integer not null,
   user_name text not null,
@@ -80,7 +97,7 @@ CREATE TABLE hu_comment (
 select trigger_updated_at('hu_comment');
 
 -- 微博
-CREATE TABLE xi (
+CREATE TABLE IF NOT EXISTS xi (
   xi_id serial PRIMARY KEY,
   user_id integer NOT NULL,
   user_name text not null,
@@ -103,17 +120,17 @@ CREATE TABLE xi (
 
 select trigger_updated_at('xi');
 
-alter table xi add column
+alter table xi add column if not exists
   search_ti tsvector GENERATED ALWAYS AS (
       to_tsvector('jiebacfg',
            coalesce(title, '')
            || coalesce(tags, '')
            || coalesce(body, '')
       )) STORED;
-CREATE INDEX xi_search_idx ON xi USING GIN(search_ti);
+CREATE INDEX IF NOT EXISTS xi_search_idx ON xi USING GIN(search_ti);
 
 --微博评论
-CREATE TABLE xi_comment (
+CREATE TABLE IF NOT EXISTS xi_comment (
   id serial PRIMARY KEY,
   user_id integer not null,
   user_name text not null,
@@ -127,7 +144,7 @@ CREATE TABLE xi_comment (
 select trigger_updated_at('xi_comment');
 
 --图片
-CREATE TABLE image (
+CREATE TABLE IF NOT EXISTS image (
   id serial PRIMARY KEY,
   user_id integer NOT NULL,
   title text not null,
@@ -139,17 +156,17 @@ CREATE TABLE image (
   updated_at timestamptz
 );
 select trigger_updated_at('image');
-alter table image add column
+alter table image add column if not exists
   search_ti tsvector GENERATED ALWAYS AS (
       to_tsvector('jiebacfg',
            coalesce(title, '')
            || coalesce(tags, '')
            || coalesce(brief, '')
       )) STORED;
-CREATE INDEX image_search_idx ON image USING GIN(search_ti);
+CREATE INDEX IF NOT EXISTS image_search_idx ON image USING GIN(search_ti);
 
 --站内短信
-CREATE TABLE message (
+CREATE TABLE IF NOT EXISTS message (
   id uuid primary key default uuid_generate_v1mc(),
   user_id integer not null,
   user_name text not null,
@@ -169,7 +186,7 @@ CREATE TABLE message (
 select trigger_updated_at('message');
 
 --站内短信回复
-CREATE TABLE message_comment (
+CREATE TABLE IF NOT EXISTS message_comment (
   id serial PRIMARY KEY,
   user_id integer not null,
   user_name text not null,
@@ -183,7 +200,7 @@ CREATE TABLE message_comment (
 select trigger_updated_at('message_comment');
 
 --密码重置
-CREATE TABLE reset_pw_req (
+CREATE TABLE IF NOT EXISTS reset_pw_req (
   id uuid primary key default uuid_generate_v1mc(),
   user_id integer NOT NULL,
   user_name text NOT NULL,
@@ -196,7 +213,7 @@ CREATE TABLE reset_pw_req (
 select trigger_updated_at('reset_pw_req');
 
 --用户
-CREATE TABLE users (
+CREATE TABLE IF NOT EXISTS users (
   user_id serial PRIMARY KEY,
   uid uuid not null default uuid_generate_v1mc(),
   name text collate "case_insensitive" not null,
@@ -219,11 +236,11 @@ CREATE TABLE users (
   created_at  timestamptz not null default now(),
   updated_at timestamptz
 );
-create index on "users" (name collate "ucs_basic");
+create index if not exists users_name_idx on "users" (name collate "ucs_basic");
 select trigger_updated_at('users');
 
 --好书
-CREATE TABLE book (
+CREATE TABLE IF NOT EXISTS book (
   book_id serial PRIMARY KEY,
   user_id integer NOT NULL,
   user_name text not null,
@@ -242,10 +259,6 @@ CREATE TABLE book (
   log text, -- 目录
   log_html text,
   press text, -- 出版社
-  version text, -- new
-  price text, -- new
-  src text, -- 图片源文件 new
-  file text, -- 图片文件 new
   url text,
   tags text,
   click integer not null DEFAULT 0,
@@ -257,7 +270,7 @@ CREATE TABLE book (
 );
 select trigger_updated_at('book');
 
-alter table book add column
+alter table book add column if not exists
   search_ti tsvector GENERATED ALWAYS AS (
       to_tsvector('jiebacfg',
            coalesce(title, '')
@@ -267,10 +280,10 @@ alter table book add column
            || coalesce(body2, '')
            || coalesce(log, '')
       )) STORED;
-CREATE INDEX book_search_idx ON book USING GIN(search_ti);
+CREATE INDEX IF NOT EXISTS book_search_idx ON book USING GIN(search_ti);
 
 --好书评论
-CREATE TABLE book_comment (
+CREATE TABLE IF NOT EXISTS book_comment (
   id serial PRIMARY KEY,
   user_id integer not null,
   user_name text not null,
@@ -0,0 +1,6 @@
+-- 好书后来增加的字段，老数据库里可能没有
+alter table book
+  add column if not exists version text,
+  add column if not exists price text,
+  add column if not exists src text,  -- 图片源文件
+  add column if not exists file text; -- 图片文件
@@ -0,0 +1,11 @@
+--访问令牌，只保存 sha256
+CREATE TABLE IF NOT EXISTS api_token (
+  id serial PRIMARY KEY,
+  user_id integer not null,
+  name text not null,
+  token_hash text not null unique,
+  prefix text not null,
+  last_used_at timestamptz,
+  created_at  timestamptz not null default now()
+);
+create index if not exists api_token_user_id_idx on api_token (user_id);
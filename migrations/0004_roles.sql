@@ -0,0 +1,3 @@
+-- 权限改为 reader(1) author(2) moderator(3) editor(4) admin(5)，-1 停用，0 未激活。
+-- 原来的普通用户（1）都可以发表内容，改为 author。
+update users set i_role=2 where i_role=1;
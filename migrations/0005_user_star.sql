@@ -0,0 +1,14 @@
+--星标，每个用户对每条内容只能加一次
+CREATE TABLE IF NOT EXISTS user_star (
+  user_id integer not null,
+  kind text not null,
+  obj_id integer not null,
+  created_at  timestamptz not null default now(),
+  PRIMARY KEY (user_id, kind, obj_id)
+);
+create index if not exists user_star_obj_idx on user_star (kind, obj_id);
+
+-- star 以前没有使用，按 user_star 重新计算
+update hu set star=0 where star<>0;
+update xi set star=0 where star<>0;
+update book set star=0 where star<>0;
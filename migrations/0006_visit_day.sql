@@ -0,0 +1,9 @@
+--每天的访问次数，click 是总数
+CREATE TABLE IF NOT EXISTS visit_day (
+  kind text not null,
+  obj_id integer not null,
+  day date not null,
+  views integer not null default 0,
+  PRIMARY KEY (kind, obj_id, day)
+);
+create index if not exists visit_day_day_idx on visit_day (day);
@@ -0,0 +1,46 @@
+--标签，name 是小写、去掉 # 以后的值
+CREATE TABLE IF NOT EXISTS tag (
+  id serial PRIMARY KEY,
+  name text not null unique
+);
+
+--kind: hu, xi, book, image
+CREATE TABLE IF NOT EXISTS tagging (
+  tag_id integer not null references tag (id) on delete cascade,
+  kind text not null,
+  obj_id integer not null,
+  PRIMARY KEY (tag_id, kind, obj_id)
+);
+create index if not exists tagging_obj_idx on tagging (kind, obj_id);
+
+-- 把已有的 tags 字段拆开，和 tag::parse_tags 相同：分隔符一样，去掉空的、太长的和重复的，
+-- 每条内容按原来的顺序最多留 10 个（tag::MAX_TAGS）
+create temporary table tag_split on commit drop as
+select kind, obj_id, name
+from (
+  select kind, obj_id, name, row_number() over (partition by kind, obj_id order by min(pos)) as n
+  from (
+    select kind, obj_id, lower(ltrim(t, '#')) as name, pos
+    from (
+      select 'hu' as kind, hu_id as obj_id, s.t, s.pos
+        from hu, regexp_split_to_table(tags, '[,，;；、[:space:]]+') with ordinality as s(t, pos)
+      union all
+      select 'xi', xi_id, s.t, s.pos
+        from xi, regexp_split_to_table(tags, '[,，;；、[:space:]]+') with ordinality as s(t, pos)
+      union all
+      select 'book', book_id, s.t, s.pos
+        from book, regexp_split_to_table(tags, '[,，;；、[:space:]]+') with ordinality as s(t, pos)
+      union all
+      select 'image', id, s.t, s.pos
+        from image, regexp_split_to_table(tags, '[,，;；、[:space:]]+') with ordinality as s(t, pos)
+    ) s
+  ) s
+  where name <> '' and char_length(name) <= 32
+  group by kind, obj_id, name
+) s
+where n <= 10;
+
+insert into tag (name) select distinct name from tag_split on conflict (name) do nothing;
+insert into tagging (tag_id, kind, obj_id)
+select tag.id, s.kind, s.obj_id from tag_split s join tag on tag.name = s.name
+on conflict do nothing;
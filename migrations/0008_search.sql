@@ -0,0 +1,52 @@
+-- search_ti 的各字段之间加上空格，原来标题和正文会连成一个词。
+-- 字段和 db::search::Backend::document 相同。
+alter table hu drop column if exists search_ti;
+alter table hu add column search_ti tsvector GENERATED ALWAYS AS (
+    to_tsvector('jiebacfg',
+         coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')
+         || ' ' || coalesce(body, '') || ' ' || coalesce(body2, '')
+    )) STORED;
+CREATE INDEX IF NOT EXISTS hu_search_idx ON hu USING GIN(search_ti);
+
+alter table xi drop column if exists search_ti;
+alter table xi add column search_ti tsvector GENERATED ALWAYS AS (
+    to_tsvector('jiebacfg',
+         coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(body, '')
+    )) STORED;
+CREATE INDEX IF NOT EXISTS xi_search_idx ON xi USING GIN(search_ti);
+
+alter table book drop column if exists search_ti;
+alter table book add column search_ti tsvector GENERATED ALWAYS AS (
+    to_tsvector('jiebacfg',
+         coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')
+         || ' ' || coalesce(body, '') || ' ' || coalesce(body2, '') || ' ' || coalesce(log, '')
+    )) STORED;
+CREATE INDEX IF NOT EXISTS book_search_idx ON book USING GIN(search_ti);
+
+alter table image drop column if exists search_ti;
+alter table image add column search_ti tsvector GENERATED ALWAYS AS (
+    to_tsvector('jiebacfg',
+         coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')
+    )) STORED;
+CREATE INDEX IF NOT EXISTS image_search_idx ON image USING GIN(search_ti);
+
+-- [search] backend = "trgm" 时用的三元组索引，没有 pg_trgm 时也能搜索，只是比较慢
+do $$
+begin
+    if exists (select 1 from pg_available_extensions where name = 'pg_trgm') then
+        create extension if not exists pg_trgm;
+        create index if not exists hu_trgm_idx on hu using gin ((
+            coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')
+            || ' ' || coalesce(body, '') || ' ' || coalesce(body2, '')) gin_trgm_ops);
+        create index if not exists xi_trgm_idx on xi using gin ((
+            coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(body, '')) gin_trgm_ops);
+        create index if not exists book_trgm_idx on book using gin ((
+            coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')
+            || ' ' || coalesce(body, '') || ' ' || coalesce(body2, '') || ' ' || coalesce(log, '')) gin_trgm_ops);
+        create index if not exists image_trgm_idx on image using gin ((
+            coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '')) gin_trgm_ops);
+    else
+        raise notice 'pg_trgm is not available, trgm search will not use an index';
+    end if;
+end
+$$;
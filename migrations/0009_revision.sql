@@ -0,0 +1,26 @@
+--文章和好书的修改记录，每次保存一条
+CREATE TABLE IF NOT EXISTS revision (
+  id serial PRIMARY KEY,
+  kind text not null, -- hu, book
+  obj_id integer not null,
+  user_id integer not null,
+  user_name text not null,
+  title text not null,
+  brief text,
+  body text not null,
+  body2 text,
+  log text,
+  created_at timestamptz not null default now()
+);
+create index if not exists revision_obj_idx on revision (kind, obj_id, id);
+
+-- 已有的内容作为第一条记录
+insert into revision (kind, obj_id, user_id, user_name, title, brief, body, body2, log, created_at)
+select 'hu', hu_id, user_id, user_name, title, brief, body, body2, log, coalesce(updated_at, created_at)
+from hu
+where not exists (select 1 from revision r where r.kind = 'hu' and r.obj_id = hu_id);
+
+insert into revision (kind, obj_id, user_id, user_name, title, brief, body, body2, log, created_at)
+select 'book', book_id, user_id, user_name, title, brief, body, body2, log, coalesce(updated_at, created_at)
+from book
+where not exists (select 1 from revision r where r.kind = 'book' and r.obj_id = book_id);
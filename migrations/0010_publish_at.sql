@@ -0,0 +1,8 @@
+-- 定时发布：i_public = 2 时到 publish_at 自动改成公布
+alter table hu add column if not exists publish_at timestamptz;
+alter table xi add column if not exists publish_at timestamptz;
+alter table book add column if not exists publish_at timestamptz;
+
+create index if not exists hu_publish_at_idx on hu (publish_at) where i_public = 2;
+create index if not exists xi_publish_at_idx on xi (publish_at) where i_public = 2;
+create index if not exists book_publish_at_idx on book (publish_at) where i_public = 2;
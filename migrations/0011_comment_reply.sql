@@ -0,0 +1,8 @@
+-- 评论的回复：parent_id 是回复的评论，为空时是直接评论内容
+alter table hu_comment add column if not exists parent_id integer;
+alter table xi_comment add column if not exists parent_id integer;
+alter table book_comment add column if not exists parent_id integer;
+
+create index if not exists hu_comment_obj_idx on hu_comment (obj_id);
+create index if not exists xi_comment_obj_idx on xi_comment (obj_id);
+create index if not exists book_comment_obj_idx on book_comment (obj_id);
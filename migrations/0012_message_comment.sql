@@ -0,0 +1,18 @@
+-- 站内短信的回复：message.id 是 uuid，原来的 message_id 是 integer，没有代码写过这个表，
+-- 已有的记录对不上任何信件，删除以后改成 uuid。
+do $$
+begin
+    if exists (select 1 from information_schema.columns
+               where table_name = 'message_comment' and column_name = 'message_id'
+                 and data_type = 'integer') then
+        delete from message_comment;
+        alter table message_comment alter column message_id type uuid using null;
+        alter table message_comment
+          add constraint message_comment_message_id_fkey
+          foreign key (message_id) references message (id) on delete cascade;
+    end if;
+end
+$$;
+-- 对方是否读过，0 未读，1 已读
+alter table message_comment add column if not exists i_status smallint not null default 0;
+create index if not exists message_comment_message_idx on message_comment (message_id, id);
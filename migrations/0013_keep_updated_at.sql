@@ -0,0 +1,12 @@
+-- 重新生成 HTML 不算修改内容：事务里 set local huxi.keep_updated_at = 'on' 时不改 updated_at
+create or replace function set_updated_at()
+    returns trigger as
+$$
+begin
+    if current_setting('huxi.keep_updated_at', true) = 'on' then
+        return NEW;
+    end if;
+    NEW.updated_at = now();
+    return NEW;
+end;
+$$ language plpgsql;
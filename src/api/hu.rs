@@ -0,0 +1,170 @@
+//! 文章接口 /api/v1/hu
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{ApiError, ApiUser, Result};
+use crate::fun::hu::db::*;
+use crate::fun::hu::{access, can_edit};
+use crate::fun::user::{Cap, SessUser};
+use crate::fun::widget::list::{DbList, List, ListBy};
+use crate::http::WebContext;
+
+const MAX_PAGE_SIZE: u8 = 100;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/hu", get(hu_list).post(hu_create))
+        .route("/hu/:id", get(hu_get).put(hu_update).delete(hu_delete))
+        .route("/hu/:id/good", post(hu_good).delete(hu_good_cancel))
+}
+
+/// 列表参数，和 List 的过滤条件一致
+#[derive(Deserialize, Debug, Default)]
+pub struct ListArgs {
+    pub page: Option<u32>,
+    pub size: Option<u8>,
+    pub cat: Option<u8>,
+    #[serde(rename = "type")]
+    pub i_type: Option<u8>,
+    pub good: Option<u8>,
+    /// 全文搜索
+    pub q: Option<String>,
+    /// 标题搜索
+    pub title: Option<String>,
+    /// 只列出自己的文章，包括草稿
+    pub mine: Option<bool>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ListPage<T> {
+    pub total: i64,
+    pub page: u32,
+    pub size: u8,
+    pub items: Vec<T>,
+}
+
+async fn hu_list(
+    State(ctx): State<WebContext>,
+    user: Option<ApiUser>,
+    Query(args): Query<ListArgs>,
+) -> Result<Json<ListPage<HuSimple>>> {
+    let filter = if args.mine.unwrap_or(false) {
+        let ApiUser(user) = user.ok_or(ApiError::Unauthorized)?;
+        ListBy::UserId(user.id)
+    } else {
+        ListBy::AllPublic
+    };
+    let page = args.page.unwrap_or(1).max(1);
+    let size = args
+        .size
+        .unwrap_or(ctx.config.hu.page_size)
+        .clamp(1, MAX_PAGE_SIZE);
+    let list = List::new(&ctx, filter, page)
+        .size(size)
+        .cat(args.cat)
+        .i_type(args.i_type)
+        .good(args.good)
+        .web_search_key(args.q.as_deref())
+        .title_search_key(args.title.as_deref());
+    let (total, data) = db_list(&list).await?;
+    let items = match data {
+        DbList::Hu(rows) => rows,
+        _ => Vec::new(),
+    };
+    Ok(Json(ListPage {
+        total,
+        page,
+        size,
+        items,
+    }))
+}
+
+async fn hu_get(
+    State(ctx): State<WebContext>,
+    user: Option<ApiUser>,
+    Path(id): Path<i32>,
+) -> Result<Json<Hu>> {
+    let hu = db_get_one(&ctx, id).await?;
+    let user = user.map(|ApiUser(u)| u);
+    if !access(&hu, user.as_ref()).allow {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(Json(hu))
+}
+
+async fn hu_create(
+    State(ctx): State<WebContext>,
+    ApiUser(user): ApiUser,
+    Json(mut input): Json<Input>,
+) -> Result<(StatusCode, Json<Hu>)> {
+    if !user.can(Cap::Publish) {
+        return Err(ApiError::Forbidden);
+    }
+    input.check().map_err(ApiError::Invalid)?;
+    let id = db_insert(&ctx, &user, input).await?;
+    let hu = db_get_one(&ctx, id).await?;
+    Ok((StatusCode::CREATED, Json(hu)))
+}
+
+async fn hu_update(
+    State(ctx): State<WebContext>,
+    ApiUser(user): ApiUser,
+    Path(id): Path<i32>,
+    Json(mut input): Json<Input>,
+) -> Result<Json<Hu>> {
+    let hu = db_get_one(&ctx, id).await?;
+    if !can_edit(hu.user_id, &user) {
+        return Err(ApiError::Forbidden);
+    }
+    input.check().map_err(ApiError::Invalid)?;
+    if !db_update(&ctx, &user, id, &input).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(db_get_one(&ctx, id).await?))
+}
+
+async fn hu_delete(
+    State(ctx): State<WebContext>,
+    ApiUser(user): ApiUser,
+    Path(id): Path<i32>,
+) -> Result<StatusCode> {
+    let hu = db_get_one(&ctx, id).await?;
+    if !can_edit(hu.user_id, &user) {
+        return Err(ApiError::Forbidden);
+    }
+    if !db_rm(&ctx, id).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 推荐，只有管理员可以
+async fn set_good(ctx: &WebContext, user: SessUser, id: i32, value: i16) -> Result<Json<Hu>> {
+    if !user.can(Cap::Recommend) {
+        return Err(ApiError::Forbidden);
+    }
+    if !db_good(ctx, id, value).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(Json(db_get_one(ctx, id).await?))
+}
+
+async fn hu_good(
+    State(ctx): State<WebContext>,
+    ApiUser(user): ApiUser,
+    Path(id): Path<i32>,
+) -> Result<Json<Hu>> {
+    set_good(&ctx, user, id, 1).await
+}
+
+async fn hu_good_cancel(
+    State(ctx): State<WebContext>,
+    ApiUser(user): ApiUser,
+    Path(id): Path<i32>,
+) -> Result<Json<Hu>> {
+    set_good(&ctx, user, id, 0).await
+}
@@ -0,0 +1,85 @@
+//! JSON 接口 /api/v1
+
+pub mod hu;
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::{request::Parts, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::fun::user::token::AuthUser;
+use crate::fun::user::SessUser;
+use crate::http::WebContext;
+
+pub type Result<T, E = ApiError> = std::result::Result<T, E>;
+
+pub fn router() -> Router<WebContext> {
+    Router::new().nest("/api/v1", Router::new().merge(hu::router()))
+}
+
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("forbidden")]
+    Forbidden,
+    #[error("not found")]
+    NotFound,
+    #[error("invalid input")]
+    Invalid(Vec<String>),
+    #[error(transparent)]
+    Anyhow(#[from] anyhow::Error),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    messages: Vec<String>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Anyhow(ref e) => match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+                _ => {
+                    tracing::error!("api error: {e:?}");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            },
+        };
+        let messages = match self {
+            ApiError::Invalid(ref m) => m.clone(),
+            _ => Vec::new(),
+        };
+        let error = match status {
+            StatusCode::NOT_FOUND => "not found".to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR => "internal error".to_string(),
+            _ => self.to_string(),
+        };
+        (status, Json(ErrorBody { error, messages })).into_response()
+    }
+}
+
+/// 登录的接口用户，Bearer 令牌或 session，未登录返回 401；可选时用 `Option<ApiUser>`
+pub struct ApiUser(pub SessUser);
+
+#[async_trait]
+impl FromRequestParts<WebContext> for ApiUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &WebContext) -> Result<Self> {
+        let AuthUser(user) = AuthUser::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::Unauthorized)?;
+        Ok(ApiUser(user))
+    }
+}
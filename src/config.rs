@@ -1,11 +1,81 @@
 use serde::{Deserialize, Serialize};
 
+use crate::db::search::Backend;
+use crate::fun::user::Role;
+
+/// 不写子命令时启动网站，和 `serve` 相同
 #[derive(clap::Parser, Clone)]
+#[command(subcommand_negates_reqs = true)]
 pub struct WebArgs {
     #[clap(long, env)]
     pub www_config: String,
+    #[command(flatten)]
+    pub serve: Option<ServeArgs>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Clone)]
+pub enum Command {
+    /// 启动网站
+    Serve(ServeArgs),
+    /// 执行数据库迁移
+    Migrate,
+    /// 用户管理
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Markdown 的处理改了以后重新生成已保存的 HTML
+    Rerender(RerenderArgs),
+}
+
+#[derive(clap::Args, Clone)]
+pub struct RerenderArgs {
+    /// 只列出会变的记录，不写入
+    #[clap(long)]
+    pub dry_run: bool,
+    /// 每批的记录数
+    #[clap(long, default_value = "200")]
+    pub batch: i64,
+    /// 只处理这些表，不写时是所有的表
+    pub tables: Vec<String>,
+}
+
+/// USER 可以是用户编号、用户名或邮箱，全是数字时是用户编号
+#[derive(clap::Subcommand, Clone)]
+pub enum UserCommand {
+    /// 新建用户，不需要邮件激活
+    Create {
+        name: String,
+        email: String,
+        /// 不写时从标准输入读取
+        #[clap(long)]
+        password: Option<String>,
+        /// reader, author, moderator, editor, admin
+        #[clap(long, default_value = "author")]
+        role: Role,
+    },
+    /// 修改权限：reader, author, moderator, editor, admin, disabled
+    SetRole { user: String, role: Role },
+    /// 重置密码
+    ResetPassword {
+        user: String,
+        /// 不写时从标准输入读取
+        #[clap(long)]
+        password: Option<String>,
+    },
+    /// 停用，不能再登录
+    Disable { user: String },
+    /// 列出所有用户
+    List,
+}
+
+#[derive(clap::Args, Clone)]
+pub struct ServeArgs {
     #[clap(long, env)]
     pub www_port: u16,
+    /// 启动前先执行数据库迁移
+    #[clap(long, env = "WWW_AUTO_MIGRATE")]
+    pub auto_migrate: bool,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Host {
@@ -87,6 +157,148 @@ pub struct User {
     pub public_url: String,
 }
 
+/// robots.txt，没有配置时使用默认值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Robots {
+    pub user_agent: String,
+    pub allow: Vec<String>,
+    pub disallow: Vec<String>,
+    pub crawl_delay: Option<u32>,
+    /// 是否加上 Sitemap: {host.www}/sitemap.xml
+    pub sitemap: bool,
+}
+
+impl Default for Robots {
+    fn default() -> Self {
+        Robots {
+            user_agent: "*".to_string(),
+            allow: Vec::new(),
+            disallow: vec!["/my/".to_string(), "/api/".to_string()],
+            crawl_delay: None,
+            sitemap: true,
+        }
+    }
+}
+
+/// 访问统计，没有配置时使用默认值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Visit {
+    /// 同一个 session 或 IP 在这段时间内只算一次
+    pub window_minutes: u64,
+    /// 多久写一次数据库
+    pub flush_seconds: u64,
+    /// User-Agent 包含这些词（不分大小写）时不计数
+    pub bots: Vec<String>,
+}
+
+impl Default for Visit {
+    fn default() -> Self {
+        Visit {
+            window_minutes: 30,
+            flush_seconds: 60,
+            bots: [
+                "bot", "spider", "crawl", "slurp", "curl", "wget", "python", "go-http", "java/",
+                "headless", "feed", "preview",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        }
+    }
+}
+
+/// 搜索，没有配置时使用 pg_jieba
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Search {
+    pub backend: Backend,
+}
+
+/// 评论什么时候需要审核
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Moderation {
+    /// 直接公布
+    #[default]
+    Auto,
+    /// 每个用户第一次评论需要审核，通过以后直接公布
+    First,
+    /// 都需要审核
+    Always,
+}
+
+/// 评论，没有配置时使用默认值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Comment {
+    /// 有新评论和回复时，除了站内信，是否也发邮件
+    pub email: bool,
+    pub moderation: Moderation,
+    /// 链接超过这么多个时需要审核
+    pub max_links: usize,
+    /// 包含这些词（不分大小写）时需要审核
+    pub banned_words: Vec<String>,
+    /// 每个用户 rate_minutes 分钟内最多评论 rate_count 次
+    pub rate_count: i64,
+    pub rate_minutes: i64,
+}
+
+impl Default for Comment {
+    fn default() -> Self {
+        Comment {
+            email: false,
+            moderation: Moderation::Auto,
+            max_links: 3,
+            banned_words: Vec::new(),
+            rate_count: 5,
+            rate_minutes: 10,
+        }
+    }
+}
+
+/// Markdown 渲染，没有配置时使用默认值
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Markdown {
+    /// 文章的标题不少于这么多个时加上目录
+    pub toc_min: usize,
+    /// 保存时在服务器上高亮代码，页面不再用 highlight.js
+    pub highlight: bool,
+    /// 高亮的样式，htdocs/css/hl 里的文件名
+    pub highlight_theme: String,
+    /// $...$ 和 $$...$$ 的公式转成 MathML
+    pub math: bool,
+    /// dot 代码块的 Graphviz 图转成 SVG
+    pub graphviz: bool,
+    /// 生成 mermaid 代码块的图的命令，会加上 -i 输入文件 -o 输出文件 --svgId id，空的时候按代码显示。
+    /// 评论和信件谁都能写，为了不让它们运行外部命令，其中的 mermaid 代码块总是按代码显示
+    pub mermaid: String,
+    /// 文章、好书可以用的 HTML 标签，没有配置时用 ammonia 默认的
+    pub article_tags: Option<Vec<String>>,
+    /// 微博可以用的 HTML 标签，没有配置时和文章一样，但是没有 h1、h2
+    pub xi_tags: Option<Vec<String>>,
+    /// 评论和信件可以用的 HTML 标签，没有配置时只有简单的格式，没有图片和表格
+    pub comment_tags: Option<Vec<String>>,
+}
+
+impl Default for Markdown {
+    fn default() -> Self {
+        Markdown {
+            toc_min: 3,
+            highlight: true,
+            highlight_theme: "github".to_string(),
+            math: true,
+            graphviz: true,
+            mermaid: String::new(),
+            article_tags: None,
+            xi_tags: None,
+            comment_tags: None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WebConfig {
     pub host: Host,
@@ -97,4 +309,14 @@ pub struct WebConfig {
     pub xi: Xi,
     pub image: Image,
     pub user: User,
+    #[serde(default)]
+    pub robots: Robots,
+    #[serde(default)]
+    pub visit: Visit,
+    #[serde(default)]
+    pub search: Search,
+    #[serde(default)]
+    pub comment: Comment,
+    #[serde(default)]
+    pub markdown: Markdown,
 }
@@ -0,0 +1,148 @@
+//! 列表查询条件
+//!
+//! 所有搜索词、分类、类型、分页的值都用参数绑定，不拼进 SQL。
+//! 表名、字段名、排序只接受 `&'static str`，由代码写死。
+
+use sqlx::{Postgres, QueryBuilder};
+
+use super::search::Backend;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    SmallInt(i16),
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<i16> for Value {
+    fn from(v: i16) -> Self {
+        Value::SmallInt(v)
+    }
+}
+
+impl From<u8> for Value {
+    fn from(v: u8) -> Self {
+        Value::SmallInt(v as i16)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cond {
+    Eq(&'static str, Value),
+    WebSearch(Backend, String),
+    TitleLike(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    table: &'static str,
+    conds: Vec<Cond>,
+}
+
+impl Filter {
+    pub fn new(table: &'static str) -> Self {
+        Filter {
+            table,
+            conds: Vec::new(),
+        }
+    }
+
+    pub fn eq<V: Into<Value>>(mut self, column: &'static str, value: V) -> Self {
+        self.conds.push(Cond::Eq(column, value.into()));
+        self
+    }
+
+    pub fn eq_opt<V: Into<Value>>(self, column: &'static str, value: Option<V>) -> Self {
+        match value {
+            Some(v) => self.eq(column, v),
+            None => self,
+        }
+    }
+
+    /// 全文搜索，见 [`Backend`]
+    pub fn web_search(mut self, backend: Backend, key: Option<&str>) -> Self {
+        if let Some(k) = key.map(str::trim).filter(|k| !k.is_empty()) {
+            self.conds.push(Cond::WebSearch(backend, k.to_string()));
+        }
+        self
+    }
+
+    /// 标题包含，% 和 _ 按普通字符处理
+    pub fn title_like(mut self, key: Option<&str>) -> Self {
+        if let Some(k) = key.map(str::trim).filter(|k| !k.is_empty()) {
+            self.conds.push(Cond::TitleLike(format!(
+                "%{}%",
+                escape_like(&k.to_lowercase())
+            )));
+        }
+        self
+    }
+
+    fn push_where(&self, qb: &mut QueryBuilder<'static, Postgres>) {
+        for (i, cond) in self.conds.iter().enumerate() {
+            qb.push(if i == 0 { " where " } else { " and " });
+            match cond {
+                Cond::Eq(column, Value::Int(v)) => {
+                    qb.push(*column).push("=").push_bind(*v);
+                }
+                Cond::Eq(column, Value::SmallInt(v)) => {
+                    qb.push(*column).push("=").push_bind(*v);
+                }
+                Cond::WebSearch(backend, k) => {
+                    backend.push_match(qb, self.table, k);
+                }
+                Cond::TitleLike(k) => {
+                    qb.push("lower(title) like ").push_bind(k.clone());
+                }
+            }
+        }
+    }
+
+    /// select count(*) as total ...
+    pub fn count(&self) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new("select count(*) as total from ");
+        qb.push(self.table);
+        self.push_where(&mut qb);
+        qb
+    }
+
+    pub fn select(
+        &self,
+        columns: &'static str,
+        order_by: &'static str,
+        limit: i64,
+        offset: i64,
+    ) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new("select ");
+        qb.push(columns).push(" from ").push(self.table);
+        self.push_where(&mut qb);
+        qb.push(" order by ")
+            .push(order_by)
+            .push(" limit ")
+            .push_bind(limit)
+            .push(" offset ")
+            .push_bind(offset);
+        qb
+    }
+}
+
+/// 分页的 offset，page 从 1 开始
+pub fn offset(page: u32, size: u8) -> i64 {
+    page.saturating_sub(1) as i64 * size as i64
+}
+
+pub fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
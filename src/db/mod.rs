@@ -1,3 +1,31 @@
+pub mod filter;
+pub mod search;
+
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::PgPool;
+
+/// migrations 目录下的数据库迁移，编译进程序
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// 执行还没有执行过的迁移，返回这次执行的版本
+pub async fn migrate(db: &PgPool) -> anyhow::Result<Vec<i64>> {
+    let applied: Vec<i64> = {
+        let mut conn = db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        conn.list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect()
+    };
+    MIGRATOR.run(db).await?;
+    Ok(MIGRATOR
+        .iter()
+        .map(|m| m.version)
+        .filter(|v| !applied.contains(v))
+        .collect())
+}
+
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct Total {
     pub total: i64,
@@ -0,0 +1,149 @@
+//! 全文搜索的方式，在配置文件 `[search]` 中设置
+//!
+//! * `jieba`：用 search_ti 列和它的索引。没有 pg_jieba 时 jiebacfg 是 simple 的副本。
+//! * `simple`、`english`：查询时用对应的配置生成 tsvector，用不到索引，适合内容不多的网站。
+//! * `trgm`：整个搜索词用 ilike 匹配，不分词，装了 pg_trgm 时迁移会建三元组索引。
+//!
+//! 这里生成的 SQL 片段只包含写死的表名和字段名，配置名和搜索词都用参数绑定。
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+
+use super::filter::escape_like;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Jieba,
+    Simple,
+    English,
+    Trgm,
+}
+
+impl Backend {
+    /// 文本搜索配置，trgm 没有
+    pub fn ts_config(&self) -> Option<&'static str> {
+        match self {
+            Backend::Jieba => Some("jiebacfg"),
+            Backend::Simple => Some("simple"),
+            Backend::English => Some("english"),
+            Backend::Trgm => None,
+        }
+    }
+
+    /// 参与搜索的文字，和 search_ti 用的字段相同
+    pub fn document(table: &str) -> &'static str {
+        match table {
+            "hu" => {
+                "(coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '') || ' ' || coalesce(body, '') || ' ' || coalesce(body2, ''))"
+            }
+            "xi" => "(coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(body, ''))",
+            "book" => {
+                "(coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, '') || ' ' || coalesce(body, '') || ' ' || coalesce(body2, '') || ' ' || coalesce(log, ''))"
+            }
+            "image" => "(coalesce(title, '') || ' ' || coalesce(tags, '') || ' ' || coalesce(brief, ''))",
+            _ => "coalesce(title, '')",
+        }
+    }
+
+    /// 和搜索词比较的 tsvector 或者文字
+    fn push_target(&self, qb: &mut QueryBuilder<'static, Postgres>, table: &str) {
+        match self {
+            Backend::Jieba => {
+                qb.push("search_ti");
+            }
+            Backend::Trgm => {
+                qb.push(Backend::document(table));
+            }
+            _ => {
+                qb.push("to_tsvector(")
+                    .push_bind(self.ts_config().unwrap_or("simple"))
+                    .push("::regconfig, ")
+                    .push(Backend::document(table))
+                    .push(")");
+            }
+        }
+    }
+
+    fn push_query(cfg: &'static str, qb: &mut QueryBuilder<'static, Postgres>, key: &str) {
+        qb.push("websearch_to_tsquery(")
+            .push_bind(cfg)
+            .push("::regconfig, ")
+            .push_bind(key.to_string())
+            .push(")");
+    }
+
+    /// 搜索条件
+    pub fn push_match(&self, qb: &mut QueryBuilder<'static, Postgres>, table: &str, key: &str) {
+        self.push_target(qb, table);
+        match self.ts_config() {
+            Some(cfg) => {
+                qb.push(" @@ ");
+                Backend::push_query(cfg, qb, key);
+            }
+            None => {
+                qb.push(" ilike ").push_bind(pattern(key));
+            }
+        }
+    }
+
+    /// 相关度，trgm 标题匹配的排在前面
+    pub fn push_rank(&self, qb: &mut QueryBuilder<'static, Postgres>, table: &str, key: &str) {
+        match self.ts_config() {
+            Some(cfg) => {
+                qb.push("ts_rank(");
+                self.push_target(qb, table);
+                qb.push(", ");
+                Backend::push_query(cfg, qb, key);
+                qb.push(")");
+            }
+            None => {
+                qb.push("(case when title ilike ")
+                    .push_bind(pattern(key))
+                    .push(" then 1 else 0.5 end)::real");
+            }
+        }
+    }
+
+    /// doc 中的摘要，用 start、stop 标出搜索词
+    pub fn push_headline(
+        &self,
+        qb: &mut QueryBuilder<'static, Postgres>,
+        doc: &'static str,
+        key: &str,
+        start: &str,
+        stop: &str,
+    ) {
+        match self.ts_config() {
+            Some(cfg) => {
+                qb.push("ts_headline(")
+                    .push_bind(cfg)
+                    .push("::regconfig, ")
+                    .push(doc)
+                    .push(", ");
+                Backend::push_query(cfg, qb, key);
+                qb.push(", ").push_bind(format!(
+                    "StartSel={}, StopSel={}, MaxFragments=2, MaxWords=30, MinWords=10, FragmentDelimiter=\" … \"",
+                    start, stop
+                ));
+                qb.push(")");
+            }
+            None => {
+                // 从第一次出现的地方往前一点开始，搜索词由调用者标出
+                qb.push("substr(")
+                    .push(doc)
+                    .push(", greatest(strpos(lower(")
+                    .push(doc)
+                    .push("), lower(")
+                    .push_bind(key.to_string())
+                    .push(")) - 40, 1), 160)");
+            }
+        }
+    }
+}
+
+/// trgm 的 like 模式
+fn pattern(key: &str) -> String {
+    format!("%{}%", escape_like(key))
+}
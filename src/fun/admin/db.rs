@@ -0,0 +1,179 @@
+use crate::db::filter::escape_like;
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+use chrono::{DateTime, Utc};
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Stats {
+    pub users: i64,
+    pub users_active: i64,
+    pub users_week: i64,
+    pub hu: i64,
+    pub xi: i64,
+    pub book: i64,
+    pub image: i64,
+    pub comment: i64,
+    pub message: i64,
+    pub content_week: i64,
+    pub good_wait: i64,
+}
+
+pub async fn db_stats(ctx: &WebContext) -> anyhow::Result<Stats> {
+    let rec = sqlx::query_as!(
+        Stats,
+        r#"select
+             (select count(*) from users) as "users!",
+             (select count(*) from users where i_role > 0) as "users_active!",
+             (select count(*) from users where created_at > now() - interval '7 days') as "users_week!",
+             (select count(*) from hu) as "hu!",
+             (select count(*) from xi) as "xi!",
+             (select count(*) from book) as "book!",
+             (select count(*) from image) as "image!",
+             (select count(*) from hu_comment)
+               + (select count(*) from xi_comment)
+               + (select count(*) from book_comment) as "comment!",
+             (select count(*) from message) as "message!",
+             (select count(*) from hu where created_at > now() - interval '7 days')
+               + (select count(*) from xi where created_at > now() - interval '7 days')
+               + (select count(*) from book where created_at > now() - interval '7 days') as "content_week!",
+             (select count(*) from hu where i_good=1 and good=0)
+               + (select count(*) from xi where i_good=1 and good=0)
+               + (select count(*) from book where i_good=1 and good=0) as "good_wait!""#
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct UserRow {
+    pub id: i32,
+    pub name: String,
+    pub email: String,
+    pub i_role: i16,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 按用户名或邮箱搜索
+pub async fn db_users(
+    ctx: &WebContext,
+    key: Option<&str>,
+    size: i64,
+    offset: i64,
+) -> anyhow::Result<(i64, Vec<UserRow>)> {
+    let key = key
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(|k| format!("%{}%", escape_like(k)));
+    let total = sqlx::query_scalar!(
+        r#"select count(*) as "total!" from users
+           where $1::text is null
+              or name collate "C" ilike $1
+              or email collate "C" ilike $1"#,
+        key
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    let rows = sqlx::query_as!(
+        UserRow,
+        r#"select user_id as id, name, email, i_role, created_at
+           from users
+           where $1::text is null
+              or name collate "C" ilike $1
+              or email collate "C" ilike $1
+           order by user_id desc
+           limit $2 offset $3"#,
+        key,
+        size,
+        offset
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok((total, rows))
+}
+
+/// 申请推荐（i_good=1）还没有推荐的内容
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct GoodWait {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub user_name: String,
+    pub i_public: i16,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn db_good_wait(ctx: &WebContext) -> anyhow::Result<Vec<GoodWait>> {
+    let rows = sqlx::query_as!(
+        GoodWait,
+        r#"select kind as "kind!", id as "id!", title as "title!", user_name as "user_name!",
+                  i_public as "i_public!", created_at as "created_at!"
+           from (
+             select 'hu' as kind, hu_id as id, title, user_name, i_public, created_at
+               from hu where i_good=1 and good=0
+             union all
+             select 'xi', xi_id, title, user_name, i_public, created_at
+               from xi where i_good=1 and good=0
+             union all
+             select 'book', book_id, title, user_name, i_public, created_at
+               from book where i_good=1 and good=0
+           ) t
+           order by created_at"#
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+/// 拒绝推荐申请
+pub async fn db_good_reject(ctx: &WebContext, kind: Kind, id: i32) -> anyhow::Result<bool> {
+    let sql = match kind {
+        Kind::Hu => "update hu set i_good=0 where hu_id=$1",
+        Kind::Xi => "update xi set i_good=0 where xi_id=$1",
+        Kind::Book => "update book set i_good=0 where book_id=$1",
+    };
+    let rows = sqlx::query(sql)
+        .bind(id)
+        .execute(&ctx.db)
+        .await?
+        .rows_affected();
+    Ok(rows == 1)
+}
+
+/// 文章、微博、好书的评论
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct CommentRow {
+    pub kind: String,
+    pub id: i32,
+    pub obj_id: i32,
+    pub user_id: i32,
+    pub user_name: String,
+    pub i_public: i16,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn db_recent_comments(ctx: &WebContext, size: i64) -> anyhow::Result<Vec<CommentRow>> {
+    let rows = sqlx::query_as!(
+        CommentRow,
+        r#"select kind as "kind!", id as "id!", obj_id as "obj_id!", user_id as "user_id!",
+                  user_name as "user_name!", i_public as "i_public!", body as "body!",
+                  created_at as "created_at!"
+           from (
+             select 'hu' as kind, id, obj_id, user_id, user_name, i_public, body, created_at
+               from hu_comment
+             union all
+             select 'xi', id, obj_id, user_id, user_name, i_public, body, created_at
+               from xi_comment
+             union all
+             select 'book', id, obj_id, user_id, user_name, i_public, body, created_at
+               from book_comment
+           ) t
+           order by created_at desc
+           limit $1"#,
+        size
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
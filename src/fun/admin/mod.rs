@@ -0,0 +1,424 @@
+//! 网站管理
+//!
+//! 每一栏需要对应的权限：用户管理、推荐、评论管理、修改所有内容。
+
+pub mod db;
+
+use axum::extract::{Form, MatchedPath, Path, Query, State};
+use axum::response::Redirect;
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::db::filter;
+use crate::fun::kind::Kind;
+use crate::fun::layout::Html;
+use crate::fun::rerender::Rerender;
+use crate::fun::user::db::db_set_role;
+use crate::fun::user::{get_user_can, get_user_from, Cap, Role, SessUser};
+use crate::fun::widget::*;
+use crate::fun::{book, comment, hu, xi};
+use crate::http::types::Page;
+use crate::http::{error::AppError, Result, WebContext};
+
+use db::*;
+
+const USER_PAGE_SIZE: u8 = 50;
+const COMMENT_SIZE: i64 = 100;
+/// 每张表最多显示的变化记录
+const RERENDER_SHOW: usize = 100;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/admin", get(admin_index))
+        .route("/admin/user", get(user_list))
+        .route("/admin/user/role/:id", post(user_role))
+        .route("/admin/user/disable/:id", get(user_disable))
+        .route("/admin/good", get(good_list))
+        .route("/admin/good/:kind/ok/:id", get(good_ok))
+        .route("/admin/good/:kind/no/:id", get(good_no))
+        .route("/admin/comment", get(comment_list))
+        .route("/admin/rerender", get(rerender_page).post(rerender_start))
+}
+
+const NAV: [(&str, &str, Option<Cap>); 5] = [
+    ("/admin", "统计", None),
+    ("/admin/user", "用户", Some(Cap::ManageUser)),
+    ("/admin/good", "推荐申请", Some(Cap::Recommend)),
+    ("/admin/comment", "最新评论", Some(Cap::ModerateComment)),
+    ("/admin/rerender", "重新生成", Some(Cap::EditAll)),
+];
+
+/// 有任何一项管理权限
+pub fn is_staff(user: &SessUser) -> bool {
+    NAV.iter()
+        .filter_map(|(_, _, cap)| *cap)
+        .any(|cap| user.can(cap))
+}
+
+fn admin_nav(path: &str, user: &SessUser) -> Markup {
+    let items = NAV
+        .iter()
+        .filter(|(_, _, cap)| cap.map_or(true, |cap| user.can(cap)));
+    html! {
+        ul class="nav nav-pills justify-content-center m-3" {
+            @for (url, txt, _) in items {
+                li class="nav-item" {
+                    @if path == *url {
+                        a class="nav-link active" href=(url) {(txt)}
+                    } @else {
+                        a class="nav-link" href=(url) {(txt)}
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn admin_page(ctx: &WebContext, user: &SessUser, title: &str, path: &str, main: Markup) -> Page {
+    let main = html! {
+        (admin_nav(path, user))
+        div class="container" {
+            div class="row justify-content-center" {
+                div class="col col-md-12 border shadow-lg p-3 mb-5 bg-body rounded" {
+                    (main)
+                }
+            }
+        }
+    };
+    Html::new(title, main).path(Some(path)).page(ctx)
+}
+
+async fn admin_index(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    if !is_staff(&user) {
+        return Err(AppError::Unauthorized);
+    }
+    let s = db_stats(&ctx).await?;
+    let items = [
+        ("用户", s.users),
+        ("已激活用户", s.users_active),
+        ("最近7天新用户", s.users_week),
+        ("文章", s.hu),
+        ("微博", s.xi),
+        ("好书", s.book),
+        ("图片", s.image),
+        ("评论", s.comment),
+        ("站内短信", s.message),
+        ("最近7天新内容", s.content_week),
+        ("等待推荐", s.good_wait),
+    ];
+    let main = html! {
+        div class="row row-cols-2 row-cols-md-4 g-3 m-2" {
+            @for (name, total) in items {
+                div class="col" {
+                    div class="border rounded p-3 text-center" {
+                        div class="fs-3" {(total)}
+                        div class="text-secondary" {(name)}
+                    }
+                }
+            }
+        }
+    };
+    Ok(admin_page(&ctx, &user, "网站管理", path.as_str(), main))
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct UserArgs {
+    key: Option<String>,
+    page: Option<u32>,
+}
+
+async fn user_list(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    args: Option<Query<UserArgs>>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let admin = get_user_can(&session, Cap::ManageUser).await?;
+    let Query(args) = args.unwrap_or_default();
+    let page = args.page.unwrap_or(1).max(1);
+    let key = args.key.unwrap_or_default();
+    let (total, users) = db_users(
+        &ctx,
+        Some(key.as_str()),
+        USER_PAGE_SIZE as i64,
+        filter::offset(page, USER_PAGE_SIZE),
+    )
+    .await?;
+    let url = if key.is_empty() {
+        "/admin/user".to_string()
+    } else {
+        format!("/admin/user?key={}", urlencoding::encode(&key))
+    };
+    let main = html! {
+        form action="/admin/user" method="get" class="d-flex m-2" {
+            input class="form-control me-2" type="search" name="key" value=(key) placeholder="用户名或邮箱";
+            button class="btn btn-outline-success flex-shrink-0" type="submit" {"搜索"}
+        }
+        div class="m-2 text-secondary" {"共 " (total) " 个用户"}
+        @for u in users {
+            div class="row border-bottom m-2 p-2 align-items-center" {
+                div class="col col-md-1" {(u.id)}
+                div class="col col-md-2" {(u.name)}
+                div class="col col-md-3 text-break" {(u.email)}
+                div class="col col-md-2" {(show_date(u.created_at))}
+                div class="col col-md-4" {
+                    @if u.id == admin.id {
+                        (Role::from(u.i_role).name())
+                    } @else {
+                        form action={"/admin/user/role/" (u.id)} method="post" class="d-flex" {
+                            select class="form-select form-select-sm me-2" name="role" {
+                                @for r in Role::ALL {
+                                    option value=(r as i16) selected[r as i16 == u.i_role] {(r.name())}
+                                }
+                            }
+                            button class="btn btn-sm btn-outline-primary flex-shrink-0 me-2" type="submit" {"修改"}
+                            @if Role::from(u.i_role).active() {
+                                @let cfm = format!("javascript:if(confirm('确实要停用吗?'))location='/admin/user/disable/{}'", u.id);
+                                a class="btn btn-sm btn-outline-danger flex-shrink-0" href=(cfm) {"停用"}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (pager(&url, total, USER_PAGE_SIZE, page))
+    };
+    Ok(admin_page(&ctx, &admin, "用户管理", path.as_str(), main))
+}
+
+#[derive(Deserialize, Debug)]
+struct RoleInput {
+    role: i16,
+}
+
+async fn set_role(ctx: &WebContext, admin: &SessUser, id: i32, role: i16) -> Result<Redirect> {
+    // 不能修改自己的权限，避免没有管理员
+    if id == admin.id || !Role::ALL.iter().any(|r| *r as i16 == role) {
+        return Err(AppError::InvalidArg("role".to_string()));
+    }
+    if !db_set_role(ctx, id, role).await? {
+        return Err(AppError::NotFound("user"));
+    }
+    Ok(Redirect::to("/admin/user"))
+}
+
+async fn user_role(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path(id): Path<i32>,
+    Form(input): Form<RoleInput>,
+) -> Result<Redirect> {
+    let admin = get_user_can(&session, Cap::ManageUser).await?;
+    set_role(&ctx, &admin, id, input.role).await
+}
+
+async fn user_disable(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path(id): Path<i32>,
+) -> Result<Redirect> {
+    let admin = get_user_can(&session, Cap::ManageUser).await?;
+    set_role(&ctx, &admin, id, Role::Disabled as i16).await
+}
+
+async fn good_list(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_can(&session, Cap::Recommend).await?;
+    let rows = db_good_wait(&ctx).await?;
+    let main = html! {
+        @if rows.is_empty() {
+            div class="m-2 text-secondary" {"没有推荐申请"}
+        }
+        @for g in rows {
+            @if let Some(kind) = Kind::from_path(&g.kind) {
+                div class="row border-bottom m-2 p-2 align-items-center" {
+                    div class="col col-md-1" {(kind.name())}
+                    div class="col col-md-5" {
+                        a href=(kind.view_url(g.id)) {(g.title)}
+                        @if g.i_public != 1 {
+                            span class="badge bg-secondary ms-2" {"未公布"}
+                        }
+                    }
+                    div class="col col-md-2" {(g.user_name)}
+                    div class="col col-md-2" {(show_date(g.created_at))}
+                    div class="col col-md-2" {
+                        a class="btn btn-sm btn-outline-primary me-2" href={"/admin/good/" (g.kind) "/ok/" (g.id)} {"推荐"}
+                        a class="btn btn-sm btn-outline-secondary" href={"/admin/good/" (g.kind) "/no/" (g.id)} {"拒绝"}
+                    }
+                }
+            }
+        }
+    };
+    Ok(admin_page(&ctx, &user, "推荐申请", path.as_str(), main))
+}
+
+async fn good_ok(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+) -> Result<Redirect> {
+    get_user_can(&session, Cap::Recommend).await?;
+    let kind = Kind::from_path(&kind).ok_or(AppError::NotFound("kind"))?;
+    let ok = match kind {
+        Kind::Hu => hu::db::db_good(&ctx, id, 1).await?,
+        Kind::Xi => xi::db::db_good(&ctx, id, 1).await?,
+        Kind::Book => book::db::db_good(&ctx, id, 1).await?,
+    };
+    if !ok {
+        return Err(AppError::NotFound("good"));
+    }
+    Ok(Redirect::to("/admin/good"))
+}
+
+async fn good_no(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+) -> Result<Redirect> {
+    get_user_can(&session, Cap::Recommend).await?;
+    let kind = Kind::from_path(&kind).ok_or(AppError::NotFound("kind"))?;
+    if !db_good_reject(&ctx, kind, id).await? {
+        return Err(AppError::NotFound("good"));
+    }
+    Ok(Redirect::to("/admin/good"))
+}
+
+async fn comment_list(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_can(&session, Cap::ModerateComment).await?;
+    let rows = db_recent_comments(&ctx, COMMENT_SIZE).await?;
+    let back = urlencoding::encode("/admin/comment");
+    let main = html! {
+        @for c in rows {
+            @if let Some(kind) = Kind::from_path(&c.kind) {
+                div class="border-bottom m-2 p-2" {
+                    div class="d-flex text-secondary small" {
+                        span class="me-3" {(kind.name())}
+                        a class="me-3" href={(kind.view_url(c.obj_id)) "#start"} {"查看原文"}
+                        span class="me-3" {(c.user_name)}
+                        span class="me-3" {(show_time(c.created_at))}
+                        @if c.i_public == 0 {
+                            span class="badge bg-secondary me-3" {"私有"}
+                        } @else if c.i_public == comment::PENDING {
+                            a class="badge bg-warning text-dark me-3" href="/my/comment" {"等待审核"}
+                        } @else {
+                            a class="me-3" href={"/my/" (c.kind) "/comment/hide/" (c.id)} {"设为私有"}
+                        }
+                        @let cfm = format!("javascript:if(confirm('确实要删除吗?'))location='/my/{}/comment/rm/{}?url={}'", c.kind, c.id, back);
+                        a href=(cfm) {"删除"}
+                    }
+                    div class="text-break" {(c.body)}
+                }
+            }
+        }
+    };
+    Ok(admin_page(&ctx, &user, "最新评论", path.as_str(), main))
+}
+
+async fn rerender_page(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_can(&session, Cap::EditAll).await?;
+    let r = ctx.rerender.report();
+    let main = html! {
+        p class="m-2 text-secondary" {
+            "Markdown 的处理改了以后，重新生成已保存的文章、好书、微博、评论和站内短信的 HTML。"
+            "只写入有变化的记录，不改更新时间。先检查可以看到哪些记录会变。"
+        }
+        form action="/admin/rerender" method="post" class="m-2" {
+            button class="btn btn-outline-primary me-2" type="submit" name="dry_run" value="1" disabled[r.running] {"检查"}
+            button class="btn btn-outline-danger" type="submit" disabled[r.running] {"重新生成"}
+        }
+        @if let Some(started) = r.started_at {
+            div class="m-2" {
+                (if r.dry_run { "检查" } else { "重新生成" })
+                "开始于 " (show_time(started))
+                @if let Some(finished) = r.finished_at {
+                    "，结束于 " (show_time(finished))
+                }
+                @if r.running {
+                    span class="badge bg-warning text-dark ms-2" {"进行中"}
+                } @else {
+                    "，" (if r.dry_run { "会变的" } else { "变化的" }) "记录 " (r.changed()) " 条"
+                    @if r.skipped() > 0 {
+                        "，正在被修改跳过的 " (r.skipped()) " 条"
+                    }
+                }
+            }
+        }
+        @if let Some(ref error) = r.error {
+            (tip(error))
+        }
+        @for t in &r.tables {
+            div class="border-bottom m-2 p-2" {
+                div class="row align-items-center" {
+                    div class="col col-md-2" {(t.table)}
+                    div class="col col-md-6" {
+                        div class="progress" {
+                            div class="progress-bar" style={"width: " (t.scanned * 100 / t.total.max(1)) "%"} {}
+                        }
+                    }
+                    div class="col col-md-2" {(t.scanned) " / " (t.total)}
+                    div class="col col-md-2" {
+                        "变化 " (t.changed.len())
+                        @if !t.skipped.is_empty() {
+                            "，跳过 " (t.skipped.len())
+                        }
+                    }
+                }
+                @if !t.changed.is_empty() {
+                    div class="small text-secondary mt-2 text-break" {
+                        @for c in t.changed.iter().take(RERENDER_SHOW) {
+                            @match (Kind::from_path(t.table), c.key.parse::<i32>()) {
+                                (Some(kind), Ok(id)) => a class="me-3" href=(kind.view_url(id)) title=(c.columns.join(", ")) {(c.key)},
+                                _ => span class="me-3" title=(c.columns.join(", ")) {(c.key)},
+                            }
+                        }
+                        @if t.changed.len() > RERENDER_SHOW {
+                            "……"
+                        }
+                    }
+                }
+            }
+        }
+        @if r.running {
+            script { "setTimeout(function(){location.reload()}, 2000);" }
+        }
+    };
+    Ok(admin_page(&ctx, &user, "重新生成", path.as_str(), main))
+}
+
+#[derive(Deserialize, Debug)]
+struct RerenderInput {
+    dry_run: Option<String>,
+}
+
+async fn rerender_start(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Form(input): Form<RerenderInput>,
+) -> Result<Redirect> {
+    get_user_can(&session, Cap::EditAll).await?;
+    // 已经在运行时直接看进度
+    Rerender::start(&ctx, input.dry_run.is_some());
+    Ok(Redirect::to("/admin/rerender"))
+}
@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
 
 use crate::db;
-use crate::fun::widget::list::{DbList, List, ListBy};
+use crate::fun::kind::{self, Kind};
+use crate::fun::widget::list::{DbList, List};
+use crate::fun::{publish, revision};
 use crate::http::WebContext;
 
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
@@ -39,6 +41,7 @@ pub struct Book {
     pub updated_at: Option<DateTime<Utc>>,
     pub good_at: Option<DateTime<Utc>>,
     pub click: i32,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 impl Book {
@@ -64,6 +67,7 @@ impl Book {
             brief: self.brief,
             body2: self.body2,
             log: self.log,
+            publish_at: publish::input_value(self.publish_at),
         }
     }
 }
@@ -87,6 +91,8 @@ pub struct Input {
     pub log: Option<String>,
     pub file: Option<String>,
     pub data: Option<Vec<u8>>,
+    /// 定时发布的时间，表单中的本地时间
+    pub publish_at: Option<String>,
 }
 
 impl Input {
@@ -102,6 +108,7 @@ impl Input {
         if self.body.is_empty() {
             error.push("请输入详细介绍".to_string());
         }
+        publish::check(self.i_public, &mut self.publish_at, &mut error);
         if !error.is_empty() {
             return Err(error);
         }
@@ -115,6 +122,16 @@ impl Input {
         self.log = self.log.as_ref().and_then(db::check_none);
         Ok(())
     }
+    /// 记入修改记录的内容
+    pub fn content(&self) -> revision::db::Content<'_> {
+        revision::db::Content {
+            title: &self.title,
+            brief: self.brief.as_deref(),
+            body: &self.body,
+            body2: self.body2.as_deref(),
+            log: self.log.as_deref(),
+        }
+    }
 }
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct BookSimple {
@@ -129,52 +146,27 @@ pub struct BookSimple {
     pub file: Option<String>,
     pub url: Option<String>,
     pub brief_html: Option<String>,
+    pub star: i32,
     pub created_at: DateTime<Utc>,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 pub async fn db_list<'a>(list: &List<'a>) -> anyhow::Result<(i64, DbList)> {
-    let offset = list.size as u32 * (list.page - 1);
-    let s_web = list.web_search_key.map_or("".to_string(), |k| {
-        format!("and search_ti @@ websearch_to_tsquery('{}') ", k)
-    });
-    let s_title = list.title_search_key.map_or("".to_string(), |k| {
-        format!("and lower(title) like '%{}%' ", k.to_lowercase())
-    });
-    let s_type = list
-        .i_type
-        .map_or("".to_string(), |tid| format!("and i_type={} ", tid));
-    let s_cat = list
-        .cat
-        .map_or("".to_string(), |cid| format!("and i_category={} ", cid));
-    let s_good = list
-        .good
-        .map_or("".to_string(), |gid| format!("and good={} ", gid));
-    let sql_where = match list.filter {
-        ListBy::All => "".to_owned(),
-        ListBy::UserId(id) => format!(
-            "where user_id={id} {} {} {} {} {}",
-            s_cat, s_type, s_good, s_web, s_title
-        ),
-        ListBy::AllPublic => format!(
-            "where i_public=1 {} {} {} {} {}",
-            s_cat, s_type, s_good, s_web, s_title
-        ),
-    };
-    let sql_total = format!("select count(*) as total from book {};", &sql_where);
-    let row = sqlx::query_as::<_, db::Total>(&sql_total)
+    let filter = list.filter("book");
+    let total = filter
+        .count()
+        .build_query_as::<db::Total>()
         .fetch_one(&list.ctx.db)
-        .await?;
-    let total = row.total;
-    let sql = format!(
-        r#"
-            select
-                book_id, title, i_public, i_type, i_category, good, created_at, user_name, file, brief_html, url, i_good
-            from book
-            {}
-            order by book_id desc limit {} offset {};"#,
-        sql_where, list.size, offset
-    );
-    let rows = sqlx::query_as::<_, BookSimple>(&sql)
+        .await?
+        .total;
+    let rows = filter
+        .select(
+            "book_id, title, i_public, i_type, i_category, good, created_at, user_name, file, brief_html, url, i_good, star, publish_at",
+            list.order_by(Kind::Book),
+            list.size as i64,
+            list.offset(),
+        )
+        .build_query_as::<BookSimple>()
         .fetch_all(&list.ctx.db)
         .await?;
     Ok((total, DbList::Book(rows)))
@@ -185,7 +177,7 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Book> {
         Book,
         r#"select book_id as id, user_id, user_name, title, author, body, html, body2, html2, log, log_html, brief, brief_html,
                i_category, i_type, i_public, i_good, star, version, tags, good, created_at, updated_at, good_at, click, 
-               price, src, file, press, url
+               price, src, file, press, url, publish_at
            from book where book_id=$1"#,
         id
     )
@@ -196,29 +188,20 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Book> {
 }
 
 pub async fn db_rm(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"delete from book
            where book_id=$1"#,
         id
     )
-    .execute(&ctx.db)
-    .await?
-    .rows_affected();
-
-    Ok(rows == 1)
-}
-
-pub async fn db_update_click(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
-    let rows = sqlx::query!(
-        r#"update book
-           set click=click+1
-           where book_id=$1"#,
-        id
-    )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows == 1 {
+        kind::db_rm_related(&mut tx, Kind::Book, id).await?;
+    }
+    tx.commit().await?;
     Ok(rows == 1)
 }
 
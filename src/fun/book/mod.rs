@@ -2,7 +2,6 @@
 
 use axum::{routing::get, Router};
 
-use crate::md;
 use anyhow::anyhow;
 use axum::extract::{MatchedPath, Multipart, Path, Query, State};
 use axum_session::Session;
@@ -12,12 +11,14 @@ use serde::Deserialize;
 use std::fs::File;
 
 use crate::config::CategoryType;
-use crate::fun::comment;
 use crate::fun::image::{create_file_name, get_format_and_ext, get_orientation, img_rotate};
+use crate::fun::kind::{self, Kind};
 use crate::fun::layout::{split, vsplit, Html};
-use crate::fun::user::{check_sudo, get_user_from, is_sudo_role, SessUser};
+use crate::fun::user::{check_cap, get_user_can, get_user_from, Cap, SessUser};
+use crate::fun::visit::Visitor;
 use crate::fun::widget::list::*;
 use crate::fun::widget::*;
+use crate::fun::{comment, embed, publish, revision, star, tag};
 use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
 //use futures_util::stream::StreamExt;
@@ -25,7 +26,11 @@ use crate::http::{error::AppError, Result, WebContext};
 pub mod db;
 use db::*;
 
-static PUBLIC_STATUS: [(i16, &str, &str); 2] = [(0, "craft", "草稿"), (1, "published", "公布")];
+static PUBLIC_STATUS: [(i16, &str, &str); 3] = [
+    (0, "craft", "草稿"),
+    (1, "published", "公布"),
+    (publish::SCHEDULED, "scheduled", "定时发布"),
+];
 
 pub fn router() -> Router<WebContext> {
     Router::new()
@@ -58,8 +63,9 @@ async fn book_my(
     let page = pagination.page;
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .pager(Some(path.as_str()))
+        .sort(pagination.sort)
         .show_cat_type_name()
-        .sudo(is_sudo_role(user.role));
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     Ok(Html::new("我的好书", main)
@@ -76,15 +82,19 @@ async fn book_pub(
 ) -> Result<Page> {
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .pager(Some(path.as_str()))
+        .sort(pagination.sort)
         .admin(false)
         .sudo(sudo)
         .show_cat_type_name();
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
-    let right = list_category_name(&ctx, false);
+    let right = html! {
+        (list_category_name(&ctx, false))
+        (tag::tag_cloud(&ctx).await?)
+    };
     let main = split(left, right);
     Ok(Html::new("好书", main).path(Some(path.as_str())).page(&ctx))
 }
@@ -95,7 +105,7 @@ async fn book_add_input(
     cat_type: Option<Query<CatType>>,
     path: MatchedPath,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::Publish).await?;
     let Query(cat_type) = cat_type.unwrap_or_default();
     let book: Input = Input {
         i_category: cat_type.cat,
@@ -213,7 +223,7 @@ fn input_form(ctx: &WebContext, book: &Input, error: ErrorMessage, edit: bool) -
                             div class="row mb-3 border-bottom" {
                                 label class="col-md-2 col-form-label text-md-end" for="tags" {"Tags："}
                                 div class="col-md-6" {
-                                    (TextInput::new("tags", "tags", false).value(book.tags.as_ref()).show())
+                                    (tag::tag_input(book.tags.as_ref()))
                                 }
                                 div class="col-md-4" {
                                 }
@@ -253,6 +263,7 @@ fn input_form(ctx: &WebContext, book: &Input, error: ErrorMessage, edit: bool) -
                                     @for (_i, p) in PUBLIC_STATUS.iter().enumerate() {
                                         (radio(p.1, "i_public", &p.0.to_string(), book.i_public == p.0, p.2))
                                     }
+                                    (publish::publish_input(book.publish_at.as_ref()))
                                 }
                             }
                             div class="text-center bg-light" {
@@ -276,7 +287,7 @@ async fn book_add_do(
     path: MatchedPath,
     multipart: Multipart,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::Publish).await?;
     let mut error: Vec<String> = vec![];
     let result = form_data(multipart, None).await;
     if result.is_err() {
@@ -290,8 +301,7 @@ async fn book_add_do(
     }
     let mut input = result.unwrap();
 
-    let check = input.check();
-    if let Err(e) = check {
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), false);
         return Ok(Html::new("新建好书", main)
             .my_huxi(&user)
@@ -310,6 +320,7 @@ async fn book_add_do(
             .page(&ctx));
     }
     let clone = input.clone();
+    let broken = embed::broken(&ctx, user.id, &input.content().markdown()).await?;
     let result = save(input, &user, &ctx, None).await;
     if result.is_err() {
         let main = input_form(
@@ -328,11 +339,14 @@ async fn book_add_do(
     let main = html! {
         div {
             (tip("好书添加成功"))
+            (embed::warning(&broken))
             div class="text-center" {
                 a href=(url) class="m-2" {"继续查看"}
                 a href="/my/book" class="m-2" {"显示列表"}
             }
-            (PreEscaped(redirect_script("/my/book")))
+            @if broken.is_empty() {
+                (PreEscaped(redirect_script("/my/book")))
+            }
         }
     };
     Ok(Html::new("好书添加成功", main)
@@ -359,6 +373,7 @@ async fn form_data(mut multipart: Multipart, file_src: Option<String>) -> anyhow
     let mut log: Option<String> = None;
     let mut file: Option<String> = file_src;
     let mut data: Option<Vec<u8>> = None;
+    let mut publish_at: Option<String> = None;
 
     while let Some(field) = multipart.next_field().await? {
         let f_name = field.name().unwrap_or("").to_string();
@@ -377,6 +392,7 @@ async fn form_data(mut multipart: Multipart, file_src: Option<String>) -> anyhow
             "body2" => body2 = Some(String::from_utf8(bytes)?),
             "brief" => brief = Some(String::from_utf8(bytes)?),
             "log" => log = Some(String::from_utf8(bytes)?),
+            "publish_at" => publish_at = Some(String::from_utf8(bytes)?),
             "i_public" => {
                 let tmp: i16 = std::str::from_utf8(&bytes)?.parse()?;
                 if tmp >= 0 {
@@ -430,6 +446,7 @@ async fn form_data(mut multipart: Multipart, file_src: Option<String>) -> anyhow
         data,
         press,
         url,
+        publish_at,
     })
 }
 
@@ -439,6 +456,20 @@ async fn save(
     ctx: &WebContext,
     id: Option<i32>,
 ) -> Result<(Option<String>, Option<u64>)> {
+    let tags = input.tags.clone();
+    let owner = match id {
+        Some(id) => match kind::db_owner(ctx, Kind::Book, id).await? {
+            Some(owner) => owner.user_id,
+            None => return Err(AppError::NotFound("book")),
+        },
+        None => user.id,
+    };
+    let embed::Rendered {
+        html,
+        html2,
+        brief_html,
+        log_html,
+    } = embed::render(ctx, Kind::Book, owner, &input.content()).await?;
     let mut new_file: Option<String> = None;
     if let Some(data) = input.data {
         let img_bytes = data;
@@ -465,29 +496,19 @@ async fn save(
         new_file = Some(image_new_file);
     }
 
-    let html = md::to_html(&input.body);
-    let mut html2: Option<String> = None;
-    let mut log_html: Option<String> = None;
-    let mut brief_html: Option<String> = None;
-    if let Some(ref text) = input.body2 {
-        html2 = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.log {
-        log_html = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.brief {
-        brief_html = Some(md::to_html(text));
-    }
     let i_good = input.i_good.map_or(0, |v| v);
+    let publish_at = publish::publish_at(input.i_public, input.publish_at.as_deref());
 
     // update
+    let mut tx = ctx.db.begin().await?;
     let sql_result: Option<u64> = if let Some(book_id) = id {
         if let Some(ref file) = new_file {
             let rows = sqlx::query!(
                 r#"update book
            set title=$1, body=$2, html=$3, i_category=$4, i_public=$5, i_type=$6, version=$7, tags=$8, body2=$9, html2=$10,
-               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15, price=$16, file=$17, author=$18, press=$19, url=$20
-           where book_id=$21"#,
+               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15, price=$16, file=$17, author=$18, press=$19, url=$20,
+               publish_at=$21
+           where book_id=$22"#,
                 input.title,
                 input.body,
                 html,
@@ -508,9 +529,10 @@ async fn save(
                 input.author,
                 input.press,
                 input.url,
+                publish_at,
                 book_id
             )
-            .execute(&ctx.db)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
             if rows > 0 {
@@ -522,8 +544,9 @@ async fn save(
             let rows = sqlx::query!(
                 r#"update book
            set title=$1, body=$2, html=$3, i_category=$4, i_public=$5, i_type=$6, version=$7, tags=$8, body2=$9, html2=$10,
-               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15, price=$16, author=$17, press=$18, url=$19
-           where book_id=$20"#,
+               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15, price=$16, author=$17, press=$18, url=$19,
+               publish_at=$20
+           where book_id=$21"#,
                 input.title,
                 input.body,
                 html,
@@ -543,9 +566,10 @@ async fn save(
                 input.author,
                 input.press,
                 input.url,
+                publish_at,
                 book_id
             )
-            .execute(&ctx.db)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
             if rows > 0 {
@@ -559,8 +583,9 @@ async fn save(
         let rec = sqlx::query!(
             r#"insert into book
              (user_id, user_name, title, body, html, i_category, i_public, i_type,
-               version, tags, body2, html2, log, log_html, i_good, brief, brief_html, price, src, file, author, press, url)
-           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23)
+               version, tags, body2, html2, log, log_html, i_good, brief, brief_html, price, src, file, author, press, url,
+               publish_at)
+           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)
            returning book_id"#,
             user.id,
             user.name,
@@ -585,11 +610,32 @@ async fn save(
             input.author,
             input.press,
             input.url,
+            publish_at,
         )
-        .fetch_one(&ctx.db)
+        .fetch_one(&mut *tx)
         .await?;
         Some(rec.book_id.try_into().unwrap())
     };
+    // 保存成功以后在同一个事务里更新标签，新建时 sql_result 是新的编号
+    let saved = match (id, sql_result) {
+        (Some(book_id), Some(_)) => Some(book_id),
+        (None, Some(new_id)) => Some(new_id as i32),
+        _ => None,
+    };
+    if let Some(book_id) = saved {
+        tag::db::db_set_tags(&mut tx, Kind::Book.table(), book_id, tags.as_deref()).await?;
+    }
+    tx.commit().await?;
+    if let Some(book_id) = saved {
+        let content = revision::db::Content {
+            title: &input.title,
+            brief: input.brief.as_deref(),
+            body: &input.body,
+            body2: input.body2.as_deref(),
+            log: input.log.as_deref(),
+        };
+        revision::db::db_add(ctx, Kind::Book, book_id, user, content).await?;
+    }
     Ok((new_file, sql_result))
 }
 
@@ -611,7 +657,7 @@ async fn book_edit_input(
 
 async fn check_owner(book_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if book_user_id == user.id || is_sudo_role(user.role) {
+    if user.can_manage(book_user_id, Cap::EditAll) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
@@ -638,8 +684,7 @@ async fn book_edit_do(
             .page(&ctx));
     }
     let mut input = result.unwrap();
-    let check = input.check();
-    if let Err(e) = check {
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), true);
         return Ok(Html::new("修改", main)
             .my_huxi(&user)
@@ -647,6 +692,7 @@ async fn book_edit_do(
             .page(&ctx));
     }
     let input_clone = input.clone();
+    let broken = embed::broken(&ctx, book.user_id, &input.content().markdown()).await?;
     let result = save(input, &user, &ctx, Some(id)).await;
     if result.is_err() {
         let main = input_form(
@@ -678,11 +724,14 @@ async fn book_edit_do(
     let main = html! {
         div {
             (tip("好书修改成功"))
+            (embed::warning(&broken))
             div class="text-center" {
                 a href=(url) class="m-2" {"继续查看"}
                 a href="/my/book" class="m-2" {"显示列表"}
             }
-            (PreEscaped(redirect_script("/my/book")))
+            @if broken.is_empty() {
+                (PreEscaped(redirect_script("/my/book")))
+            }
         }
     };
     Ok(Html::new("修改成功", main)
@@ -691,7 +740,7 @@ async fn book_edit_do(
         .page(&ctx))
 }
 
-fn view_url(id: i32) -> String {
+pub fn view_url(id: i32) -> String {
     format!("/book/view/{}/index.html", id)
 }
 
@@ -699,6 +748,7 @@ async fn book_view(
     State(ctx): State<WebContext>,
     session: Session<SessionPgPool>,
     Path(id): Path<i32>,
+    visitor: Visitor,
     path: MatchedPath,
 ) -> Result<Page> {
     let book = db_get_one(&ctx, id).await?;
@@ -709,18 +759,20 @@ async fn book_view(
     let mut admin = false;
     let mut sudo = false;
     let mut owner: Option<SessUser> = None;
-    if book.i_public > 0 {
+    if book.i_public == 1 {
         allow = true;
     }
 
     let result = get_user_from(&session).await;
+    let star =
+        star::star_button(&ctx, Kind::Book, book.id, book.star, result.as_ref().ok()).await?;
     if let Ok(user) = result {
         login = true;
-        if is_sudo_role(user.role) {
-            sudo = true;
+        if user.can(Cap::EditAll) {
             allow = true;
             admin = true;
         }
+        sudo = user.can(Cap::Recommend);
         if book.user_id == user.id {
             allow = true;
             admin = true;
@@ -730,9 +782,11 @@ async fn book_view(
 
     let left = if allow {
         let url = view_url(id);
-        let _ = db_update_click(&ctx, id).await;
+        if owner.is_none() {
+            ctx.visits.hit(Kind::Book, id, &visitor);
+        }
         let cms = comment::list_comment(&ctx, book.id, url.as_str(), login, admin, "book").await?;
-        content_html(&book, cms, &ctx, admin, sudo)
+        content_html(&book, cms, star, &ctx, admin, sudo)
     } else {
         tip("权限错误")
     };
@@ -764,7 +818,14 @@ async fn book_view(
     Ok(html.page(&ctx))
 }
 
-fn content_html(book: &Book, comment: Markup, ctx: &WebContext, admin: bool, sudo: bool) -> Markup {
+fn content_html(
+    book: &Book,
+    comment: Markup,
+    star: Markup,
+    ctx: &WebContext,
+    admin: bool,
+    sudo: bool,
+) -> Markup {
     let path = format!(
         "{}/{}",
         ctx.config.book.public_url,
@@ -795,6 +856,9 @@ fn content_html(book: &Book, comment: Markup, ctx: &WebContext, admin: bool, sud
                         @if book.updated_at.is_some() {
                             div class="col" {
                                 "更新：" (show_date(book.updated_at.unwrap()))
+                                @if admin {
+                                    a class="ms-2" href=(revision::list_url(Kind::Book, book.id)) {"修改记录"}
+                                }
                             }
                         }
                         div class="col" {
@@ -831,6 +895,17 @@ fn content_html(book: &Book, comment: Markup, ctx: &WebContext, admin: bool, sud
                         }
                         div class="col" {
                             "浏览：" (book.click)
+                            @if admin {
+                                a class="ms-2" href={"/my/visit/book/" (book.id)} {"统计"}
+                            }
+                        }
+                        div class="col" {
+                            "星标：" (star)
+                        }
+                        @if let Some(ref tags) = book.tags {
+                            div class="col" {
+                                "Tags: " (tag::tag_links(tags))
+                            }
                         }
                         @if admin {
                             div class="col" {
@@ -931,7 +1006,7 @@ async fn book_good(
     let user = check_owner(book.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 1).await?;
         if result_ok {
             main = tip("推荐成功");
@@ -953,7 +1028,7 @@ async fn book_good_cancel(
     let user = check_owner(book.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 0).await?;
         if result_ok {
             main = tip("取消推荐成功");
@@ -989,7 +1064,7 @@ async fn book_my_cat(
         .pager(Some(path.as_str()))
         .admin(true)
         .show_cat_type_name()
-        .sudo(is_sudo_role(user.role));
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     let title = format!("{} ", cat_name);
@@ -1011,7 +1086,7 @@ async fn book_my_cat2(
     let user = get_user_from(&session).await?;
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
 
     let cat_id = ctx.config.book.category.id(cat.as_str());
     let cid = cat.as_str();
@@ -1075,7 +1150,7 @@ async fn book_pub_cat(
         .category
         .name(cat.as_str())
         .ok_or_else(|| AppError::InvalidArg("cat name error".to_string()))?;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let left = html!(
         div class="" {
             @for ty in &ctx.config.book.content_type {
@@ -1142,7 +1217,7 @@ async fn book_my_cat_type(
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .cat(cat_id)
         .show_cat_type_name()
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .i_type(type_id)
         .pager(Some(path.as_str()));
     let (total, data) = db_list(&list).await?;
@@ -1181,7 +1256,7 @@ async fn book_pub_cat_type(
         .name(tid.as_str())
         .ok_or_else(|| AppError::InvalidArg("type name error".to_string()))?;
     let page_link = format!("/book/cat/{}/{}", cat, tid);
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
 
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .cat(cat_id)
@@ -1189,7 +1264,8 @@ async fn book_pub_cat_type(
         .sudo(sudo)
         .show_cat_type_name()
         .i_type(type_id)
-        .pager(Some(page_link.as_str()));
+        .pager(Some(page_link.as_str()))
+        .sort(pagination.sort);
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
 
@@ -1304,7 +1380,7 @@ async fn book_search(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.map_or(0, |u| u.role)))
+        .sudo(user.map_or(false, |u| u.can(Cap::Recommend)))
         .admin(false)
         .web_search_key(Some(key));
     let (total, data) = db_list(&list).await?;
@@ -1326,7 +1402,7 @@ async fn book_search_my(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .title_search_key(Some(key))
         .admin(true);
     let (total, data) = db_list(&list).await?;
@@ -2,6 +2,9 @@ use crate::http::WebContext;
 use crate::md;
 use chrono::{DateTime, Utc};
 
+/// i_public：0 私有，1 公布，2 等待审核
+pub const PENDING: i16 = 2;
+
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct Comment {
     pub id: i32,
@@ -13,6 +16,8 @@ pub struct Comment {
     pub html: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
+    /// 回复的评论
+    pub parent_id: Option<i32>,
 }
 
 impl Comment {
@@ -22,6 +27,8 @@ impl Comment {
             oid: Some(self.obj_id),
             url: None,
             body: self.body,
+            parent_id: self.parent_id,
+            website: None,
         }
     }
 }
@@ -32,6 +39,9 @@ pub struct Input {
     pub oid: Option<i32>, //obj id
     pub url: Option<String>,
     pub body: String,
+    pub parent_id: Option<i32>,
+    /// 隐藏的输入框，不是空的就是机器人
+    pub website: Option<String>,
 }
 impl Input {
     pub fn check(&self) -> std::result::Result<(), Vec<String>> {
@@ -51,38 +61,48 @@ pub async fn db_insert(
     user_id: i32,
     user_name: &str,
     input: &Input,
+    i_public: i16,
     ty: &str,
-) -> anyhow::Result<()> {
-    let html = md::to_html(input.body.as_str());
+) -> anyhow::Result<i32> {
+    let html = md::to_html(&ctx.config, md::Content::Comment, &input.body);
     let sql = format!(
         r#"insert into {}_comment
-             (user_id, user_name, obj_id, i_public, body, html)
-           values ($1, $2, $3, 1, $4, $5)
+             (user_id, user_name, obj_id, i_public, body, html, parent_id)
+           values ($1, $2, $3, $4, $5, $6, $7)
            returning id"#,
         ty
     );
-    let _rec = sqlx::query(sql.as_str())
+    let id: i32 = sqlx::query_scalar(sql.as_str())
         .bind(user_id)
         .bind(user_name)
         .bind(input.oid)
+        .bind(i_public)
         .bind(input.body.as_str())
         .bind(html)
+        .bind(input.parent_id)
         .fetch_one(&ctx.db)
         .await?;
-    Ok(())
+    Ok(id)
 }
 
-pub async fn db_update(ctx: &WebContext, id: i32, input: &Input, ty: &str) -> anyhow::Result<bool> {
-    let html = md::to_html(&input.body);
+pub async fn db_update(
+    ctx: &WebContext,
+    id: i32,
+    input: &Input,
+    i_public: i16,
+    ty: &str,
+) -> anyhow::Result<bool> {
+    let html = md::to_html(&ctx.config, md::Content::Comment, &input.body);
     let sql = format!(
         r#"update {}_comment
-           set body=$1, html=$2
-           where id=$3"#,
+           set body=$1, html=$2, i_public=$3
+           where id=$4"#,
         ty
     );
     let rows = sqlx::query(sql.as_str())
         .bind(input.body.as_str())
         .bind(html)
+        .bind(i_public)
         .bind(id)
         .execute(&ctx.db)
         .await?
@@ -104,7 +124,16 @@ pub async fn db_get_one(ctx: &WebContext, id: i32, ty: &str) -> anyhow::Result<C
     Ok(rec)
 }
 
+/// 删除以后，它的回复改为回复它的上一级
 pub async fn db_rm(ctx: &WebContext, id: i32, ty: &str) -> anyhow::Result<bool> {
+    let sql = format!(
+        r#"update {0}_comment
+           set parent_id=(select parent_id from {0}_comment where id=$1)
+           where parent_id=$1"#,
+        ty
+    );
+    let mut tx = ctx.db.begin().await?;
+    sqlx::query(sql.as_str()).bind(id).execute(&mut *tx).await?;
     let sql = format!(
         r#"delete from {}_comment
            where id=$1"#,
@@ -112,9 +141,10 @@ pub async fn db_rm(ctx: &WebContext, id: i32, ty: &str) -> anyhow::Result<bool>
     );
     let rows = sqlx::query(sql.as_str())
         .bind(id)
-        .execute(&ctx.db)
+        .execute(&mut *tx)
         .await?
         .rows_affected();
+    tx.commit().await?;
 
     Ok(rows == 1)
 }
@@ -146,3 +176,91 @@ pub async fn db_list(ctx: &WebContext, id: i32, ty: &str) -> anyhow::Result<Vec<
         .await?;
     Ok(rows)
 }
+
+/// 审核通过
+pub async fn db_approve(ctx: &WebContext, id: i32, ty: &str) -> anyhow::Result<bool> {
+    let sql = format!(
+        r#"update {}_comment
+           set i_public=1
+           where id=$1 and i_public=$2"#,
+        ty
+    );
+    let rows = sqlx::query(sql.as_str())
+        .bind(id)
+        .bind(PENDING)
+        .execute(&ctx.db)
+        .await?
+        .rows_affected();
+
+    Ok(rows == 1)
+}
+
+/// 最近 minutes 分钟内的评论数
+pub async fn db_recent_total(ctx: &WebContext, user_id: i32, minutes: i64) -> anyhow::Result<i64> {
+    let rec = sqlx::query!(
+        r#"select
+             (select count(*) from hu_comment where user_id=$1 and created_at > now() - make_interval(mins => $2))
+           + (select count(*) from xi_comment where user_id=$1 and created_at > now() - make_interval(mins => $2))
+           + (select count(*) from book_comment where user_id=$1 and created_at > now() - make_interval(mins => $2))
+           as "total!""#,
+        user_id,
+        minutes as i32
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec.total)
+}
+
+/// 有没有公布的评论
+pub async fn db_has_public(ctx: &WebContext, user_id: i32) -> anyhow::Result<bool> {
+    let rec = sqlx::query!(
+        r#"select exists (select 1 from hu_comment where user_id=$1 and i_public=1)
+               or exists (select 1 from xi_comment where user_id=$1 and i_public=1)
+               or exists (select 1 from book_comment where user_id=$1 and i_public=1)
+           as "yes!""#,
+        user_id
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec.yes)
+}
+
+/// 等待审核的评论和评论的内容
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Pending {
+    pub kind: String,
+    pub id: i32,
+    pub obj_id: i32,
+    pub user_name: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub title: String,
+}
+
+/// owner_id 为空时是所有的，不然是这个用户的内容下的
+pub async fn db_pending(ctx: &WebContext, owner_id: Option<i32>) -> anyhow::Result<Vec<Pending>> {
+    let rows = sqlx::query_as!(
+        Pending,
+        r#"select kind as "kind!", id as "id!", obj_id as "obj_id!", user_name as "user_name!",
+                  body as "body!", created_at as "created_at!", title as "title!"
+           from (
+             select 'hu' as kind, c.id, c.obj_id, c.user_name, c.body, c.created_at, t.title
+               from hu_comment c join hu t on t.hu_id=c.obj_id
+               where c.i_public=$1 and ($2::int is null or t.user_id=$2)
+             union all
+             select 'xi', c.id, c.obj_id, c.user_name, c.body, c.created_at, t.title
+               from xi_comment c join xi t on t.xi_id=c.obj_id
+               where c.i_public=$1 and ($2::int is null or t.user_id=$2)
+             union all
+             select 'book', c.id, c.obj_id, c.user_name, c.body, c.created_at, t.title
+               from book_comment c join book t on t.book_id=c.obj_id
+               where c.i_public=$1 and ($2::int is null or t.user_id=$2)
+           ) t
+           order by created_at"#,
+        PENDING,
+        owner_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
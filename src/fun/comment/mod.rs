@@ -1,9 +1,20 @@
 //! 评论模块
+//!
+//! 评论可以回复别的评论，显示成嵌套的讨论。有新评论时用站内信通知内容的作者，
+//! 有回复时通知被回复的人，配置文件 `[comment] email = true` 时同时发邮件。
+//!
+//! 提交的评论先检查：隐藏输入框有内容的丢掉，评论太频繁的拒绝，链接太多、
+//! 包含禁用词的等待审核，再按 `[comment] moderation` 决定是否需要审核。
+//! 有 ModerateComment 权限的可以审核所有评论，作者可以审核自己内容下的评论。
 
 mod db;
 
+pub use db::PENDING;
+
+use std::collections::{HashMap, HashSet};
+
 use axum::extract::{Form, MatchedPath, Path, Query, State};
-use axum::response::Redirect;
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::{
     routing::{get, post},
     Router,
@@ -13,45 +24,317 @@ use axum_session::Session;
 use axum_session_sqlx::SessionPgPool;
 use maud::{html, Markup, PreEscaped};
 
+use crate::config::Moderation;
+use crate::fun::kind::{self, Kind};
 use crate::fun::layout::Html;
-use crate::fun::user::{get_user_from, is_sudo_role, SessUser};
+use crate::fun::message;
+use crate::fun::user::db::{db_get_user, By};
+use crate::fun::user::{get_user_from, Cap, SessUser};
 use crate::fun::widget::*;
 use crate::http::types::Page;
-use crate::http::{error::AppError, Result, WebContext};
+use crate::http::{email, error::AppError, Result, WebContext};
 
 use db::*;
 
+/// 超过这么多层的回复不再缩进
+const MAX_DEPTH: usize = 4;
+
 pub fn router() -> Router<WebContext> {
     Router::new()
+        .route("/my/comment", get(pending_list))
         .route("/my/:ty/comment/add", post(add_do))
         .route("/my/:ty/comment/edit/:id", get(edit_input).post(edit_do))
         .route("/my/:ty/comment/hide/:id", get(hide))
         .route("/my/:ty/comment/rm/:id", get(rm))
+        .route("/my/:ty/comment/approve/:id", get(approve))
+        .route("/my/:ty/comment/reject/:id", get(reject))
+}
+
+/// ty 会拼到 SQL 里，只能是文章、微博、好书
+fn parse_ty(ty: &str) -> Result<Kind> {
+    Kind::from_path(ty).ok_or(AppError::NotFound("comment"))
 }
 
 async fn add_do(
     State(ctx): State<WebContext>,
     session: Session<SessionPgPool>,
     Path(ty): Path<String>,
-    Form(input): Form<Input>,
-) -> Redirect {
+    Form(mut input): Form<Input>,
+) -> Result<Response> {
     let user = get_user_from(&session).await;
     if user.is_err() {
-        return Redirect::to("/user/login");
+        return Ok(Redirect::to("/user/login").into_response());
     }
     let user = user.unwrap();
+    let kind = parse_ty(&ty)?;
     let check = input.check();
-    let url = input.url.as_ref().unwrap();
     let error_url = "/error";
-    if check.is_err() {
-        return Redirect::to(error_url);
+    let obj_id = match input.oid {
+        Some(oid) if check.is_ok() => oid,
+        _ => return Ok(Redirect::to(error_url).into_response()),
+    };
+    let url = input.url.as_deref().unwrap_or("/");
+    let url = url.split('#').next().unwrap_or(url).to_string();
+    // 机器人，当作成功
+    if input.website.as_deref().map_or(false, |w| !w.is_empty()) {
+        return Ok(Redirect::to(&url).into_response());
+    }
+    let owner = kind::db_owner(&ctx, kind, obj_id)
+        .await?
+        .ok_or(AppError::NotFound("comment"))?;
+    let i_public = match screen(&ctx, &user, owner.user_id, &input).await? {
+        Ok(i_public) => i_public,
+        Err(e) => {
+            let main = html! {
+                (tip(&e))
+                div class="text-center" {
+                    a href={(url) "#start"} {"返回"}
+                }
+            };
+            return Ok(Html::new("评论失败", main).page(&ctx).into_response());
+        }
+    };
+    // 只能回复同一个内容下的评论
+    let parent = match input.parent_id {
+        Some(pid) => db_get_one(&ctx, pid, &ty)
+            .await
+            .ok()
+            .filter(|p| p.obj_id == obj_id && p.i_public == 1),
+        None => None,
+    };
+    input.parent_id = parent.as_ref().map(|p| p.id);
+    let id = db_insert(&ctx, user.id, user.name.as_str(), &input, i_public, &ty).await;
+    let id = match id {
+        Ok(id) => id,
+        Err(_) => return Ok(Redirect::to(error_url).into_response()),
+    };
+
+    spawn_notify(&ctx, kind, id, None);
+
+    if i_public == PENDING {
+        let main = html! {
+            (tip("评论已提交，审核通过以后显示"))
+            div class="text-center" {
+                a href={(url) "#start"} {"返回"}
+            }
+        };
+        return Ok(Html::new("等待审核", main).page(&ctx).into_response());
+    }
+    Ok(Redirect::to(&format!("{}#comment-{}", url, id)).into_response())
+}
+
+/// 检查评论，返回 i_public，或者拒绝的原因
+async fn screen(
+    ctx: &WebContext,
+    user: &SessUser,
+    owner_id: i32,
+    input: &Input,
+) -> anyhow::Result<std::result::Result<i16, String>> {
+    let cfg = &ctx.config.comment;
+    if user.can(Cap::ModerateComment) {
+        return Ok(Ok(1));
+    }
+    if cfg.rate_count > 0
+        && db_recent_total(ctx, user.id, cfg.rate_minutes).await? >= cfg.rate_count
+    {
+        return Ok(Err(format!(
+            "评论太频繁，请 {} 分钟后再试",
+            cfg.rate_minutes
+        )));
+    }
+    // 作者在自己的内容下评论
+    if owner_id == user.id {
+        return Ok(Ok(1));
+    }
+    let body = input.body.to_lowercase();
+    let links = body.matches("http://").count() + body.matches("https://").count();
+    let banned = cfg
+        .banned_words
+        .iter()
+        .any(|w| !w.is_empty() && body.contains(&w.to_lowercase()));
+    if links > cfg.max_links || banned {
+        return Ok(Ok(PENDING));
+    }
+    let pending = match cfg.moderation {
+        Moderation::Auto => false,
+        Moderation::First => !db_has_public(ctx, user.id).await?,
+        Moderation::Always => true,
+    };
+    Ok(Ok(if pending { PENDING } else { 1 }))
+}
+
+/// 在后台发通知
+fn spawn_notify(ctx: &WebContext, kind: Kind, id: i32, approver: Option<i32>) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = notify(&ctx, kind, id, approver).await {
+            tracing::error!("comment notify: {:#}", e);
+        }
+    });
+}
+
+/// 以网站的名义通知内容的作者和被回复的人，不通知自己和审核的人。
+/// 等待审核时只告诉作者有评论要审核。
+async fn notify(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    approver: Option<i32>,
+) -> anyhow::Result<()> {
+    let c = db_get_one(ctx, id, kind.table()).await?;
+    let (owner_id, title) = match kind::db_owner(ctx, kind, c.obj_id).await? {
+        Some(owner) => (owner.user_id, owner.title),
+        None => return Ok(()),
+    };
+    let parent = match c.parent_id {
+        Some(pid) => db_get_one(ctx, pid, kind.table()).await.ok(),
+        None => None,
+    };
+    let mut to: Vec<(i32, String)> = Vec::new();
+    let link = if c.i_public == PENDING {
+        if owner_id != c.user_id {
+            to.push((owner_id, format!("《{}》有新评论等待审核", title)));
+        }
+        format!("{}/my/comment", ctx.config.host.www)
+    } else {
+        if let Some(p) = &parent {
+            if p.user_id != c.user_id && Some(p.user_id) != approver {
+                to.push((
+                    p.user_id,
+                    format!("{} 回复了你在《{}》的评论", c.user_name, title),
+                ));
+            }
+        }
+        if owner_id != c.user_id
+            && Some(owner_id) != approver
+            && parent.as_ref().map_or(true, |p| p.user_id != owner_id)
+        {
+            to.push((owner_id, format!("《{}》有新评论", title)));
+        }
+        format!(
+            "{}{}#comment-{}",
+            ctx.config.host.www,
+            kind.view_url(c.obj_id),
+            c.id
+        )
+    };
+    let quote: String = c.body.lines().map(|l| format!("> {}\n", l)).collect();
+    for (user_id, subject) in to {
+        let user = match db_get_user(ctx, By::Id(user_id)).await? {
+            Some(user) => user,
+            None => continue,
+        };
+        let sm = message::db::Input {
+            title: subject.clone(),
+            to_user_name: user.name.clone(),
+            body: format!("{}：\n\n{}\n[查看]({})\n", subject, quote, link),
+        };
+        if let Err(e) = message::db::db_notice(ctx, user.user_id, &sm).await {
+            tracing::warn!("comment notify message to {}: {:?}", user.name, e);
+        }
+        if ctx.config.comment.email {
+            let html = html! {
+                p {"您好，" (user.name)}
+                p {(subject) "："}
+                blockquote {(c.body)}
+                p { a href=(link) {(link)} }
+                p {(ctx.config.host.name)}
+            };
+            let send =
+                email::send_email(&ctx.config, &user.email, &subject, html.into_string()).await;
+            if let Err(e) = send {
+                tracing::warn!("comment notify email to {}: {}", user.name, e);
+            }
+        }
     }
-    let id = db_insert(&ctx, user.id, user.name.as_str(), &input, &ty).await;
-    if id.is_err() {
-        return Redirect::to(error_url);
+    Ok(())
+}
+
+/// 可以审核：有 ModerateComment 权限，或者是内容的作者
+async fn check_moderator(
+    ctx: &WebContext,
+    session: &Session<SessionPgPool>,
+    kind: Kind,
+    cm: &Comment,
+) -> Result<SessUser> {
+    let user = get_user_from(session).await?;
+    if user.can(Cap::ModerateComment) {
+        return Ok(user);
+    }
+    match kind::db_owner(ctx, kind, cm.obj_id).await? {
+        Some(owner) if owner.user_id == user.id => Ok(user),
+        _ => Err(AppError::Unauthorized),
     }
+}
 
-    Redirect::to(url)
+/// 等待审核的评论
+async fn pending_list(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let owner = if user.can(Cap::ModerateComment) {
+        None
+    } else {
+        Some(user.id)
+    };
+    let rows = db_pending(&ctx, owner).await?;
+    let main = html! {
+        div class="container" {
+            @if rows.is_empty() {
+                (tip("没有等待审核的评论"))
+            }
+            @for c in &rows {
+                @if let Some(kind) = Kind::from_path(&c.kind) {
+                    div class="border-bottom m-2 p-2" {
+                        div class="d-flex flex-wrap text-secondary small" {
+                            span class="me-3" {(kind.name())}
+                            a class="me-3" href={(kind.view_url(c.obj_id)) "#start"} {(c.title)}
+                            span class="me-3" {(c.user_name)}
+                            span class="me-3" {(show_time(c.created_at))}
+                            a class="me-3" href={"/my/" (c.kind) "/comment/approve/" (c.id)} {"通过"}
+                            a href={"/my/" (c.kind) "/comment/reject/" (c.id)}
+                                onclick="return confirm('拒绝并删除这条评论？')" {"拒绝"}
+                        }
+                        div class="text-break" style="white-space: pre-wrap" {(c.body)}
+                    }
+                }
+            }
+        }
+    };
+    Ok(Html::new("等待审核的评论", main)
+        .path(Some(path.as_str()))
+        .my_huxi(&user)
+        .page(&ctx))
+}
+
+async fn approve(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((ty, id)): Path<(String, i32)>,
+) -> Result<Redirect> {
+    let kind = parse_ty(&ty)?;
+    let cm = db_get_one(&ctx, id, &ty).await?;
+    let user = check_moderator(&ctx, &session, kind, &cm).await?;
+    if db_approve(&ctx, id, &ty).await? {
+        spawn_notify(&ctx, kind, id, Some(user.id));
+    }
+    Ok(Redirect::to("/my/comment"))
+}
+
+async fn reject(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((ty, id)): Path<(String, i32)>,
+) -> Result<Redirect> {
+    let kind = parse_ty(&ty)?;
+    let cm = db_get_one(&ctx, id, &ty).await?;
+    check_moderator(&ctx, &session, kind, &cm).await?;
+    if cm.i_public == PENDING {
+        db_rm(&ctx, id, &ty).await?;
+    }
+    Ok(Redirect::to("/my/comment"))
 }
 
 fn edit_form(_ctx: &WebContext, input: &Input, error: ErrorMessage, url: &str, ty: &str) -> Markup {
@@ -84,6 +367,7 @@ async fn edit_input(
     Path((ty, id)): Path<(String, i32)>,
     path: MatchedPath,
 ) -> Result<Page> {
+    parse_ty(&ty)?;
     let cm: Comment = db_get_one(&ctx, id, &ty).await?;
     let user = check_owner(cm.user_id, &session).await?;
     let de = urlencoding::decode(args.url.as_str());
@@ -104,7 +388,7 @@ struct UrlArgs {
 
 async fn check_owner(hu_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if hu_user_id == user.id || is_sudo_role(user.role) {
+    if user.can_manage(hu_user_id, Cap::ModerateComment) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
@@ -118,19 +402,39 @@ async fn edit_do(
     path: MatchedPath,
     Form(input): Form<Input>,
 ) -> Result<Page> {
+    let kind = parse_ty(&ty)?;
     let cm = db_get_one(&ctx, id, &ty).await?;
     let user = check_owner(cm.user_id, &session).await?;
-    let check = input.check();
+    let check = match input.check() {
+        Ok(()) => {
+            let owner = kind::db_owner(&ctx, kind, cm.obj_id)
+                .await?
+                .ok_or(AppError::NotFound("comment"))?;
+            screen(&ctx, &user, owner.user_id, &input)
+                .await?
+                .map_err(|e| vec![e])
+        }
+        Err(e) => Err(e),
+    };
     let url = input.url.as_ref().unwrap();
-    if let Err(e) = check {
-        let main = edit_form(&ctx, &input, Some(e), url, &ty);
-        return Ok(Html::new("修改评论", main)
-            .path(Some(path.as_str()))
-            .my_huxi(&user)
-            .page(&ctx));
-    }
+    let screened = match check {
+        Ok(screened) => screened,
+        Err(e) => {
+            let main = edit_form(&ctx, &input, Some(e), url, &ty);
+            return Ok(Html::new("修改评论", main)
+                .path(Some(path.as_str()))
+                .my_huxi(&user)
+                .page(&ctx));
+        }
+    };
+    // 修改以后要审核的重新等待审核，其他的保持原来的状态
+    let i_public = if screened == PENDING {
+        PENDING
+    } else {
+        cm.i_public
+    };
 
-    let update = db_update(&ctx, id, &input, &ty).await?;
+    let update = db_update(&ctx, id, &input, i_public, &ty).await?;
 
     if !update {
         let main = tip("数据错误");
@@ -139,6 +443,21 @@ async fn edit_do(
             .my_huxi(&user)
             .page(&ctx));
     }
+    if i_public == PENDING {
+        if cm.i_public != PENDING {
+            spawn_notify(&ctx, kind, id, None);
+        }
+        let main = html!(
+            (tip("修改已提交，审核通过以后显示"))
+            div class="text-center" {
+                a href={(url) "#start"} {"返回"}
+            }
+        );
+        return Ok(Html::new("等待审核", main)
+            .path(Some(path.as_str()))
+            .my_huxi(&user)
+            .page(&ctx));
+    }
     let main = html!(
         (tip("修改成功"))
         div class="text-center" {
@@ -158,6 +477,7 @@ async fn rm(
     Path((ty, id)): Path<(String, i32)>,
     path: MatchedPath,
 ) -> Result<Page> {
+    parse_ty(&ty)?;
     let cm = db_get_one(&ctx, id, &ty).await?;
     let user = check_owner(cm.user_id, &session).await?;
     let de = urlencoding::decode(args.url.as_str());
@@ -188,6 +508,7 @@ async fn hide(
     Path((ty, id)): Path<(String, i32)>,
     path: MatchedPath,
 ) -> Result<Page> {
+    parse_ty(&ty)?;
     let cm = db_get_one(&ctx, id, &ty).await?;
     let user = check_owner(cm.user_id, &session).await?;
     let result_ok = db_hide(&ctx, id, &ty).await?;
@@ -202,6 +523,123 @@ async fn hide(
         .page(&ctx))
 }
 
+/// 人看不到的输入框，机器人会填
+fn honeypot() -> Markup {
+    html! {
+        input type="text" name="website" class="d-none" tabindex="-1" autocomplete="off" aria-hidden="true";
+    }
+}
+
+/// 一条评论和它的回复
+struct Thread<'a> {
+    obj_id: i32,
+    url: &'a str,
+    login: bool,
+    admin: bool,
+    ty: &'a str,
+    names: HashMap<i32, &'a str>,
+    children: HashMap<Option<i32>, Vec<&'a Comment>>,
+}
+
+impl<'a> Thread<'a> {
+    fn new(
+        obj_id: i32,
+        url: &'a str,
+        login: bool,
+        admin: bool,
+        ty: &'a str,
+        data: &'a [Comment],
+    ) -> Self {
+        let ids: HashSet<i32> = data.iter().map(|c| c.id).collect();
+        let names = data.iter().map(|c| (c.id, c.user_name.as_str())).collect();
+        let mut children: HashMap<Option<i32>, Vec<&Comment>> = HashMap::new();
+        for c in data {
+            // 回复的评论已经隐藏时作为直接评论
+            let parent = c.parent_id.filter(|p| ids.contains(p));
+            children.entry(parent).or_default().push(c);
+        }
+        Thread {
+            obj_id,
+            url,
+            login,
+            admin,
+            ty,
+            names,
+            children,
+        }
+    }
+
+    fn show(&self, parent: Option<i32>, depth: usize) -> Markup {
+        let empty = Vec::new();
+        let list = self.children.get(&parent).unwrap_or(&empty);
+        html! {
+            @for c in list {
+                (self.comment(c, depth))
+            }
+        }
+    }
+
+    fn comment(&self, c: &Comment, depth: usize) -> Markup {
+        let ty = self.ty;
+        let url = self.url;
+        let reply_id = format!("reply-{}", c.id);
+        html! {
+            div class="border m-2 rounded" id={"comment-" (c.id)} {
+                div class="bg-light p-2 border-bottom border-2" {
+                    div class="d-flex align-content-start flex-wrap" {
+                        div class="mx-2 text-nowrap" {
+                            "作者: " (c.user_name)
+                        }
+                        @if let Some(name) = c.parent_id.and_then(|p| self.names.get(&p)) {
+                            div class="mx-2 text-nowrap" {
+                                a href={"#comment-" (c.parent_id.unwrap_or_default())} {"回复 " (name)}
+                            }
+                        }
+                        div class="mx-2 text-nowrap" {
+                            "时间: " (show_date(c.created_at))
+                        }
+                        @if self.admin {
+                            div class="text-nowrap" {
+                                a class="mx-2" href={"/my/" (ty) "/comment/edit/" (c.id) "?url=" (urlencoding::encode(url)) "#start"} {"修改"}
+                                a class="mx-2" href={"javascript:if(confirm('确实要删除吗?'))location='/my/" (ty) "/comment/rm/"
+                                                     (c.id)
+                                                     "?url=" (urlencoding::encode(url)) "'"
+                                } {"删除"}
+                            }
+                        }
+                    }
+                }
+                @if let Some(html) = &c.html {
+                    div class="p-2 md" {
+                        (PreEscaped(html))
+                    }
+                }
+                @if self.login {
+                    details class="mx-2 mb-2" {
+                        summary class="text-primary" {"回复"}
+                        form action={"/my/" (ty) "/comment/add"} method="post" {
+                            input type="hidden" name="url" value=(url);
+                            input type="hidden" name="oid" value=(self.obj_id);
+                            input type="hidden" name="parent_id" value=(c.id);
+                            (honeypot())
+                            (TextArea::new(&reply_id, "body", true).rows(3).md().show())
+                            (submit("回复"))
+                        }
+                    }
+                }
+                @if depth < MAX_DEPTH {
+                    div class="ms-4" {
+                        (self.show(Some(c.id), depth + 1))
+                    }
+                }
+            }
+            @if depth >= MAX_DEPTH {
+                (self.show(Some(c.id), depth))
+            }
+        }
+    }
+}
+
 pub async fn list_comment(
     ctx: &WebContext,
     id: i32,
@@ -211,40 +649,14 @@ pub async fn list_comment(
     ty: &str,
 ) -> Result<Markup> {
     let data = db_list(ctx, id, ty).await?;
+    let thread = Thread::new(id, url, login, admin, ty, &data);
     let res = html!(
         div {
             @if data.is_empty() {
                 p {"暂无评论"}
             } @else{
                 h5 {"评论："}
-                @for c in data {
-                   div class="border m-2 rounded" {
-                       div class="bg-light p-2 border-bottom border-2" {
-                           div class="d-flex align-content-start flex-wrap" {
-                               div class="mx-2 text-nowrap" {
-                                   "作者: " (c.user_name)
-                               }
-                               div class="mx-2 text-nowrap" {
-                                   "时间: " (show_date(c.created_at))
-                               }
-                               @if admin {
-                                   div class="text-nowrap" {
-                                       a class="mx-2" href={"/my/" (ty) "/comment/edit/" (c.id) "?url=" (urlencoding::encode(url)) "#start"} {"修改"}
-                                       a class="mx-2" href={"javascript:if(confirm('确实要删除吗?'))location='/my/" (ty) "/comment/rm/"
-                                                            (c.id)
-                                                            "?url=" (urlencoding::encode(url)) "'"
-                                       } {"删除"}
-                                   }
-                               }
-                           }
-                       }
-                       @if let Some(html) = c.html {
-                           div class="p-2 md" {
-                               (PreEscaped(html))
-                           }
-                       }
-                   }
-                }
+                (thread.show(None, 1))
             }
             div class="row justify-content-center" {
                 div class="col col-md-10" {
@@ -253,6 +665,7 @@ pub async fn list_comment(
                             form action={"/my/" (ty) "/comment/add"} method="post" {
                                 input type="hidden" name="url" value={(url) "#start"};
                                 input type="hidden" name="oid" value=(id);
+                                (honeypot())
                                 (TextArea::new("body", "body", false).md().show())
                                 (submit("评论"))
                             }
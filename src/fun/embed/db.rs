@@ -0,0 +1,47 @@
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct Card {
+    pub id: i32,
+    pub title: String,
+    pub user_name: String,
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct ImageFile {
+    pub id: i32,
+    pub title: String,
+    pub file: String,
+}
+
+/// 公布的文章、微博、好书
+pub async fn db_cards(ctx: &WebContext, kind: Kind, ids: &[i32]) -> anyhow::Result<Vec<Card>> {
+    let sql = format!(
+        "select {0}_id as id, title, user_name from {0} where {0}_id = any($1) and i_public = 1",
+        kind.table()
+    );
+    let rows = sqlx::query_as::<_, Card>(&sql)
+        .bind(ids)
+        .fetch_all(&ctx.db)
+        .await?;
+    Ok(rows)
+}
+
+/// 作者自己的、已经保存了文件的图片
+pub async fn db_images(
+    ctx: &WebContext,
+    user_id: i32,
+    ids: &[i32],
+) -> anyhow::Result<Vec<ImageFile>> {
+    let rows = sqlx::query_as!(
+        ImageFile,
+        r#"select id, title, file as "file!" from image
+           where id = any($1) and user_id = $2 and file is not null"#,
+        ids,
+        user_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
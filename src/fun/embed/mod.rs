@@ -0,0 +1,142 @@
+//! 短代码引用的站内内容
+//!
+//! 保存文章、好书、微博时先用 embeds 查出 Markdown 里引用的内容再渲染，
+//! 写错的、找不到的或者没有公布的照常保存（显示原文），用 broken 查出来提示作者。
+//! 图片只能引用内容作者自己上传的，owner 是内容的作者，不是修改的人。
+//! 保存、恢复修改记录和重新生成都用 render，渲染的方法只有这一处。
+
+pub mod db;
+
+use std::collections::HashSet;
+
+use maud::{html, Markup};
+
+use crate::fun::kind::Kind;
+use crate::fun::revision::db::Content;
+use crate::http::WebContext;
+use crate::md::embed::{self, Embeds, Found, Target};
+use crate::md::{self, diagram};
+
+use db::*;
+
+/// 文章、好书、微博渲染好的 HTML
+#[derive(Debug)]
+pub struct Rendered {
+    pub html: String,
+    pub html2: Option<String>,
+    pub brief_html: Option<String>,
+    pub log_html: Option<String>,
+}
+
+/// 查出引用的内容、生成图以后渲染，文章的正文前面加目录
+pub async fn render(
+    ctx: &WebContext,
+    kind: Kind,
+    owner: i32,
+    content: &Content<'_>,
+) -> anyhow::Result<Rendered> {
+    let texts = content.markdown();
+    let (embeds, _) = resolve(ctx, owner, &texts).await?;
+    let diagrams = diagram::prepare(&ctx.config.markdown, &texts).await;
+    let md_content = match kind {
+        Kind::Xi => md::Content::Xi,
+        Kind::Hu | Kind::Book => md::Content::Article,
+    };
+    let md = md::Markdown::new(&ctx.config, md_content)
+        .embeds(&embeds)
+        .diagrams(&diagrams);
+    let render = |text: Option<&str>| text.map(|t| md.html(t));
+    Ok(Rendered {
+        html: md.toc(kind == Kind::Hu).html(content.body),
+        html2: render(content.body2),
+        brief_html: render(content.brief),
+        log_html: render(content.log),
+    })
+}
+
+/// 有问题的短代码
+pub async fn broken(ctx: &WebContext, owner: i32, texts: &[&str]) -> anyhow::Result<Vec<String>> {
+    Ok(resolve(ctx, owner, texts).await?.1)
+}
+
+/// 保存成功以后提示有问题的短代码，没有时是空的
+pub fn warning(broken: &[String]) -> Markup {
+    html! {
+        @if !broken.is_empty() {
+            div class="row justify-content-center" {
+                div class="col col-md-7 col-xl-5 text-danger" {
+                    p {"下面的引用显示成原文，请检查："}
+                    ul {
+                        @for b in broken {
+                            li {(b)}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn resolve(
+    ctx: &WebContext,
+    owner: i32,
+    texts: &[&str],
+) -> anyhow::Result<(Embeds, Vec<String>)> {
+    let mut codes = Vec::new();
+    let mut broken = Vec::new();
+    for text in texts {
+        for (raw, code) in embed::shortcodes(text) {
+            match code {
+                Ok(code) => codes.push((raw, code.target)),
+                Err(e) => broken.push(format!("{}：{}", raw, e)),
+            }
+        }
+    }
+
+    let mut embeds = Embeds::default();
+    for kind in Kind::ALL {
+        let ids = ids(&codes, |t| match t {
+            Target::Content(k, id) if *k == kind => Some(*id),
+            _ => None,
+        });
+        if ids.is_empty() {
+            continue;
+        }
+        for c in db_cards(ctx, kind, &ids).await? {
+            let found = Found::Card {
+                title: c.title,
+                user_name: c.user_name,
+            };
+            embeds.insert(Target::Content(kind, c.id), found);
+        }
+    }
+    let ids = ids(&codes, |t| match t {
+        Target::Image(id) => Some(*id),
+        _ => None,
+    });
+    if !ids.is_empty() {
+        for i in db_images(ctx, owner, &ids).await? {
+            let found = Found::Image {
+                title: i.title,
+                file: i.file,
+            };
+            embeds.insert(Target::Image(i.id), found);
+        }
+    }
+
+    for (raw, target) in &codes {
+        if embeds.get(target).is_none() {
+            broken.push(format!("{}：{}", raw, embed::missing(target)));
+        }
+    }
+    let mut seen = HashSet::new();
+    broken.retain(|b| seen.insert(b.clone()));
+    Ok((embeds, broken))
+}
+
+fn ids(codes: &[(String, Target)], f: impl Fn(&Target) -> Option<i32>) -> Vec<i32> {
+    let mut ids: Vec<i32> = codes.iter().filter_map(|(_, t)| f(t)).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
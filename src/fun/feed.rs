@@ -0,0 +1,164 @@
+//! Atom 订阅
+//!
+//! 只包含公布（i_public=1）的内容，链接使用 config.host.www。
+
+use axum::extract::{Path, State};
+use axum::{routing::get, Router};
+use chrono::{DateTime, Utc};
+use maud::{html, PreEscaped};
+
+use crate::config::CategoryType;
+use crate::fun::kind::Kind;
+use crate::fun::user::db::{db_get_user, By};
+use crate::http::types::Xml;
+use crate::http::{error::AppError, Result, WebContext};
+
+const FEED_SIZE: i64 = 20;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/hu/feed.xml", get(hu_feed))
+        .route("/hu/cat/:cat/feed.xml", get(hu_cat_feed))
+        .route("/xi/feed.xml", get(xi_feed))
+        .route("/xi/cat/:cat/feed.xml", get(xi_cat_feed))
+        .route("/book/feed.xml", get(book_feed))
+        .route("/book/cat/:cat/feed.xml", get(book_cat_feed))
+        .route("/user/:id/feed.xml", get(user_feed))
+}
+
+fn columns(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Hu => "hu_id as id, title, user_name, brief_html as summary, html as content, created_at, updated_at",
+        Kind::Xi => "xi_id as id, title, user_name, null::text as summary, html as content, created_at, updated_at",
+        Kind::Book => "book_id as id, title, user_name, brief_html as summary, html as content, created_at, updated_at",
+    }
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct Entry {
+    pub id: i32,
+    pub title: String,
+    pub user_name: String,
+    pub summary: Option<String>,
+    pub content: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    #[sqlx(skip)]
+    pub url: String,
+}
+
+pub async fn db_entries(
+    ctx: &WebContext,
+    kind: Kind,
+    cat: Option<u8>,
+    user_id: Option<i32>,
+) -> anyhow::Result<Vec<Entry>> {
+    let mut rows = kind
+        .public()
+        .eq_opt("i_category", cat)
+        .eq_opt("user_id", user_id)
+        .select(columns(kind), kind.order_by(), FEED_SIZE, 0)
+        .build_query_as::<Entry>()
+        .fetch_all(&ctx.db)
+        .await?;
+    for e in rows.iter_mut() {
+        e.url = format!("{}{}", ctx.config.host.www, kind.view_url(e.id));
+    }
+    Ok(rows)
+}
+
+/// path 是对应的网页，订阅地址为 path + "/feed.xml"
+fn atom(ctx: &WebContext, title: &str, path: &str, alternate: &str, entries: Vec<Entry>) -> Xml {
+    let www = &ctx.config.host.www;
+    let updated = entries
+        .iter()
+        .map(|e| e.updated_at.unwrap_or(e.created_at))
+        .max()
+        .unwrap_or_else(Utc::now);
+    let feed = html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="utf-8"?>"#))
+        feed xmlns="http://www.w3.org/2005/Atom" {
+            title { (title) " -- " (ctx.config.host.name) }
+            id { (www) (path) "/feed.xml" }
+            link rel="self" href={(www) (path) "/feed.xml"} {}
+            link rel="alternate" href={(www) (alternate)} {}
+            updated { (updated.to_rfc3339()) }
+            @for e in entries {
+                entry {
+                    title { (e.title) }
+                    id { (e.url) }
+                    link rel="alternate" href=(e.url) {}
+                    published { (e.created_at.to_rfc3339()) }
+                    updated { (e.updated_at.unwrap_or(e.created_at).to_rfc3339()) }
+                    author { name { (e.user_name) } }
+                    @if let Some(summary) = e.summary.filter(|s| !s.is_empty()) {
+                        summary type="html" { (summary) }
+                    }
+                    @if let Some(content) = e.content {
+                        content type="html" { (content) }
+                    }
+                }
+            }
+        }
+    };
+    Xml {
+        content_type: "application/atom+xml; charset=utf-8",
+        body: feed.into_string(),
+    }
+}
+
+async fn kind_feed(ctx: &WebContext, kind: Kind) -> Result<Xml> {
+    let entries = db_entries(ctx, kind, None, None).await?;
+    Ok(atom(ctx, kind.name(), kind.path(), kind.path(), entries))
+}
+
+async fn kind_cat_feed(ctx: &WebContext, kind: Kind, cat: &str) -> Result<Xml> {
+    let category = kind.category(ctx);
+    let (cat_id, cat_name) = category
+        .id(cat)
+        .zip(category.name(cat))
+        .ok_or(AppError::NotFound("category"))?;
+    let entries = db_entries(ctx, kind, Some(cat_id), None).await?;
+    let title = format!("{}: {}", kind.name(), cat_name);
+    let path = format!("{}/cat/{}", kind.path(), cat);
+    Ok(atom(ctx, &title, &path, &path, entries))
+}
+
+async fn hu_feed(State(ctx): State<WebContext>) -> Result<Xml> {
+    kind_feed(&ctx, Kind::Hu).await
+}
+
+async fn hu_cat_feed(State(ctx): State<WebContext>, Path(cat): Path<String>) -> Result<Xml> {
+    kind_cat_feed(&ctx, Kind::Hu, &cat).await
+}
+
+async fn xi_feed(State(ctx): State<WebContext>) -> Result<Xml> {
+    kind_feed(&ctx, Kind::Xi).await
+}
+
+async fn xi_cat_feed(State(ctx): State<WebContext>, Path(cat): Path<String>) -> Result<Xml> {
+    kind_cat_feed(&ctx, Kind::Xi, &cat).await
+}
+
+async fn book_feed(State(ctx): State<WebContext>) -> Result<Xml> {
+    kind_feed(&ctx, Kind::Book).await
+}
+
+async fn book_cat_feed(State(ctx): State<WebContext>, Path(cat): Path<String>) -> Result<Xml> {
+    kind_cat_feed(&ctx, Kind::Book, &cat).await
+}
+
+/// 一个作者公布的所有内容
+async fn user_feed(State(ctx): State<WebContext>, Path(id): Path<i32>) -> Result<Xml> {
+    let user = db_get_user(&ctx, By::Id(id))
+        .await?
+        .ok_or(AppError::NotFound("user"))?;
+    let mut entries = Vec::new();
+    for kind in Kind::ALL {
+        entries.extend(db_entries(&ctx, kind, None, Some(id)).await?);
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+    entries.truncate(FEED_SIZE as usize);
+    let path = format!("/user/{}", id);
+    Ok(atom(&ctx, &user.name, &path, "/", entries))
+}
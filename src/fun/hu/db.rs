@@ -1,10 +1,11 @@
 use chrono::{DateTime, Utc};
 
 use crate::db;
+use crate::fun::kind::{self, Kind};
 use crate::fun::user::SessUser;
-use crate::fun::widget::list::{DbList, List, ListBy};
+use crate::fun::widget::list::{DbList, List};
+use crate::fun::{embed, publish, revision, tag};
 use crate::http::WebContext;
-use crate::md;
 
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct Hu {
@@ -33,6 +34,7 @@ pub struct Hu {
     pub updated_at: Option<DateTime<Utc>>,
     pub good_at: Option<DateTime<Utc>>,
     pub click: i32,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 impl Hu {
@@ -49,6 +51,7 @@ impl Hu {
             brief: self.brief,
             body2: self.body2,
             log: self.log,
+            publish_at: publish::input_value(self.publish_at),
         }
     }
 }
@@ -66,6 +69,8 @@ pub struct Input {
     pub body2: Option<String>,
     pub brief: Option<String>,
     pub log: Option<String>,
+    /// 定时发布的时间，表单中的本地时间
+    pub publish_at: Option<String>,
 }
 
 impl Input {
@@ -78,6 +83,7 @@ impl Input {
         if self.body.is_empty() {
             error.push("请输入正文".to_string());
         }
+        publish::check(self.i_public, &mut self.publish_at, &mut error);
         if !error.is_empty() {
             return Err(error);
         }
@@ -88,6 +94,16 @@ impl Input {
         self.log = self.log.as_ref().and_then(db::check_none);
         Ok(())
     }
+    /// 记入修改记录的内容
+    pub fn content(&self) -> revision::db::Content<'_> {
+        revision::db::Content {
+            title: &self.title,
+            brief: self.brief.as_deref(),
+            body: &self.body,
+            body2: self.body2.as_deref(),
+            log: self.log.as_deref(),
+        }
+    }
 }
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct HuSimple {
@@ -101,78 +117,47 @@ pub struct HuSimple {
     pub i_public: i16,
     pub i_good: i16,
     pub url: Option<String>,
+    pub star: i32,
     pub created_at: DateTime<Utc>,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 pub async fn db_list<'a>(list: &List<'a>) -> anyhow::Result<(i64, DbList)> {
-    let offset = list.size as u32 * (list.page - 1);
-    let s_web = list.web_search_key.map_or("".to_string(), |k| {
-        format!("and search_ti @@ websearch_to_tsquery('{}') ", k)
-    });
-    let s_title = list.title_search_key.map_or("".to_string(), |k| {
-        format!("and lower(title) like '%{}%' ", k.to_lowercase())
-    });
-    let s_type = list
-        .i_type
-        .map_or("".to_string(), |tid| format!("and i_type={} ", tid));
-    let s_cat = list
-        .cat
-        .map_or("".to_string(), |cid| format!("and i_category={} ", cid));
-    let s_good = list
-        .good
-        .map_or("".to_string(), |gid| format!("and good={} ", gid));
-    let sql_where = match list.filter {
-        ListBy::All => "".to_owned(),
-        ListBy::UserId(id) => format!(
-            "where user_id={id} {} {} {} {} {}",
-            s_cat, s_type, s_good, s_web, s_title
-        ),
-        ListBy::AllPublic => format!(
-            "where i_public=1 {} {} {} {} {}",
-            s_cat, s_type, s_good, s_web, s_title
-        ),
-    };
-    let sql_total = format!("select count(*) as total from hu {};", &sql_where);
-    let row = sqlx::query_as::<_, db::Total>(&sql_total)
+    let filter = list.filter("hu");
+    let total = filter
+        .count()
+        .build_query_as::<db::Total>()
         .fetch_one(&list.ctx.db)
-        .await?;
-    let total = row.total;
-    let sql = format!(
-        r#"
-            select
-                hu_id, title, i_public, i_type, i_category, good, created_at, user_name, brief, url, i_good
-            from hu
-            {}
-            order by hu_id desc limit {} offset {};"#,
-        sql_where, list.size, offset
-    );
-    let rows = sqlx::query_as::<_, HuSimple>(&sql)
+        .await?
+        .total;
+    let rows = filter
+        .select(
+            "hu_id, title, i_public, i_type, i_category, good, created_at, user_name, brief, url, i_good, star, publish_at",
+            list.order_by(Kind::Hu),
+            list.size as i64,
+            list.offset(),
+        )
+        .build_query_as::<HuSimple>()
         .fetch_all(&list.ctx.db)
         .await?;
     Ok((total, DbList::Hu(rows)))
 }
 
 pub async fn db_insert(ctx: &WebContext, user: &SessUser, input: Input) -> anyhow::Result<i32> {
-    let html = md::to_html(&input.body);
-    let mut html2: Option<String> = None;
-    let mut log_html: Option<String> = None;
-    let mut brief_html: Option<String> = None;
-    if let Some(ref text) = input.body2 {
-        html2 = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.log {
-        log_html = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.brief {
-        brief_html = Some(md::to_html(text));
-    }
+    let embed::Rendered {
+        html,
+        html2,
+        brief_html,
+        log_html,
+    } = embed::render(ctx, Kind::Hu, user.id, &input.content()).await?;
 
     let i_good = input.i_good.map_or(0, |v| v);
+    let mut tx = ctx.db.begin().await?;
     let rec = sqlx::query!(
         r#"insert into hu
              (user_id, user_name, title, body, html, i_category, i_public, i_type,
-               url, tags, body2, html2, log, log_html, i_good, brief, brief_html)
-           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+               url, tags, body2, html2, log, log_html, i_good, brief, brief_html, publish_at)
+           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
            returning hu_id"#,
         user.id,
         user.name,
@@ -190,33 +175,40 @@ pub async fn db_insert(ctx: &WebContext, user: &SessUser, input: Input) -> anyho
         log_html,
         i_good,
         input.brief,
-        brief_html
+        brief_html,
+        publish::publish_at(input.i_public, input.publish_at.as_deref())
     )
-    .fetch_one(&ctx.db)
+    .fetch_one(&mut *tx)
     .await?;
+    tag::db::db_set_tags(&mut tx, Kind::Hu.table(), rec.hu_id, input.tags.as_deref()).await?;
+    tx.commit().await?;
+    revision::db::db_add(ctx, Kind::Hu, rec.hu_id, user, input.content()).await?;
     Ok(rec.hu_id)
 }
 
-pub async fn db_update(ctx: &WebContext, id: i32, input: &Input) -> anyhow::Result<bool> {
-    let html = md::to_html(&input.body);
-    let mut html2: Option<String> = None;
-    let mut log_html: Option<String> = None;
-    let mut brief_html: Option<String> = None;
-    if let Some(ref text) = input.body2 {
-        html2 = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.log {
-        log_html = Some(md::to_html(text));
-    }
-    if let Some(ref text) = input.brief {
-        brief_html = Some(md::to_html(text));
-    }
+pub async fn db_update(
+    ctx: &WebContext,
+    user: &SessUser,
+    id: i32,
+    input: &Input,
+) -> anyhow::Result<bool> {
+    let owner = match kind::db_owner(ctx, Kind::Hu, id).await? {
+        Some(owner) => owner,
+        None => return Ok(false),
+    };
+    let embed::Rendered {
+        html,
+        html2,
+        brief_html,
+        log_html,
+    } = embed::render(ctx, Kind::Hu, owner.user_id, &input.content()).await?;
     let i_good = input.i_good.map_or(0, |v| v);
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"update hu
            set title=$1, body=$2, html=$3, i_category=$4, i_public=$5, i_type=$6, url=$7, tags=$8, body2=$9, html2=$10,
-               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15
-           where hu_id=$16"#,
+               log=$11, log_html=$12, i_good=$13, brief=$14, brief_html=$15, publish_at=$16
+           where hu_id=$17"#,
         input.title,
         input.body,
         html,
@@ -232,12 +224,20 @@ pub async fn db_update(ctx: &WebContext, id: i32, input: &Input) -> anyhow::Resu
         i_good,
         input.brief,
         brief_html,
+        publish::publish_at(input.i_public, input.publish_at.as_deref()),
         id
     )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows > 0 {
+        tag::db::db_set_tags(&mut tx, Kind::Hu.table(), id, input.tags.as_deref()).await?;
+    }
+    tx.commit().await?;
+    if rows > 0 {
+        revision::db::db_add(ctx, Kind::Hu, id, user, input.content()).await?;
+    }
     Ok(rows > 0)
 }
 
@@ -245,7 +245,8 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Hu> {
     let rec = sqlx::query_as!(
         Hu,
         r#"select hu_id as id, user_id, user_name, title, body, html, body2, html2, log, log_html, brief, brief_html,
-               i_category, i_type, i_public, i_good, star, url, tags, good, created_at, updated_at, good_at, click
+               i_category, i_type, i_public, i_good, star, url, tags, good, created_at, updated_at, good_at, click,
+               publish_at
            from hu where hu_id=$1"#,
         id
     )
@@ -256,29 +257,20 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Hu> {
 }
 
 pub async fn db_rm(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"delete from hu
            where hu_id=$1"#,
         id
     )
-    .execute(&ctx.db)
-    .await?
-    .rows_affected();
-
-    Ok(rows == 1)
-}
-
-pub async fn db_update_click(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
-    let rows = sqlx::query!(
-        r#"update hu
-           set click=click+1
-           where hu_id=$1"#,
-        id
-    )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows == 1 {
+        kind::db_rm_related(&mut tx, Kind::Hu, id).await?;
+    }
+    tx.commit().await?;
     Ok(rows == 1)
 }
 
@@ -11,17 +11,23 @@ use maud::{html, Markup, PreEscaped};
 use serde::Deserialize;
 
 use crate::config::CategoryType;
-use crate::fun::comment;
+use crate::fun::kind::Kind;
 use crate::fun::layout::{split, vsplit, Html};
-use crate::fun::user::{check_sudo, get_user_from, is_sudo_role, SessUser};
+use crate::fun::user::{check_cap, get_user_can, get_user_from, Cap, SessUser};
+use crate::fun::visit::Visitor;
 use crate::fun::widget::list::*;
 use crate::fun::widget::*;
+use crate::fun::{comment, embed, publish, revision, star, tag};
 use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
 
 use db::*;
 
-static PUBLIC_STATUS: [(i16, &str, &str); 2] = [(0, "craft", "草稿"), (1, "published", "公布")];
+static PUBLIC_STATUS: [(i16, &str, &str); 3] = [
+    (0, "craft", "草稿"),
+    (1, "published", "公布"),
+    (publish::SCHEDULED, "scheduled", "定时发布"),
+];
 
 pub fn router() -> Router<WebContext> {
     Router::new()
@@ -54,8 +60,9 @@ async fn hu_my(
     let page = pagination.page;
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .pager(Some(path.as_str()))
+        .sort(pagination.sort)
         .show_cat_type_name()
-        .sudo(is_sudo_role(user.role));
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     Ok(Html::new("我的文章", main)
@@ -72,15 +79,19 @@ async fn hu_pub(
 ) -> Result<Page> {
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .pager(Some(path.as_str()))
+        .sort(pagination.sort)
         .admin(false)
         .sudo(sudo)
         .show_cat_type_name();
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
-    let right = list_category_name(&ctx, false);
+    let right = html! {
+        (list_category_name(&ctx, false))
+        (tag::tag_cloud(&ctx).await?)
+    };
     let main = split(left, right);
     Ok(Html::new("文章", main).path(Some(path.as_str())).page(&ctx))
 }
@@ -91,7 +102,7 @@ async fn hu_add_input(
     session: Session<SessionPgPool>,
     path: MatchedPath,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::Publish).await?;
     let Query(cat_type) = cat_type.unwrap_or_default();
     let hu: Input = Input {
         i_category: cat_type.cat,
@@ -171,7 +182,7 @@ fn input_form(ctx: &WebContext, hu: &Input, error: ErrorMessage, edit: bool) ->
                             div class="row mb-3 border-bottom" {
                                 label class="col-md-2 col-form-label text-md-end" for="tags" {"Tags："}
                                 div class="col-md-7" {
-                                    (TextInput::new("tags", "tags", false).value(hu.tags.as_ref()).show())
+                                    (tag::tag_input(hu.tags.as_ref()))
                                 }
                                 div class="col-md-3" {
                                 }
@@ -205,6 +216,7 @@ fn input_form(ctx: &WebContext, hu: &Input, error: ErrorMessage, edit: bool) ->
                                     @for (_i, p) in PUBLIC_STATUS.iter().enumerate() {
                                         (radio(p.1, "i_public", &p.0.to_string(), hu.i_public == p.0, p.2))
                                     }
+                                    (publish::publish_input(hu.publish_at.as_ref()))
                                 }
                             }
                             div class="text-center bg-light" {
@@ -228,23 +240,26 @@ async fn hu_add_do(
     path: MatchedPath,
     Form(mut input): Form<Input>,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
-    let check = input.check();
-    if let Err(e) = check {
+    let user = get_user_can(&session, Cap::Publish).await?;
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), false);
         return Ok(Html::new("新文章", main)
             .my_huxi(&user)
             .path(Some(path.as_str()))
             .page(&ctx));
     }
+    let broken = embed::broken(&ctx, user.id, &input.content().markdown()).await?;
     let id = db_insert(&ctx, &user, input).await?;
     let main = html!(
         (tip("新就文章添加成功"))
+        (embed::warning(&broken))
         div class="text-center" {
             a href={"/hu/view/" (id) "/index.html#start"} class="m-2" {"继续查看"}
             a href="/my/hu" class="m-2" {"显示列表"}
         }
-        (PreEscaped(redirect_script("/my/hu")))
+        @if broken.is_empty() {
+            (PreEscaped(redirect_script("/my/hu")))
+        }
     );
     Ok(Html::new("添加文章成功", main)
         .my_huxi(&user)
@@ -269,13 +284,53 @@ async fn hu_edit_input(
 
 async fn check_owner(hu_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if hu_user_id == user.id || is_sudo_role(user.role) {
+    if can_edit(hu_user_id, &user) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
     }
 }
 
+/// 作者或编辑可以修改、删除
+pub fn can_edit(hu_user_id: i32, user: &SessUser) -> bool {
+    user.can_manage(hu_user_id, Cap::EditAll)
+}
+
+/// 查看权限
+pub struct Access {
+    pub allow: bool,
+    pub login: bool,
+    pub admin: bool,
+    pub sudo: bool,
+    pub owner: bool,
+}
+
+pub fn access(hu: &Hu, user: Option<&SessUser>) -> Access {
+    let mut access = Access {
+        allow: hu.i_public == 1,
+        login: false,
+        admin: false,
+        sudo: false,
+        owner: false,
+    };
+    if let Some(user) = user {
+        access.login = true;
+        if user.can(Cap::EditAll) {
+            access.allow = true;
+            access.admin = true;
+        }
+        if user.can(Cap::Recommend) {
+            access.sudo = true;
+        }
+        if hu.user_id == user.id {
+            access.allow = true;
+            access.admin = true;
+            access.owner = true;
+        }
+    }
+    access
+}
+
 async fn hu_edit_do(
     State(ctx): State<WebContext>,
     session: Session<SessionPgPool>,
@@ -285,8 +340,7 @@ async fn hu_edit_do(
 ) -> Result<Page> {
     let hu = db_get_one(&ctx, id).await?;
     let user = check_owner(hu.user_id, &session).await?;
-    let check = input.check();
-    if let Err(e) = check {
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), true);
         return Ok(Html::new("修改文章", main)
             .my_huxi(&user)
@@ -294,7 +348,7 @@ async fn hu_edit_do(
             .page(&ctx));
     }
 
-    let update = db_update(&ctx, id, &input).await?;
+    let update = db_update(&ctx, &user, id, &input).await?;
 
     if !update {
         let main = tip("数据错误");
@@ -303,13 +357,17 @@ async fn hu_edit_do(
             .path(Some(path.as_str()))
             .page(&ctx));
     }
+    let broken = embed::broken(&ctx, hu.user_id, &input.content().markdown()).await?;
     let main = html!(
         (tip("修改文章成功"))
+        (embed::warning(&broken))
         div class="text-center" {
             a href={"/hu/view/" (id) "/index.html#start"} class="m-2" {"继续查看"}
             a href="/my/hu" class="m-2" {"显示列表"}
         }
-        (PreEscaped(redirect_script("/my/hu")))
+        @if broken.is_empty() {
+            (PreEscaped(redirect_script("/my/hu")))
+        }
     );
     Ok(Html::new("修改文章", main)
         .my_huxi(&user)
@@ -317,7 +375,7 @@ async fn hu_edit_do(
         .page(&ctx))
 }
 
-fn view_url(id: i32) -> String {
+pub fn view_url(id: i32) -> String {
     format!("/hu/view/{}/index.html", id)
 }
 
@@ -325,43 +383,33 @@ async fn hu_view(
     session: Session<SessionPgPool>,
     State(ctx): State<WebContext>,
     Path(id): Path<i32>,
+    visitor: Visitor,
     path: MatchedPath,
 ) -> Result<Page> {
     let hu = db_get_one(&ctx, id).await?;
 
     // 检查权限
-    let mut allow = false;
-    let mut login = false;
-    let mut admin = false;
-    let mut sudo = false;
-    let mut owner: Option<SessUser> = None;
-    if hu.i_public > 0 {
-        allow = true;
-    }
-
-    let result = get_user_from(&session).await;
-    if let Ok(user) = result {
-        login = true;
-        if is_sudo_role(user.role) {
-            sudo = true;
-            allow = true;
-            admin = true;
-        }
-        if hu.user_id == user.id {
-            allow = true;
-            admin = true;
-            owner = Some(user);
-        }
-    }
+    let user = get_user_from(&session).await.ok();
+    let Access {
+        allow,
+        login,
+        admin,
+        sudo,
+        owner,
+    } = access(&hu, user.as_ref());
 
     let left = if allow {
         let url = view_url(id);
-        let _ = db_update_click(&ctx, id).await;
+        if !owner {
+            ctx.visits.hit(Kind::Hu, id, &visitor);
+        }
         let cms = comment::list_comment(&ctx, hu.id, url.as_str(), login, admin, "hu").await?;
-        content_html(&hu, cms, &ctx, admin, sudo)
+        let star = star::star_button(&ctx, Kind::Hu, hu.id, hu.star, user.as_ref()).await?;
+        content_html(&hu, cms, star, &ctx, admin, sudo)
     } else {
         tip("权限错误")
     };
+    let owner = if owner { user } else { None };
     let main = if owner.is_some() {
         left
     } else {
@@ -388,7 +436,14 @@ async fn hu_view(
     Ok(html.page(&ctx))
 }
 
-fn content_html(hu: &Hu, comment: Markup, ctx: &WebContext, admin: bool, sudo: bool) -> Markup {
+fn content_html(
+    hu: &Hu,
+    comment: Markup,
+    star: Markup,
+    ctx: &WebContext,
+    admin: bool,
+    sudo: bool,
+) -> Markup {
     html! {
         div class="container" {
             div class="row justify-content-center" {
@@ -408,6 +463,9 @@ fn content_html(hu: &Hu, comment: Markup, ctx: &WebContext, admin: bool, sudo: b
                                 div class="col mx-2" {
                                     "更新："
                                         (show_date(hu.updated_at.unwrap()))
+                                    @if admin {
+                                        a class="ms-2" href=(revision::list_url(Kind::Hu, hu.id)) {"修改记录"}
+                                    }
                                 }
                             }
                         }
@@ -430,12 +488,18 @@ fn content_html(hu: &Hu, comment: Markup, ctx: &WebContext, admin: bool, sudo: b
                             }
                             div class="col mx-2" {
                                 "点击：" (hu.click)
+                                @if admin {
+                                    a class="ms-2" href={"/my/visit/hu/" (hu.id)} {"统计"}
+                                }
+                            }
+                            div class="col mx-2" {
+                                "星标：" (star)
                             }
                         }
                         div class="row row-cols-1  row-cols-md-4" {
                             @if let Some(ref tags) = hu.tags {
                                 div class="col mx-2" {
-                                    "Tags: " (tags)
+                                    "Tags: " (tag::tag_links(tags))
                                 }
                             }
                             @if admin {
@@ -526,7 +590,7 @@ async fn hu_good(
     let user = check_owner(hu.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 1).await?;
         if result_ok {
             main = tip("推荐成功");
@@ -547,7 +611,7 @@ async fn hu_good_cancel(
     let user = check_owner(hu.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 0).await?;
         if result_ok {
             main = tip("取消推荐成功");
@@ -582,7 +646,7 @@ async fn hu_my_cat(
         .pager(Some(path.as_str()))
         .show_cat_type_name()
         .admin(true)
-        .sudo(is_sudo_role(user.role));
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     let title = format!("我的文章: {} ", cat_name);
@@ -603,7 +667,7 @@ async fn hu_my_cat2(
     let user = get_user_from(&session).await?;
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
 
     let cat_id = ctx.config.hu.category.id(cat.as_str());
     let cid = cat.as_str();
@@ -669,7 +733,7 @@ async fn hu_pub_cat(
         .category
         .name(cat.as_str())
         .ok_or_else(|| AppError::InvalidArg("cat name error".to_string()))?;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let left = html!(
         div class="" {
             @for ty in &ctx.config.hu.content_type {
@@ -736,12 +800,12 @@ async fn hu_my_cat_type(
         .name(tid.as_str())
         .ok_or_else(|| AppError::InvalidArg("type name error".to_string()))?;
     // let page_link = format!("/hu/cat/{}/{}", cat, tid);
-    // let sudo = check_sudo(&session).await;
+    // let sudo = check_cap(&session, Cap::Recommend).await;
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .cat(cat_id)
         .show_cat_type_name()
         .admin(true)
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .i_type(type_id)
         .pager(Some(path.as_str()));
     let (total, data) = db_list(&list).await?;
@@ -779,7 +843,7 @@ async fn hu_pub_cat_type(
         .name(tid.as_str())
         .ok_or_else(|| AppError::InvalidArg("type name error".to_string()))?;
     let page_link = format!("/hu/cat/{}/{}", cat, tid);
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
 
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .cat(cat_id)
@@ -787,7 +851,8 @@ async fn hu_pub_cat_type(
         .sudo(sudo)
         .show_cat_type_name()
         .i_type(type_id)
-        .pager(Some(page_link.as_str()));
+        .pager(Some(page_link.as_str()))
+        .sort(pagination.sort);
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
 
@@ -902,7 +967,7 @@ async fn hu_search(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.map_or(0, |u| u.role)))
+        .sudo(user.map_or(false, |u| u.can(Cap::Recommend)))
         .admin(false)
         .web_search_key(Some(key));
     let (total, data) = db_list(&list).await?;
@@ -925,7 +990,7 @@ async fn hu_search_my(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .title_search_key(Some(key))
         .admin(true);
     let (total, data) = db_list(&list).await?;
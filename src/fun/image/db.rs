@@ -1,4 +1,6 @@
+use crate::db::filter::{self, Filter};
 use crate::fun::image::List;
+use crate::fun::tag;
 use crate::http::WebContext;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
@@ -56,28 +58,24 @@ pub struct DbTotal {
 }
 
 pub async fn db_list<'a>(list: &List<'a>) -> anyhow::Result<(i64, Vec<ImageSimple>)> {
-    let offset = list.size as u32 * (list.page - 1);
-    let sql_where = match list.filter {
-        ListBy::All => "".to_owned(),
-        ListBy::UserId(id) => format!("where user_id={id}"),
+    let filter = match list.filter {
+        ListBy::All => Filter::new("image"),
+        ListBy::UserId(id) => Filter::new("image").eq("user_id", id),
     };
-
-    let sql_total = format!("select count(*) as total from image {};", &sql_where);
-    let row = sqlx::query_as::<_, DbTotal>(&sql_total)
+    let total = filter
+        .count()
+        .build_query_as::<DbTotal>()
         .fetch_one(&list.ctx.db)
-        .await?;
-    let total = row.total;
-
-    let sql = format!(
-        r#"
-            select
-                id, title, file, created_at
-            from image
-            {}
-            order by id desc limit {} offset {};"#,
-        sql_where, list.size, offset
-    );
-    let rows = sqlx::query_as::<_, ImageSimple>(&sql)
+        .await?
+        .total;
+    let rows = filter
+        .select(
+            "id, title, file, created_at",
+            "id desc",
+            list.size as i64,
+            filter::offset(list.page, list.size),
+        )
+        .build_query_as::<ImageSimple>()
         .fetch_all(&list.ctx.db)
         .await?;
     Ok((total, rows))
@@ -97,14 +95,19 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Image> {
 }
 
 pub async fn db_rm(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"delete from image
            where id=$1"#,
         id
     )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows == 1 {
+        tag::db::db_set_tags(&mut tx, tag::IMAGE, id, None).await?;
+    }
+    tx.commit().await?;
     Ok(rows == 1)
 }
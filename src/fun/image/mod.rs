@@ -17,7 +17,8 @@ use maud::{html, Markup, PreEscaped};
 
 use super::user::SessUser;
 use crate::fun::layout::Html;
-use crate::fun::user::{get_user_from, is_sudo_role};
+use crate::fun::tag;
+use crate::fun::user::{get_user_can, get_user_from, Cap};
 use crate::fun::widget::*;
 use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
@@ -151,7 +152,7 @@ async fn image_add_input(
     session: Session<SessionPgPool>,
     path: MatchedPath,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::UploadImage).await?;
     let input: Input = Default::default();
     let main = input_form(&ctx, &input, None, true);
     Ok(Html::new("添加图片", main)
@@ -187,7 +188,7 @@ fn input_form(ctx: &WebContext, image: &Input, error: ErrorMessage, is_add: bool
                         div class="row mb-3 border-bottom" {
                             label for="tags" class="col-md-2 col-form-label text-md-end" {"Tags："}
                             div class="col-md-7" {
-                                (TextInput::new("tags", "tags", false).value(image.tags.as_ref()).show())
+                                (tag::tag_input(image.tags.as_ref()))
                             }
                             div class="col-md-3" {
                             }
@@ -236,7 +237,7 @@ async fn image_add_do(
     path: MatchedPath,
     multipart: Multipart,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::UploadImage).await?;
     let mut error: Vec<String> = vec![];
     let result = form_data(multipart).await;
     if result.is_err() {
@@ -336,6 +337,7 @@ async fn save(
     ctx: &WebContext,
     id: Option<i32>,
 ) -> Result<(Option<String>, Option<u64>)> {
+    let tags = image.tags.clone();
     let mut new_file: Option<String> = None;
     if let Some(data) = image.data {
         let img_bytes = data;
@@ -367,6 +369,7 @@ async fn save(
         new_file = Some(image_new_file);
     }
     // update
+    let mut tx = ctx.db.begin().await?;
     let sql_result: Option<u64> = if let Some(image_id) = id {
         if let Some(ref file) = new_file {
             let rows = sqlx::query!(
@@ -379,7 +382,7 @@ async fn save(
                 file,
                 image_id
             )
-            .execute(&ctx.db)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
             if rows > 0 {
@@ -397,7 +400,7 @@ async fn save(
                 image.tags,
                 image_id
             )
-            .execute(&ctx.db)
+            .execute(&mut *tx)
             .await?
             .rows_affected();
             if rows > 0 {
@@ -420,10 +423,20 @@ async fn save(
             image.file.unwrap_or("".to_string()),
             &new_file.clone().unwrap()
         )
-        .fetch_one(&ctx.db)
+        .fetch_one(&mut *tx)
         .await?;
         Some(rec.id.try_into().unwrap())
     };
+    // 保存成功以后在同一个事务里更新标签，新建时 sql_result 是新的编号
+    let saved = match (id, sql_result) {
+        (Some(image_id), Some(_)) => Some(image_id),
+        (None, Some(new_id)) => Some(new_id as i32),
+        _ => None,
+    };
+    if let Some(image_id) = saved {
+        tag::db::db_set_tags(&mut tx, tag::IMAGE, image_id, tags.as_deref()).await?;
+    }
+    tx.commit().await?;
     Ok((new_file, sql_result))
 }
 
@@ -444,7 +457,7 @@ async fn image_edit_input(
 
 async fn check_owner(image_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if image_user_id == user.id || is_sudo_role(user.role) {
+    if user.can_manage(image_user_id, Cap::EditAll) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
@@ -555,7 +568,7 @@ fn content_html(ctx: &WebContext, image: &Image) -> Result<Markup> {
                     div class="text-center" {
                         @if let Some(ref tags) = image.tags {
                             span class="col mx-2" {
-                                "Tags: " (tags)
+                                "Tags: " (tag::tag_links(tags))
                             }
                         }
                         span class="mx-2" {
@@ -573,6 +586,8 @@ fn content_html(ctx: &WebContext, image: &Image) -> Result<Markup> {
                     div class="border m-2 p-2" {
                         h5 {"Markdown:"}
                         pre class="m-2 p-2" {(md)}
+                        h5 {"短代码（文章、好书、微博中使用，可以加上 width=700）:"}
+                        pre class="m-2 p-2" {"[[image:" (image.id) "]]"}
                     }
                     div class="bg-light text-center m-2 p-2" {
                         img src=(&path);
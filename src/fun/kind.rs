@@ -0,0 +1,112 @@
+//! 内容类型：文章、微博、好书
+
+use sqlx::PgConnection;
+
+use crate::db::filter::Filter;
+use crate::fun::{book, hu, xi};
+use crate::http::WebContext;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Hu,
+    Xi,
+    Book,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Hu, Kind::Xi, Kind::Book];
+
+    pub fn from_path(s: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|k| k.table() == s)
+    }
+    pub fn table(&self) -> &'static str {
+        match self {
+            Kind::Hu => "hu",
+            Kind::Xi => "xi",
+            Kind::Book => "book",
+        }
+    }
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            Kind::Hu => "hu_id desc",
+            Kind::Xi => "xi_id desc",
+            Kind::Book => "book_id desc",
+        }
+    }
+    /// 星标多的在前
+    pub fn order_by_star(&self) -> &'static str {
+        match self {
+            Kind::Hu => "star desc, hu_id desc",
+            Kind::Xi => "star desc, xi_id desc",
+            Kind::Book => "star desc, book_id desc",
+        }
+    }
+    /// 公布的内容
+    pub fn public(&self) -> Filter {
+        Filter::new(self.table()).eq("i_public", 1i16)
+    }
+    pub fn view_url(&self, id: i32) -> String {
+        match self {
+            Kind::Hu => hu::view_url(id),
+            Kind::Xi => xi::view_url(id),
+            Kind::Book => book::view_url(id),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Hu => "文章",
+            Kind::Xi => "微博",
+            Kind::Book => "好书",
+        }
+    }
+    pub fn path(&self) -> &'static str {
+        match self {
+            Kind::Hu => "/hu",
+            Kind::Xi => "/xi",
+            Kind::Book => "/book",
+        }
+    }
+    pub fn category<'a>(&self, ctx: &'a WebContext) -> &'a Vec<(u8, String, String)> {
+        match self {
+            Kind::Hu => &ctx.config.hu.category,
+            Kind::Xi => &ctx.config.xi.category,
+            Kind::Book => &ctx.config.book.category,
+        }
+    }
+}
+
+/// 内容的作者、标题和是否公布
+#[derive(sqlx::FromRow, Debug)]
+pub struct Owner {
+    pub user_id: i32,
+    pub title: String,
+    pub i_public: i16,
+}
+
+pub async fn db_owner(ctx: &WebContext, kind: Kind, id: i32) -> anyhow::Result<Option<Owner>> {
+    let sql = format!(
+        "select user_id, title, i_public from {0} where {0}_id=$1",
+        kind.table()
+    );
+    let row = sqlx::query_as(&sql)
+        .bind(id)
+        .fetch_optional(&ctx.db)
+        .await?;
+    Ok(row)
+}
+
+/// 删除内容时一起删除的记录：标签、修改记录、星标和每天的访问次数
+const RELATED: [&str; 4] = ["tagging", "revision", "user_star", "visit_day"];
+
+/// 在删除内容的事务里删除相关的记录
+pub async fn db_rm_related(tx: &mut PgConnection, kind: Kind, id: i32) -> anyhow::Result<()> {
+    for table in RELATED {
+        let sql = format!("delete from {} where kind=$1 and obj_id=$2", table);
+        sqlx::query(&sql)
+            .bind(kind.table())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
@@ -71,9 +71,9 @@ fn top_nav(ctx: &WebContext, path: Option<&str>) -> Markup {
                     }
 
 
-                    form class="d-flex" role="search" action="/hu/search" method="get" {
+                    form class="d-flex" role="search" action="/search" method="get" {
                         input class="form-control me-2" type="search" placeholder="Search" aria-label="Search" name="key";
-                        button class="btn btn-outline-success flex-shrink-0" type="submit" {"搜索文章"}
+                        button class="btn btn-outline-success flex-shrink-0" type="submit" {"搜索"}
                     }
 
                 }
@@ -209,6 +209,7 @@ impl<'a> Html<'a> {
                     meta name="description" content={(des)};
                 }
                 link rel="icon" href="/img/favicon.ico" type="image/x-icon";
+                link rel="alternate" type="application/atom+xml" title={(ctx.config.host.name) " -- 文章"} href="/hu/feed.xml";
                 //link rel="stylesheet" href="/css/tailwind.css";
                 link rel="stylesheet" href="/css/bootstrap.min.css";
                 //script src="/js/index.min.js" {}
@@ -217,9 +218,13 @@ impl<'a> Html<'a> {
                     script src="/js/easymde.min.js" {}
                 }
                 @if self.highlight {
-                    link rel="stylesheet" href="/css/hl/github.css";
-                    script src="/js/hl/highlight.min.js" {}
-                    script {"hljs.highlightAll();"}
+                    @if ctx.config.markdown.highlight {
+                        link rel="stylesheet" href={"/css/hl/" (ctx.config.markdown.highlight_theme) ".css"};
+                    } @else {
+                        link rel="stylesheet" href="/css/hl/github.css";
+                        script src="/js/hl/highlight.min.js" {}
+                        script {"hljs.highlightAll();"}
+                    }
                 }
                 link rel="stylesheet" href="/css/main.css";
             }
@@ -241,6 +246,9 @@ impl<'a> Html<'a> {
                                         (self.my_nav_item("/my/xi", "微博"))
                                         (self.my_nav_item("/my/book", "好书"))
                                         (self.my_nav_item("/my/image", "图片"))
+                                        (self.my_nav_item("/my/star", "星标"))
+                                        (self.my_nav_item("/my/visit", "访问"))
+                                        (self.my_nav_item("/my/comment", "评论"))
                                         (self.my_nav_item("/my/inbox", "收信箱"))
                                         (self.my_nav_item("/my/outbox", "发信箱"))
                                         (self.my_nav_item("/my/info", "设置"))
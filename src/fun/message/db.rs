@@ -1,12 +1,19 @@
 use chrono::{DateTime, Utc};
 use sqlx::types::Uuid;
+use std::collections::HashMap;
 use std::str::FromStr;
 
+use crate::db::filter::{self, Filter};
 use crate::fun::message::List;
 use crate::fun::user::db::{db_get_user, By};
 use crate::http::WebContext;
 use crate::md;
 
+/// i_type：系统通知，只在收信箱里，不能回复
+pub const NOTICE: i16 = 1;
+/// 系统通知的发信人编号，没有这个用户
+pub const SYSTEM_USER: i32 = 0;
+
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct Message {
     pub id: Uuid,
@@ -105,29 +112,29 @@ impl ListBy {
 }
 
 pub async fn db_list<'a>(list: &List<'a>) -> anyhow::Result<(i64, Vec<MessageSimple>)> {
-    let offset = list.size as u32 * (list.page - 1);
-    let s_title = list.title_search_key.map_or("".to_string(), |k| {
-        format!("and lower(title) like '%{}%' ", k.to_lowercase())
-    });
-    let sql_where = match list.filter {
-        ListBy::UserInbox(id) => format!("where to_user_id={id} and in_public=1 {}", s_title),
-        ListBy::UserOutbox(id) => format!("where user_id={id} and out_public=1 {}", s_title),
-    };
-    let sql_total = format!("select count(*) as total from message {};", &sql_where);
-    let row = sqlx::query_as::<_, DbTotal>(&sql_total)
+    let filter = match list.filter {
+        ListBy::UserInbox(id) => Filter::new("message")
+            .eq("to_user_id", id)
+            .eq("in_public", 1i16),
+        ListBy::UserOutbox(id) => Filter::new("message")
+            .eq("user_id", id)
+            .eq("out_public", 1i16),
+    }
+    .title_like(list.title_search_key);
+    let total = filter
+        .count()
+        .build_query_as::<DbTotal>()
         .fetch_one(&list.ctx.db)
-        .await?;
-    let total = row.total;
-    let sql = format!(
-        r#"
-            select
-                id, title, i_status, created_at, user_name, to_user_name
-            from message
-            {}
-            order by created_at desc limit {} offset {};"#,
-        sql_where, list.size, offset
-    );
-    let rows = sqlx::query_as::<_, MessageSimple>(&sql)
+        .await?
+        .total;
+    let rows = filter
+        .select(
+            "id, title, i_status, created_at, user_name, to_user_name",
+            "created_at desc",
+            list.size as i64,
+            filter::offset(list.page, list.size),
+        )
+        .build_query_as::<MessageSimple>()
         .fetch_all(&list.ctx.db)
         .await?;
     Ok((total, rows))
@@ -140,7 +147,7 @@ pub async fn db_insert(
     input: &Input,
     to_user_id: i32,
 ) -> anyhow::Result<Uuid> {
-    let html = md::to_html(input.body.as_str());
+    let html = md::to_html(&ctx.config, md::Content::Comment, &input.body);
 
     let rec = sqlx::query!(
         r#"insert into message
@@ -160,6 +167,28 @@ pub async fn db_insert(
     Ok(rec.id)
 }
 
+/// 以网站的名义发通知，发信箱里没有
+pub async fn db_notice(ctx: &WebContext, to_user_id: i32, input: &Input) -> anyhow::Result<Uuid> {
+    let html = md::to_html(&ctx.config, md::Content::Comment, &input.body);
+    let rec = sqlx::query!(
+        r#"insert into message
+             (user_id, user_name, to_user_id, to_user_name, i_type, title, body, html, out_public)
+           values ($1, $2, $3, $4, $5, $6, $7, $8, 0)
+           returning id"#,
+        SYSTEM_USER,
+        ctx.config.host.name,
+        to_user_id,
+        input.to_user_name,
+        NOTICE,
+        input.title,
+        input.body,
+        html
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec.id)
+}
+
 pub async fn db_get_one(ctx: &WebContext, id: &str) -> anyhow::Result<Message> {
     let uuid = Uuid::from_str(id)?;
     let rec = sqlx::query_as!(
@@ -204,3 +233,122 @@ pub async fn db_rm(ctx: &WebContext, id: &str) -> anyhow::Result<bool> {
 
     Ok(rows == 1)
 }
+
+/// 信件的回复
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Reply {
+    pub id: i32,
+    pub user_id: i32,
+    pub user_name: String,
+    pub body: String,
+    pub html: Option<String>,
+    pub i_status: i16,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+pub struct ReplyInput {
+    pub body: String,
+}
+
+/// 一封信的所有回复，先回复的在前
+pub async fn db_replies(ctx: &WebContext, message_id: Uuid) -> anyhow::Result<Vec<Reply>> {
+    let rows = sqlx::query_as!(
+        Reply,
+        r#"select id, user_id, user_name, body, html, i_status, created_at
+           from message_comment
+           where message_id=$1 and i_public=1
+           order by id"#,
+        message_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+/// 回复，对方删除了这封信时重新显示在对方的信箱里
+pub async fn db_reply_insert(
+    ctx: &WebContext,
+    sm: &Message,
+    user_id: i32,
+    user_name: &str,
+    body: &str,
+) -> anyhow::Result<i32> {
+    let html = md::to_html(&ctx.config, md::Content::Comment, body);
+    let mut tx = ctx.db.begin().await?;
+    let rec = sqlx::query!(
+        r#"insert into message_comment (user_id, user_name, message_id, i_public, body, html)
+           values ($1, $2, $3, 1, $4, $5)
+           returning id"#,
+        user_id,
+        user_name,
+        sm.id,
+        body,
+        html
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if sm.to_user_id == user_id {
+        sqlx::query!("update message set out_public=1 where id=$1", sm.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    if sm.user_id == user_id {
+        sqlx::query!("update message set in_public=1 where id=$1", sm.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(rec.id)
+}
+
+/// 对方的回复都改为已读
+pub async fn db_replies_read(
+    ctx: &WebContext,
+    message_id: Uuid,
+    user_id: i32,
+) -> anyhow::Result<u64> {
+    let rows = sqlx::query!(
+        r#"update message_comment set i_status=1
+           where message_id=$1 and user_id<>$2 and i_status=0"#,
+        message_id,
+        user_id
+    )
+    .execute(&ctx.db)
+    .await?
+    .rows_affected();
+    Ok(rows)
+}
+
+/// 每封信里对方还没有读的回复数
+pub async fn db_unread_replies(
+    ctx: &WebContext,
+    user_id: i32,
+    ids: &[Uuid],
+) -> anyhow::Result<HashMap<Uuid, i64>> {
+    let rows = sqlx::query!(
+        r#"select message_id as "message_id!", count(*) as "total!"
+           from message_comment
+           where message_id = any($1) and user_id<>$2 and i_status=0 and i_public=1
+           group by message_id"#,
+        ids,
+        user_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.message_id, r.total)).collect())
+}
+
+/// 所有信件里还没有读的回复数
+pub async fn db_new_reply_total(ctx: &WebContext, user_id: i32) -> anyhow::Result<i64> {
+    let rec = sqlx::query!(
+        r#"select count(*) as "total!"
+           from message_comment c join message m on m.id=c.message_id
+           where (m.user_id=$1 or m.to_user_id=$1)
+             and c.user_id<>$1 and c.i_status=0 and c.i_public=1"#,
+        user_id
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec.total)
+}
@@ -1,9 +1,16 @@
 //! 信箱
+//!
+//! 收信人和发信人都可以在信件下面回复，回复按时间顺序显示，对方没有读的回复在列表中显示条数。
+//! 评论等系统通知以网站的名义发送（i_type 是 NOTICE），只在收信箱里，不能回复。
 
 pub mod db;
 
-use axum::{routing::get, Router};
+use std::collections::HashMap;
+
+use axum::response::Redirect;
+use axum::{routing::get, routing::post, Router};
 use serde::Deserialize;
+use sqlx::types::Uuid;
 
 use axum::extract::{Form, MatchedPath, Path, Query, State};
 
@@ -13,7 +20,7 @@ use axum_session_sqlx::SessionPgPool;
 use maud::{html, Markup, PreEscaped};
 
 use crate::fun::layout::Html;
-use crate::fun::user::{get_user_from, is_sudo_role, SessUser};
+use crate::fun::user::{get_user_from, Cap, SessUser};
 use crate::fun::widget::*;
 use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
@@ -38,6 +45,7 @@ pub fn router() -> Router<WebContext> {
         .route("/my/outbox", get(sm_my_outbox))
         .route("/my/outbox/new", get(sm_new_input).post(sm_new_do))
         .route("/my/outbox/:id/index.html", get(sm_outbox_view))
+        .route("/my/message/reply/:id", post(sm_reply_do))
 }
 
 async fn sm_my_inbox(
@@ -51,16 +59,25 @@ async fn sm_my_inbox(
     let page = pagination.page;
 
     let new_total = db_new_total(&ctx, user.id).await?;
+    let new_reply = db_new_reply_total(&ctx, user.id).await?;
     let mut tip_new = None;
-    if new_total > 0 {
-        tip_new = Some(tip(format!("新信件 {} 封", new_total).as_str()));
+    if new_total > 0 || new_reply > 0 {
+        let mut s = Vec::new();
+        if new_total > 0 {
+            s.push(format!("新信件 {} 封", new_total));
+        }
+        if new_reply > 0 {
+            s.push(format!("新回复 {} 条", new_reply));
+        }
+        tip_new = Some(tip(s.join("，").as_str()));
     }
 
     let list = List::new(&ctx, ListBy::UserInbox(user.id), page)
         .pager(Some(path.as_str()))
         .i_tip(tip_new);
     let (total, data) = db_list(&list).await?;
-    let main = list.show(total, data);
+    let unread = unread_replies(&ctx, user.id, &data).await?;
+    let main = list.unread(unread).show(total, data);
 
     Ok(Html::new("收信箱", main)
         .path(Some(path.as_str()))
@@ -82,7 +99,8 @@ async fn sm_my_outbox(
         .pager(Some(path.as_str()))
         .show_type(ShowType::Outbox);
     let (total, data) = db_list(&list).await?;
-    let main = list.show(total, data);
+    let unread = unread_replies(&ctx, user.id, &data).await?;
+    let main = list.unread(unread).show(total, data);
     Ok(Html::new("发信箱", main)
         .path(Some(path.as_str()))
         .my_huxi(&user)
@@ -181,7 +199,8 @@ async fn sm_new_do(
         .i_tip(Some(tip))
         .pager(Some("/sm/outbox"));
     let (total, data) = db_list(&list).await?;
-    let main = list.show(total, data);
+    let unread = unread_replies(&ctx, user.id, &data).await?;
+    let main = list.unread(unread).show(total, data);
     Ok(Html::new("发送成功", main)
         .my_huxi(&user)
         .path(Some(path.as_str()))
@@ -208,13 +227,23 @@ pub async fn send_message(
 
 async fn check_owner(hu_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if hu_user_id == user.id || is_sudo_role(user.role) {
+    if user.can_manage(hu_user_id, Cap::ManageUser) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
     }
 }
 
+/// 列表中每封信没有读的回复数
+async fn unread_replies(
+    ctx: &WebContext,
+    user_id: i32,
+    data: &[MessageSimple],
+) -> Result<HashMap<Uuid, i64>> {
+    let ids: Vec<Uuid> = data.iter().map(|sm| sm.id).collect();
+    Ok(db_unread_replies(ctx, user_id, &ids).await?)
+}
+
 async fn sm_inbox_view(
     session: Session<SessionPgPool>,
     State(ctx): State<WebContext>,
@@ -223,23 +252,11 @@ async fn sm_inbox_view(
 ) -> Result<Page> {
     let user = get_user_from(&session).await?;
     let sm = db_get_one(&ctx, id.as_str()).await?;
-
-    // 检查权限
-    let mut allow = false;
-    if sm.user_id == user.id {
-        allow = true;
-    }
     if sm.to_user_id == user.id {
-        allow = true;
         // 更新为已经读过
         db_update_status(&ctx, sm.id.to_string()).await?;
     }
-
-    let main = if allow {
-        content_html(&sm, true)
-    } else {
-        tip("权限错误")
-    };
+    let main = thread_html(&ctx, &user, &sm, true).await?;
     Ok(Html::new(&sm.title, main)
         .path(Some(path.as_str()))
         .my_huxi(&user)
@@ -255,21 +272,7 @@ async fn sm_outbox_view(
 ) -> Result<Page> {
     let user = get_user_from(&session).await?;
     let sm = db_get_one(&ctx, id.as_str()).await?;
-
-    // 检查权限
-    let mut allow = false;
-    if sm.user_id == user.id {
-        allow = true;
-    }
-    if sm.to_user_id == user.id {
-        allow = true;
-    }
-
-    let main = if allow {
-        content_html(&sm, false)
-    } else {
-        tip("权限错误")
-    };
+    let main = thread_html(&ctx, &user, &sm, false).await?;
     Ok(Html::new(&sm.title, main)
         .path(Some(path.as_str()))
         .my_huxi(&user)
@@ -277,7 +280,52 @@ async fn sm_outbox_view(
         .page(&ctx))
 }
 
-fn content_html(sm: &Message, reply: bool) -> Markup {
+/// 只有发信人和收信人能看，看了以后对方的回复改为已读
+async fn thread_html(
+    ctx: &WebContext,
+    user: &SessUser,
+    sm: &Message,
+    inbox: bool,
+) -> Result<Markup> {
+    if sm.user_id != user.id && sm.to_user_id != user.id {
+        return Ok(tip("权限错误"));
+    }
+    let replies = db_replies(ctx, sm.id).await?;
+    db_replies_read(ctx, sm.id, user.id).await?;
+    Ok(content_html(sm, inbox, user.id, &replies))
+}
+
+/// 信件的地址，收信人回到收信箱，发信人回到发信箱
+fn view_url(sm: &Message, user_id: i32) -> String {
+    let box_name = if sm.to_user_id == user_id {
+        "inbox"
+    } else {
+        "outbox"
+    };
+    format!("/my/{}/{}/index.html", box_name, sm.id)
+}
+
+async fn sm_reply_do(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path(id): Path<String>,
+    Form(input): Form<ReplyInput>,
+) -> Result<Redirect> {
+    let user = get_user_from(&session).await?;
+    let sm = db_get_one(&ctx, &id).await?;
+    if (sm.user_id != user.id && sm.to_user_id != user.id) || sm.i_type == NOTICE {
+        return Err(AppError::Unauthorized);
+    }
+    let url = view_url(&sm, user.id);
+    let body = input.body.trim();
+    if body.is_empty() {
+        return Ok(Redirect::to(&format!("{}#reply", url)));
+    }
+    let reply_id = db_reply_insert(&ctx, &sm, user.id, &user.name, body).await?;
+    Ok(Redirect::to(&format!("{}#reply-{}", url, reply_id)))
+}
+
+fn content_html(sm: &Message, inbox: bool, user_id: i32, replies: &[Reply]) -> Markup {
     html! {
         div class="container" {
             div class="row justify-content-center" {
@@ -286,7 +334,7 @@ fn content_html(sm: &Message, reply: bool) -> Markup {
                         div {
                             "时间：" (show_date(sm.created_at))
                         }
-                        @if reply {
+                        @if inbox {
                             "发信人：" (sm.user_name)
                         } @else {
                             "收信人：" (sm.to_user_name)
@@ -299,11 +347,32 @@ fn content_html(sm: &Message, reply: bool) -> Markup {
                             (PreEscaped(&sm.html))
                         }
                     }
-                    @if reply {
+                    @if !replies.is_empty() {
+                        hr;
+                        p {"回复 " (replies.len()) " 条："}
+                    }
+                    @for r in replies {
+                        div class="border-start border-3 ps-3 my-3" id={"reply-" (r.id)} {
+                            div class="small text-secondary" {
+                                span class="me-3" {(r.user_name)}
+                                (show_time(r.created_at))
+                                @if r.user_id == user_id && r.i_status == 0 {
+                                    span class="ms-2 text-danger" {"未读"}
+                                }
+                            }
+                            div class="md" {
+                                (PreEscaped(r.html.as_deref().unwrap_or_default()))
+                            }
+                        }
+                    }
+                    @if sm.i_type != NOTICE {
                         hr;
-                        @let title = format!("回复：{}", sm.title);
-                        a href={"/my/outbox/new?to=" (urlencoding::encode(sm.user_name.as_str()))
-                                "&title=" (urlencoding::encode(title.as_str())) "#start"} {"回复"}
+                        form id="reply" action={"/my/message/reply/" (sm.id)} method="post" {
+                            (TextArea::new("body", "body", true).rows(4).md().show())
+                            div class="text-center bg-light" {
+                                (submit("回复"))
+                            }
+                        }
                     }
                 }
             }
@@ -346,6 +415,7 @@ pub struct List<'a> {
     size: u8,
     pager: Option<&'a str>,
     show_type: ShowType,
+    unread: HashMap<Uuid, i64>,
 }
 
 impl<'a> List<'a> {
@@ -361,6 +431,7 @@ impl<'a> List<'a> {
             pager: None,
             show_type: ShowType::Inbox,
             title_search_key: None,
+            unread: HashMap::new(),
         }
     }
     pub fn i_tip(mut self, tip: Option<Markup>) -> Self {
@@ -391,6 +462,11 @@ impl<'a> List<'a> {
         self.show_type = show;
         self
     }
+    /// 每封信没有读的回复数
+    pub fn unread(mut self, unread: HashMap<Uuid, i64>) -> Self {
+        self.unread = unread;
+        self
+    }
 
     pub fn show(self, total: i64, data: Vec<MessageSimple>) -> Markup {
         let box_name = match self.filter {
@@ -414,6 +490,9 @@ impl<'a> List<'a> {
                             @if self.filter.is_inbox() {
                                 span class="mx-2 text-danger" {(get_status_name(INBOX_STATUS_MAP, sm.i_status).map_or("", |v| v)) }
                             }
+                            @if let Some(n) = self.unread.get(&sm.id) {
+                                span class="badge bg-danger" {"新回复 " (n)}
+                            }
                         }
                         div class="col col-md-3" {
                             @if box_name == "inbox" {
@@ -475,7 +554,7 @@ async fn _sm_search_my(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::UserInbox(user.id), page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .title_search_key(Some(key))
         .admin(true);
     let (total, data) = db_list(&list).await?;
@@ -1,10 +1,22 @@
+pub mod admin;
 pub mod book;
 pub mod comment;
+pub mod embed;
+pub mod feed;
 pub mod hu;
 pub mod image;
+pub mod kind;
 pub mod layout;
 pub mod message;
+pub mod publish;
+pub mod rerender;
+pub mod revision;
+pub mod search;
+pub mod sitemap;
+pub mod star;
+pub mod tag;
 pub mod user;
+pub mod visit;
 pub mod widget;
 pub mod xi;
 
@@ -13,7 +25,7 @@ use axum::{extract::State, routing::get, Router};
 use axum_session::Session;
 use axum_session_sqlx::SessionPgPool;
 
-use crate::fun::user::check_sudo;
+use crate::fun::user::{check_cap, Cap};
 use crate::md;
 use layout::Html;
 use maud::{html, PreEscaped};
@@ -30,11 +42,12 @@ pub fn router() -> Router<WebContext> {
 }
 
 async fn index(State(ctx): State<WebContext>, session: Session<SessionPgPool>) -> Result<Page> {
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let hu_top = hu::list_pub_top(&ctx, sudo).await?;
     let xi_top = xi::list_pub_top(&ctx, sudo).await?;
     let book_top = book::list_pub_top(&ctx, sudo).await?;
     let cat_name = hu::list_category_name(&ctx, false);
+    let cloud = tag::tag_cloud(&ctx).await?;
     let main = html! {
         div class="row row-cols-1" {
             div class="col col-md-9" {
@@ -65,6 +78,7 @@ async fn index(State(ctx): State<WebContext>, session: Session<SessionPgPool>) -
             }
             div class="col col-md-3" {
                 (cat_name)
+                (cloud)
             }
         }
     };
@@ -87,7 +101,7 @@ async fn error_404(State(ctx): State<WebContext>) -> Result<Page> {
 async fn doc_about(State(ctx): State<WebContext>) -> Result<Page> {
     let doc = std::fs::read_to_string("htdocs/docs/about.md")
         .map_err(|_| anyhow::anyhow!("read about.md error"))?;
-    let html = md::to_html(doc.as_str());
+    let html = md::to_html(&ctx.config, md::Content::Article, &doc);
     let main = html! {
         div class="shadow-lg p-5 mb-5 bg-body rounded md" {
             (PreEscaped(html))
@@ -99,7 +113,7 @@ async fn doc_about(State(ctx): State<WebContext>) -> Result<Page> {
 async fn doc_help(State(ctx): State<WebContext>) -> Result<Page> {
     let doc = std::fs::read_to_string("htdocs/docs/help.md")
         .map_err(|_| anyhow::anyhow!("read help.md error"))?;
-    let html = md::to_html(doc.as_str());
+    let html = md::to_html(&ctx.config, md::Content::Article, &doc);
     let main = html! {
         div class="shadow-lg p-5 mb-5 bg-body rounded md" {
             (PreEscaped(html))
@@ -111,7 +125,7 @@ async fn doc_help(State(ctx): State<WebContext>) -> Result<Page> {
 async fn doc_contact(State(ctx): State<WebContext>) -> Result<Page> {
     let doc = std::fs::read_to_string("htdocs/docs/contact.md")
         .map_err(|_| anyhow::anyhow!("read help.md error"))?;
-    let html = md::to_html(doc.as_str());
+    let html = md::to_html(&ctx.config, md::Content::Article, &doc);
     let main = html! {
         div class="shadow-lg p-5 mb-5 bg-body rounded md" {
             (PreEscaped(html))
@@ -0,0 +1,49 @@
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+
+use super::SCHEDULED;
+
+/// 到时间的定时发布改成公布，发布时间作为创建时间，返回改了几条
+pub async fn db_publish_due(ctx: &WebContext) -> anyhow::Result<u64> {
+    let mut total = 0;
+    for kind in Kind::ALL {
+        let sql = match kind {
+            Kind::Hu => {
+                "update hu set i_public=1, created_at=publish_at, publish_at=null
+                 where i_public=$1 and publish_at<=now()"
+            }
+            Kind::Xi => {
+                "update xi set i_public=1, created_at=publish_at, publish_at=null
+                 where i_public=$1 and publish_at<=now()"
+            }
+            Kind::Book => {
+                "update book set i_public=1, created_at=publish_at, publish_at=null
+                 where i_public=$1 and publish_at<=now()"
+            }
+        };
+        total += sqlx::query(sql)
+            .bind(SCHEDULED)
+            .execute(&ctx.db)
+            .await?
+            .rows_affected();
+    }
+    Ok(total)
+}
+
+/// 取消定时发布，改成草稿
+pub async fn db_cancel(ctx: &WebContext, kind: Kind, id: i32) -> anyhow::Result<bool> {
+    let sql = match kind {
+        Kind::Hu => "update hu set i_public=0, publish_at=null where hu_id=$1 and i_public=$2",
+        Kind::Xi => "update xi set i_public=0, publish_at=null where xi_id=$1 and i_public=$2",
+        Kind::Book => {
+            "update book set i_public=0, publish_at=null where book_id=$1 and i_public=$2"
+        }
+    };
+    let rows = sqlx::query(sql)
+        .bind(id)
+        .bind(SCHEDULED)
+        .execute(&ctx.db)
+        .await?
+        .rows_affected();
+    Ok(rows > 0)
+}
@@ -0,0 +1,134 @@
+//! 定时发布
+//!
+//! 文章、微博、好书的状态选“定时发布”时 i_public 为 2，发布时间记在 publish_at。
+//! 后台任务每分钟把到时间的改成公布，作者可以在自己的列表中取消，取消以后是草稿。
+
+pub mod db;
+
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::response::Redirect;
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use maud::{html, Markup};
+
+use crate::fun::kind::{self, Kind};
+use crate::fun::user::{get_user_from, Cap};
+use crate::fun::widget::{show_time, Back};
+use crate::http::{error::AppError, Result, WebContext};
+
+use db::*;
+
+/// i_public 的定时发布状态
+pub const SCHEDULED: i16 = 2;
+
+/// 多久检查一次
+const CHECK_SECONDS: u64 = 60;
+
+/// 表单中 datetime-local 的格式
+const INPUT_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+pub fn router() -> Router<WebContext> {
+    Router::new().route("/my/publish/cancel/:kind/:id", get(publish_cancel))
+}
+
+/// 表单中的本地时间，可以带秒
+pub fn parse(s: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(s, INPUT_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// 显示在表单中的发布时间
+pub fn input_value(dt: Option<DateTime<Utc>>) -> Option<String> {
+    dt.map(|dt| dt.with_timezone(&Local).format(INPUT_FORMAT).to_string())
+}
+
+/// 定时发布时检查发布时间，不是定时发布时清空
+pub fn check(i_public: i16, publish_at: &mut Option<String>, error: &mut Vec<String>) {
+    if i_public != SCHEDULED {
+        *publish_at = None;
+        return;
+    }
+    match publish_at.as_deref().map(str::trim).and_then(parse) {
+        Some(at) if at > Utc::now() => {}
+        Some(_) => error.push("请输入以后的发布时间".to_string()),
+        None => error.push("请输入发布时间".to_string()),
+    }
+}
+
+/// 保存到数据库的发布时间
+pub fn publish_at(i_public: i16, publish_at: Option<&str>) -> Option<DateTime<Utc>> {
+    if i_public == SCHEDULED {
+        publish_at.map(str::trim).and_then(parse)
+    } else {
+        None
+    }
+}
+
+/// 表单中状态后面的发布时间
+pub fn publish_input(value: Option<&String>) -> Markup {
+    html! {
+        span class="p-3 d-inline-block" {
+            label for="publish_at" class="me-2" {"发布时间"}
+            input class="form-control d-inline-block w-auto" id="publish_at" type="datetime-local"
+                name="publish_at" value=[value];
+        }
+    }
+}
+
+/// 列表中的发布时间和取消链接
+pub fn scheduled_info(
+    kind: Kind,
+    id: i32,
+    publish_at: Option<DateTime<Utc>>,
+    back: &str,
+) -> Markup {
+    html! {
+        @if let Some(at) = publish_at {
+            div class="small text-secondary" {(show_time(at)) " 发布"}
+        }
+        a href={"/my/publish/cancel/" (kind.table()) "/" (id) "?url=" (urlencoding::encode(back))}
+            onclick="return confirm('取消定时发布？取消后是草稿')" {"取消定时"}
+    }
+}
+
+/// 后台定时发布
+pub async fn publish_task(ctx: WebContext) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_SECONDS));
+    loop {
+        interval.tick().await;
+        match db_publish_due(&ctx).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("published {} scheduled items", n),
+            Err(e) => tracing::error!("publish scheduled: {:#}", e),
+        }
+    }
+}
+
+async fn publish_cancel(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+    back: Option<Query<Back>>,
+) -> Result<Redirect> {
+    let user = get_user_from(&session).await?;
+    let kind = Kind::from_path(&kind).ok_or(AppError::NotFound("kind"))?;
+    let owner = kind::db_owner(&ctx, kind, id)
+        .await?
+        .ok_or(AppError::NotFound("publish"))?;
+    if !user.can_manage(owner.user_id, Cap::EditAll) {
+        return Err(AppError::Unauthorized);
+    }
+    db_cancel(&ctx, kind, id).await?;
+    let Query(back) = back.unwrap_or_default();
+    let url = back.url_or(format!("/my{}", kind.path()));
+    Ok(Redirect::to(&url))
+}
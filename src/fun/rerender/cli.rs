@@ -0,0 +1,52 @@
+//! 重新生成 HTML 的命令：huxi-server rerender [--dry-run] [表...]
+
+use std::io::Write;
+
+use crate::config::RerenderArgs;
+use crate::http::WebContext;
+
+use super::{tables, Report};
+
+pub async fn run(ctx: &WebContext, args: RerenderArgs) -> anyhow::Result<()> {
+    let tables = tables(&args.tables)?;
+    let report = super::run(ctx, &tables, args.dry_run, args.batch, show_progress).await?;
+    eprintln!();
+    for t in &report.tables {
+        for c in &t.changed {
+            println!("{} {}: {}", t.table, c.key, c.columns.join(", "));
+        }
+        for key in &t.skipped {
+            println!("{} {}: skipped, modified while rerendering", t.table, key);
+        }
+    }
+    let verb = if args.dry_run {
+        "would change"
+    } else {
+        "changed"
+    };
+    println!("{} {} rows", verb, report.changed());
+    if report.skipped() > 0 {
+        println!(
+            "skipped {} rows, run again to rerender them",
+            report.skipped()
+        );
+    }
+    Ok(())
+}
+
+/// 在同一行显示当前的表
+fn show_progress(report: &Report) {
+    if let Some(t) = report.tables.last() {
+        if t.scanned == 0 && report.tables.len() > 1 {
+            eprintln!();
+        }
+        eprint!(
+            "\r{:<16} {:>8}/{:<8} {} changed",
+            t.table,
+            t.scanned,
+            t.total,
+            t.changed.len()
+        );
+        std::io::stderr().flush().ok();
+    }
+}
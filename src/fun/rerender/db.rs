@@ -0,0 +1,227 @@
+use sqlx::Row;
+
+use crate::fun::embed::Rendered;
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+
+/// HTML 对应渲染结果里的哪一段
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Field {
+    Body,
+    Body2,
+    Brief,
+    Log,
+}
+
+/// 一列 Markdown 和由它生成的 HTML
+pub struct Column {
+    pub source: &'static str,
+    pub html: &'static str,
+    pub field: Field,
+}
+
+impl Column {
+    const fn new(source: &'static str, html: &'static str, field: Field) -> Self {
+        Column {
+            source,
+            html,
+            field,
+        }
+    }
+
+    /// 从渲染结果里取出这一列，没有 Markdown 时 HTML 也是空
+    pub fn pick(&self, rendered: &Rendered) -> Option<String> {
+        match self.field {
+            Field::Body => Some(rendered.html.clone()),
+            Field::Body2 => rendered.html2.clone(),
+            Field::Brief => rendered.brief_html.clone(),
+            Field::Log => rendered.log_html.clone(),
+        }
+    }
+}
+
+/// 保存了 HTML 的表
+pub struct Table {
+    pub name: &'static str,
+    pub key: &'static str,
+    /// 主键的类型，主键按 text 传递
+    pub key_type: &'static str,
+    pub columns: &'static [Column],
+    /// 文章、好书、微博和保存时一样渲染，其他的是评论
+    pub kind: Option<Kind>,
+}
+
+const ARTICLE: &[Column] = &[
+    Column::new("body", "html", Field::Body),
+    Column::new("body2", "html2", Field::Body2),
+    Column::new("brief", "brief_html", Field::Brief),
+    Column::new("log", "log_html", Field::Log),
+];
+const XI: &[Column] = &[Column::new("body", "html", Field::Body)];
+const COMMENT: &[Column] = &[Column::new("body", "html", Field::Body)];
+
+pub const TABLES: &[Table] = &[
+    Table {
+        name: "hu",
+        key: "hu_id",
+        key_type: "int",
+        columns: ARTICLE,
+        kind: Some(Kind::Hu),
+    },
+    Table {
+        name: "book",
+        key: "book_id",
+        key_type: "int",
+        columns: ARTICLE,
+        kind: Some(Kind::Book),
+    },
+    Table {
+        name: "xi",
+        key: "xi_id",
+        key_type: "int",
+        columns: XI,
+        kind: Some(Kind::Xi),
+    },
+    Table {
+        name: "hu_comment",
+        key: "id",
+        key_type: "int",
+        columns: COMMENT,
+        kind: None,
+    },
+    Table {
+        name: "book_comment",
+        key: "id",
+        key_type: "int",
+        columns: COMMENT,
+        kind: None,
+    },
+    Table {
+        name: "xi_comment",
+        key: "id",
+        key_type: "int",
+        columns: COMMENT,
+        kind: None,
+    },
+    Table {
+        name: "message",
+        key: "id",
+        key_type: "uuid",
+        columns: COMMENT,
+        kind: None,
+    },
+    Table {
+        name: "message_comment",
+        key: "id",
+        key_type: "int",
+        columns: COMMENT,
+        kind: None,
+    },
+];
+
+/// 一条记录：主键、作者和每一列的 (Markdown, HTML)
+pub struct Record {
+    pub key: String,
+    /// 文章、好书、微博的作者，引用的图片按作者查；评论是 0
+    pub user_id: i32,
+    pub values: Vec<(Option<String>, Option<String>)>,
+}
+
+pub async fn db_total(ctx: &WebContext, table: &Table) -> anyhow::Result<i64> {
+    let sql = format!("select count(*) from {}", table.name);
+    let total: i64 = sqlx::query_scalar(&sql).fetch_one(&ctx.db).await?;
+    Ok(total)
+}
+
+/// 按主键顺序取 after 后面的一批
+pub async fn db_batch(
+    ctx: &WebContext,
+    table: &Table,
+    after: Option<&str>,
+    limit: i64,
+) -> anyhow::Result<Vec<Record>> {
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .map(|c| format!("{}, {}", c.source, c.html))
+        .collect();
+    let user_id = if table.kind.is_some() { "user_id" } else { "0" };
+    let sql = format!(
+        "select {key}::text, {user_id}, {columns} from {name}
+         where $1::text is null or {key} > $1::text::{ty}
+         order by {key} limit $2",
+        key = table.key,
+        ty = table.key_type,
+        name = table.name,
+        columns = columns.join(", "),
+    );
+    let rows = sqlx::query(&sql)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&ctx.db)
+        .await?;
+    let mut records = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut values = Vec::with_capacity(table.columns.len());
+        for i in 0..table.columns.len() {
+            values.push((row.try_get(2 + i * 2)?, row.try_get(3 + i * 2)?));
+        }
+        records.push(Record {
+            key: row.try_get(0)?,
+            user_id: row.try_get(1)?,
+            values,
+        });
+    }
+    Ok(records)
+}
+
+/// 写入一批新的 HTML，不改 updated_at。
+/// 读出以后 Markdown 又被修改的记录不写，返回它们的主键
+pub async fn db_save(
+    ctx: &WebContext,
+    table: &Table,
+    records: &[(&Record, Vec<Option<String>>)],
+) -> anyhow::Result<Vec<String>> {
+    let n = table.columns.len();
+    let sets: Vec<String> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}=${}", c.html, i + 1))
+        .collect();
+    let sources: Vec<String> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{} is not distinct from ${}", c.source, n + 2 + i))
+        .collect();
+    let sql = format!(
+        "update {name} set {sets} where {key} = ${k}::text::{ty} and {sources}",
+        name = table.name,
+        sets = sets.join(", "),
+        key = table.key,
+        k = n + 1,
+        ty = table.key_type,
+        sources = sources.join(" and "),
+    );
+    let mut skipped = vec![];
+    let mut tx = ctx.db.begin().await?;
+    sqlx::query("set local huxi.keep_updated_at = 'on'")
+        .execute(&mut *tx)
+        .await?;
+    for (record, html) in records {
+        let mut query = sqlx::query(&sql);
+        for value in html {
+            query = query.bind(value);
+        }
+        query = query.bind(&record.key);
+        for (source, _) in &record.values {
+            query = query.bind(source);
+        }
+        if query.execute(&mut *tx).await?.rows_affected() == 0 {
+            skipped.push(record.key.clone());
+        }
+    }
+    tx.commit().await?;
+    Ok(skipped)
+}
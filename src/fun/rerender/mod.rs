@@ -0,0 +1,233 @@
+//! 重新生成 HTML
+//!
+//! 文章、好书、微博、评论和站内短信保存时就把 Markdown 转成 HTML 存起来，
+//! Markdown 的处理改了以后（允许的标签、高亮、公式等）旧内容不会跟着变。
+//! 命令 huxi-server rerender 和管理页 /admin/rerender 按表分批重新生成，
+//! 只写入有变化的记录，不改 updated_at；dry run 只列出会变的记录。
+
+pub mod cli;
+pub mod db;
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+
+use crate::fun::embed;
+use crate::fun::revision::db::Content;
+use crate::http::WebContext;
+use crate::md;
+
+use db::*;
+
+/// 管理页每批的记录数，命令行可以用 --batch 修改
+pub const BATCH: i64 = 200;
+
+/// 有变化的记录
+#[derive(Debug, Clone)]
+pub struct Changed {
+    pub key: String,
+    /// 变化的 HTML 列
+    pub columns: Vec<&'static str>,
+}
+
+/// 一张表的进度
+#[derive(Debug, Clone)]
+pub struct TableReport {
+    pub table: &'static str,
+    pub total: i64,
+    pub scanned: i64,
+    pub changed: Vec<Changed>,
+    /// 重新生成时 Markdown 被修改了，没有写入的记录
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub dry_run: bool,
+    pub running: bool,
+    pub tables: Vec<TableReport>,
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Report {
+    pub fn changed(&self) -> usize {
+        self.tables.iter().map(|t| t.changed.len()).sum()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.tables.iter().map(|t| t.skipped.len()).sum()
+    }
+}
+
+/// 管理页启动的任务的进度，放在 WebContext 里共享
+#[derive(Clone, Default)]
+pub struct Rerender(Arc<Mutex<Report>>);
+
+impl Rerender {
+    pub fn report(&self) -> Report {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// 在后台开始，已经在运行时返回 false
+    pub fn start(ctx: &WebContext, dry_run: bool) -> bool {
+        let state = ctx.rerender.clone();
+        {
+            let mut report = state.0.lock().unwrap();
+            if report.running {
+                return false;
+            }
+            *report = Report {
+                dry_run,
+                running: true,
+                started_at: Some(Utc::now()),
+                ..Default::default()
+            };
+        }
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let tables: Vec<&Table> = TABLES.iter().collect();
+            let result = run(&ctx, &tables, dry_run, BATCH, |r| {
+                *state.0.lock().unwrap() = r.clone();
+            })
+            .await;
+            let mut report = state.0.lock().unwrap();
+            match result {
+                Ok(r) => *report = r,
+                Err(e) => {
+                    tracing::error!("rerender: {:?}", e);
+                    report.error = Some(e.to_string());
+                }
+            }
+            report.running = false;
+            report.finished_at = Some(Utc::now());
+        });
+        true
+    }
+}
+
+/// 按名字找表，names 为空时是所有的表
+pub fn tables(names: &[String]) -> anyhow::Result<Vec<&'static Table>> {
+    if names.is_empty() {
+        return Ok(TABLES.iter().collect());
+    }
+    let mut found = Vec::with_capacity(names.len());
+    for name in names {
+        match TABLES.iter().find(|t| t.name == name) {
+            Some(t) => found.push(t),
+            None => {
+                let all: Vec<&str> = TABLES.iter().map(|t| t.name).collect();
+                bail!("没有这个表：{}，可以是 {}", name, all.join(", "));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// 逐表分批重新生成，每批以后调用 progress
+pub async fn run<F>(
+    ctx: &WebContext,
+    tables: &[&Table],
+    dry_run: bool,
+    batch: i64,
+    mut progress: F,
+) -> anyhow::Result<Report>
+where
+    F: FnMut(&Report),
+{
+    let mut report = Report {
+        dry_run,
+        running: true,
+        started_at: Some(Utc::now()),
+        ..Default::default()
+    };
+    for table in tables {
+        let total = db_total(ctx, table).await?;
+        report.tables.push(TableReport {
+            table: table.name,
+            total,
+            scanned: 0,
+            changed: vec![],
+            skipped: vec![],
+        });
+        progress(&report);
+        let mut after: Option<String> = None;
+        loop {
+            let records = db_batch(ctx, table, after.as_deref(), batch.max(1)).await?;
+            let last = match records.last() {
+                Some(r) => r.key.clone(),
+                None => break,
+            };
+            let mut updates = vec![];
+            let current = report.tables.last_mut().unwrap();
+            for record in &records {
+                let mut columns = vec![];
+                let html = render(ctx, table, record).await?;
+                for ((column, (_, old)), new) in table.columns.iter().zip(&record.values).zip(&html)
+                {
+                    if new != old {
+                        columns.push(column.html);
+                    }
+                }
+                if !columns.is_empty() {
+                    current.changed.push(Changed {
+                        key: record.key.clone(),
+                        columns,
+                    });
+                    updates.push((record, html));
+                }
+            }
+            current.scanned += records.len() as i64;
+            if !dry_run && !updates.is_empty() {
+                let skipped = db_save(ctx, table, &updates).await?;
+                current.changed.retain(|c| !skipped.contains(&c.key));
+                current.skipped.extend(skipped);
+            }
+            progress(&report);
+            after = Some(last);
+        }
+    }
+    report.running = false;
+    report.finished_at = Some(Utc::now());
+    Ok(report)
+}
+
+/// 和保存时一样生成一条记录的各列 HTML
+async fn render(
+    ctx: &WebContext,
+    table: &Table,
+    record: &Record,
+) -> anyhow::Result<Vec<Option<String>>> {
+    let source = |field: db::Field| {
+        table
+            .columns
+            .iter()
+            .zip(&record.values)
+            .find(|(c, _)| c.field == field)
+            .and_then(|(_, (source, _))| source.as_deref())
+    };
+    let kind = match table.kind {
+        Some(kind) => kind,
+        None => {
+            let html = source(db::Field::Body)
+                .map(|text| md::to_html(&ctx.config, md::Content::Comment, text));
+            return Ok(vec![html]);
+        }
+    };
+    // 正文没有时 HTML 也是空，和其他列一样
+    let body = match source(db::Field::Body) {
+        Some(body) => body,
+        None => return Ok(vec![None; table.columns.len()]),
+    };
+    let content = Content {
+        title: "",
+        brief: source(db::Field::Brief),
+        body,
+        body2: source(db::Field::Body2),
+        log: source(db::Field::Log),
+    };
+    let rendered = embed::render(ctx, kind, record.user_id, &content).await?;
+    Ok(table.columns.iter().map(|c| c.pick(&rendered)).collect())
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, Utc};
+
+use crate::fun::embed;
+use crate::fun::kind::{self, Kind};
+use crate::fun::user::SessUser;
+use crate::http::WebContext;
+
+/// 保存时的内容
+#[derive(Debug, Clone, Copy)]
+pub struct Content<'a> {
+    pub title: &'a str,
+    pub brief: Option<&'a str>,
+    pub body: &'a str,
+    pub body2: Option<&'a str>,
+    pub log: Option<&'a str>,
+}
+
+impl<'a> Content<'a> {
+    /// 要渲染的几段 Markdown
+    pub fn markdown(&self) -> Vec<&'a str> {
+        let mut texts = vec![self.body];
+        texts.extend(self.body2);
+        texts.extend(self.brief);
+        texts.extend(self.log);
+        texts
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Revision {
+    pub id: i32,
+    pub kind: String,
+    pub obj_id: i32,
+    pub user_id: i32,
+    pub user_name: String,
+    pub title: String,
+    pub brief: Option<String>,
+    pub body: String,
+    pub body2: Option<String>,
+    pub log: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Revision {
+    pub fn content(&self) -> Content<'_> {
+        Content {
+            title: &self.title,
+            brief: self.brief.as_deref(),
+            body: &self.body,
+            body2: self.body2.as_deref(),
+            log: self.log.as_deref(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct RevisionSimple {
+    pub id: i32,
+    pub user_name: String,
+    pub title: String,
+    /// 正文的字数
+    pub size: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 记一次修改，和上一次相同时不记，返回是否记录
+pub async fn db_add(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    user: &SessUser,
+    c: Content<'_>,
+) -> anyhow::Result<bool> {
+    let rows = sqlx::query!(
+        r#"insert into revision (kind, obj_id, user_id, user_name, title, brief, body, body2, log)
+           select $1, $2, $3, $4, $5, $6, $7, $8, $9
+           where not exists (
+             select 1 from (
+               select title, brief, body, body2, log from revision
+               where kind=$1 and obj_id=$2 order by id desc limit 1
+             ) r
+             where r.title=$5 and r.brief is not distinct from $6 and r.body=$7
+               and r.body2 is not distinct from $8 and r.log is not distinct from $9
+           )"#,
+        kind.table(),
+        id,
+        user.id,
+        user.name,
+        c.title,
+        c.brief,
+        c.body,
+        c.body2,
+        c.log
+    )
+    .execute(&ctx.db)
+    .await?
+    .rows_affected();
+    Ok(rows == 1)
+}
+
+/// 新的在前
+pub async fn db_list(ctx: &WebContext, kind: Kind, id: i32) -> anyhow::Result<Vec<RevisionSimple>> {
+    let rows = sqlx::query_as!(
+        RevisionSimple,
+        r#"select id, user_name, title, char_length(body) as "size!", created_at
+           from revision
+           where kind=$1 and obj_id=$2
+           order by id desc"#,
+        kind.table(),
+        id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn db_get(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    rev: i32,
+) -> anyhow::Result<Option<Revision>> {
+    let row = sqlx::query_as!(
+        Revision,
+        r#"select id, kind, obj_id, user_id, user_name, title, brief, body, body2, log, created_at
+           from revision
+           where kind=$1 and obj_id=$2 and id=$3"#,
+        kind.table(),
+        id,
+        rev
+    )
+    .fetch_optional(&ctx.db)
+    .await?;
+    Ok(row)
+}
+
+/// 用一条记录的内容覆盖现在的内容，同时记一次修改
+pub async fn db_restore(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    user: &SessUser,
+    rev: &Revision,
+) -> anyhow::Result<bool> {
+    let sql = match kind {
+        Kind::Hu => {
+            r#"update hu set title=$1, brief=$2, brief_html=$3, body=$4, html=$5,
+                   body2=$6, html2=$7, log=$8, log_html=$9
+               where hu_id=$10"#
+        }
+        Kind::Book => {
+            r#"update book set title=$1, brief=$2, brief_html=$3, body=$4, html=$5,
+                   body2=$6, html2=$7, log=$8, log_html=$9
+               where book_id=$10"#
+        }
+        Kind::Xi => return Ok(false),
+    };
+    // 和保存时一样渲染，引用的图片按内容的作者查
+    let owner = match kind::db_owner(ctx, kind, id).await? {
+        Some(owner) => owner,
+        None => return Ok(false),
+    };
+    let rendered = embed::render(ctx, kind, owner.user_id, &rev.content()).await?;
+    let rows = sqlx::query(sql)
+        .bind(&rev.title)
+        .bind(&rev.brief)
+        .bind(rendered.brief_html)
+        .bind(&rev.body)
+        .bind(rendered.html)
+        .bind(&rev.body2)
+        .bind(rendered.html2)
+        .bind(&rev.log)
+        .bind(rendered.log_html)
+        .bind(id)
+        .execute(&ctx.db)
+        .await?
+        .rows_affected();
+    if rows > 0 {
+        db_add(ctx, kind, id, user, rev.content()).await?;
+    }
+    Ok(rows > 0)
+}
@@ -0,0 +1,311 @@
+//! 修改记录
+//!
+//! 文章和好书每次保存都在 revision 里记一条，可以比较任意两条，恢复到以前的版本。
+//! 修改记录里有没有公布的旧内容，查看、比较和恢复都只能是作者或者有 EditAll 权限。
+
+pub mod db;
+
+use axum::extract::{Path, Query, State};
+use axum::response::Redirect;
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use maud::{html, Markup};
+use serde::Deserialize;
+use similar::{DiffOp, DiffTag, TextDiff};
+
+use crate::fun::kind::{self, Kind};
+use crate::fun::layout::Html;
+use crate::fun::user::{get_user_from, Cap, SessUser};
+use crate::fun::widget::*;
+use crate::http::types::Page;
+use crate::http::{error::AppError, Result, WebContext};
+
+use db::*;
+
+/// 比较时前后保留几行没有变化的
+const CONTEXT: usize = 3;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/revision/:kind/:id", get(revision_list))
+        .route("/revision/:kind/:id/diff", get(revision_diff))
+        .route("/my/revision/restore/:kind/:id/:rev", get(revision_restore))
+}
+
+pub fn list_url(kind: Kind, id: i32) -> String {
+    format!("/revision/{}/{}", kind.table(), id)
+}
+
+/// 只有文章和好书有修改记录
+fn parse_kind(kind: &str) -> Result<Kind> {
+    match Kind::from_path(kind) {
+        Some(k @ (Kind::Hu | Kind::Book)) => Ok(k),
+        _ => Err(AppError::NotFound("kind")),
+    }
+}
+
+/// 只有作者和有 EditAll 权限的用户能看、能恢复
+async fn access(
+    ctx: &WebContext,
+    session: &Session<SessionPgPool>,
+    kind: Kind,
+    id: i32,
+) -> Result<SessUser> {
+    let user = get_user_from(session).await?;
+    let owner = kind::db_owner(ctx, kind, id)
+        .await?
+        .ok_or(AppError::NotFound("revision"))?;
+    if !user.can_manage(owner.user_id, Cap::EditAll) {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(user)
+}
+
+fn page(ctx: &WebContext, title: &str, main: Markup, user: &SessUser) -> Page {
+    Html::new(title, main)
+        .path(Some("/revision"))
+        .my_huxi(user)
+        .page(ctx)
+}
+
+async fn revision_list(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+) -> Result<Page> {
+    let kind = parse_kind(&kind)?;
+    let user = access(&ctx, &session, kind, id).await?;
+    let rows = db_list(&ctx, kind, id).await?;
+    let main = html! {
+        div class="container" {
+            h5 class="m-2" {
+                a href={(kind.view_url(id)) "#start"} {
+                    (rows.first().map_or("", |r| r.title.as_str()))
+                }
+                span class="ms-3 text-secondary" {"修改记录"}
+            }
+            @if rows.is_empty() {
+                (tip("还没有修改记录"))
+            }
+            form action={(list_url(kind, id)) "/diff"} method="get" {
+                table class="table table-sm align-middle" {
+                    thead {
+                        tr {
+                            th {"旧"} th {"新"} th {"时间"} th {"修改人"} th {"标题"} th {"字数"} th {}
+                        }
+                    }
+                    tbody {
+                        @for (i, r) in rows.iter().enumerate() {
+                            tr {
+                                td { input class="form-check-input" type="radio" name="from" value=(r.id) checked[i == 1]; }
+                                td { input class="form-check-input" type="radio" name="to" value=(r.id) checked[i == 0]; }
+                                td {(show_time(r.created_at))}
+                                td {(r.user_name)}
+                                td {(r.title)}
+                                td {(r.size)}
+                                td {
+                                    @if let Some(prev) = rows.get(i + 1) {
+                                        a class="me-2" href={(list_url(kind, id)) "/diff?from=" (prev.id) "&to=" (r.id)} {"比较上一版"}
+                                    }
+                                    @if i > 0 {
+                                        a class="text-danger" href={"/my/revision/restore/" (kind.table()) "/" (id) "/" (r.id)}
+                                            onclick="return confirm('恢复到这个版本？')" {"恢复"}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                @if rows.len() > 1 {
+                    div class="m-2" {
+                        select class="form-select d-inline-block w-auto me-2" name="mode" {
+                            option value="unified" {"合并显示"}
+                            option value="split" {"左右对照"}
+                        }
+                        button class="btn btn-primary" type="submit" {"比较"}
+                    }
+                }
+            }
+        }
+    };
+    Ok(page(&ctx, "修改记录", main, &user))
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct DiffArgs {
+    from: Option<i32>,
+    to: Option<i32>,
+    /// unified 或者 split
+    mode: Option<String>,
+}
+
+async fn revision_diff(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+    args: Option<Query<DiffArgs>>,
+) -> Result<Page> {
+    let kind = parse_kind(&kind)?;
+    let user = access(&ctx, &session, kind, id).await?;
+    let Query(args) = args.unwrap_or_default();
+    // 没有指定时比较最新的两条
+    let (from, to) = match (args.from, args.to) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            let rows = db_list(&ctx, kind, id).await?;
+            let to = rows.first().ok_or(AppError::NotFound("revision"))?;
+            (rows.get(1).unwrap_or(to).id, to.id)
+        }
+    };
+    let old = db_get(&ctx, kind, id, from)
+        .await?
+        .ok_or(AppError::NotFound("revision"))?;
+    let new = db_get(&ctx, kind, id, to)
+        .await?
+        .ok_or(AppError::NotFound("revision"))?;
+    let split = args.mode.as_deref() == Some("split");
+    let mode_url = |mode: &str| {
+        format!(
+            "{}/diff?from={}&to={}&mode={}",
+            list_url(kind, id),
+            from,
+            to,
+            mode
+        )
+    };
+    let fields = [
+        ("标题", Some(old.title.as_str()), Some(new.title.as_str())),
+        ("简介", old.brief.as_deref(), new.brief.as_deref()),
+        ("正文", Some(old.body.as_str()), Some(new.body.as_str())),
+        ("附录", old.body2.as_deref(), new.body2.as_deref()),
+        (
+            if kind == Kind::Book {
+                "目录"
+            } else {
+                "更新日志"
+            },
+            old.log.as_deref(),
+            new.log.as_deref(),
+        ),
+    ];
+    let changed = fields.iter().any(|f| f.1 != f.2);
+    let main = html! {
+        div class="container" {
+            h5 class="m-2" {
+                a href={(kind.view_url(id)) "#start"} {(new.title)}
+                span class="ms-3 text-secondary" {"比较修改"}
+            }
+            div class="row m-2" {
+                div class="col text-danger" {
+                    "#" (old.id) " " (show_time(old.created_at)) " " (old.user_name)
+                }
+                div class="col text-success" {
+                    "#" (new.id) " " (show_time(new.created_at)) " " (new.user_name)
+                }
+                div class="col text-end" {
+                    a class="me-2" href=(list_url(kind, id)) {"修改记录"}
+                    @if split {
+                        a href=(mode_url("unified")) {"合并显示"}
+                    } @else {
+                        a href=(mode_url("split")) {"左右对照"}
+                    }
+                }
+            }
+            @if !changed {
+                (tip("两个版本相同"))
+            }
+            @for (label, a, b) in &fields {
+                @if a != b {
+                    h6 class="m-2 mt-4" {(label)}
+                    (diff_html(a.unwrap_or_default(), b.unwrap_or_default(), split))
+                }
+            }
+        }
+    };
+    Ok(page(&ctx, "比较修改", main, &user))
+}
+
+fn line(s: &str) -> &str {
+    s.trim_end_matches(['\r', '\n'])
+}
+
+/// 按行比较，只显示有变化的地方和前后几行
+fn diff_html(old: &str, new: &str, split: bool) -> Markup {
+    let diff = TextDiff::from_lines(old, new);
+    let olds = diff.old_slices();
+    let news = diff.new_slices();
+    let cell = "font-monospace small text-break";
+    html! {
+        table class="table table-sm table-borderless border" style="white-space: pre-wrap" {
+            @for (g, group) in diff.grouped_ops(CONTEXT).iter().enumerate() {
+                @if g > 0 {
+                    tr class="table-light" { td colspan="4" class="text-center text-secondary" {"⋯"} }
+                }
+                @for op in group {
+                    @if split {
+                        @for (o, n) in split_rows(op) {
+                            tr {
+                                td class="text-end text-secondary small" {(o.map_or(String::new(), |i| (i + 1).to_string()))}
+                                td class={(cell) " w-50 " (side_class(op.tag(), true))} {(o.map_or("", |i| line(olds[i])))}
+                                td class="text-end text-secondary small" {(n.map_or(String::new(), |i| (i + 1).to_string()))}
+                                td class={(cell) " w-50 " (side_class(op.tag(), false))} {(n.map_or("", |i| line(news[i])))}
+                            }
+                        }
+                    } @else {
+                        @for change in diff.iter_changes(op) {
+                            @let (sign, class) = match change.tag() {
+                                similar::ChangeTag::Delete => ("-", "table-danger"),
+                                similar::ChangeTag::Insert => ("+", "table-success"),
+                                similar::ChangeTag::Equal => (" ", ""),
+                            };
+                            tr class=(class) {
+                                td class="text-end text-secondary small" {(change.old_index().map_or(String::new(), |i| (i + 1).to_string()))}
+                                td class="text-end text-secondary small" {(change.new_index().map_or(String::new(), |i| (i + 1).to_string()))}
+                                td class="font-monospace small" {(sign)}
+                                td class=(cell) {(line(change.value()))}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 左右对照时每一行两边的行号，替换的部分一行对一行
+fn split_rows(op: &DiffOp) -> Vec<(Option<usize>, Option<usize>)> {
+    let (_, olds, news) = op.as_tag_tuple();
+    let rows = olds.len().max(news.len());
+    (0..rows)
+        .map(|i| {
+            let o = Some(olds.start + i).filter(|x| olds.contains(x));
+            let n = Some(news.start + i).filter(|x| news.contains(x));
+            (o, n)
+        })
+        .collect()
+}
+
+fn side_class(tag: DiffTag, old: bool) -> &'static str {
+    match tag {
+        DiffTag::Equal => "",
+        DiffTag::Delete | DiffTag::Replace if old => "table-danger",
+        DiffTag::Insert | DiffTag::Replace if !old => "table-success",
+        _ => "table-light",
+    }
+}
+
+async fn revision_restore(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id, rev)): Path<(String, i32, i32)>,
+) -> Result<Redirect> {
+    let kind = parse_kind(&kind)?;
+    let user = access(&ctx, &session, kind, id).await?;
+    let rev = db_get(&ctx, kind, id, rev)
+        .await?
+        .ok_or(AppError::NotFound("revision"))?;
+    db_restore(&ctx, kind, id, &user, &rev).await?;
+    Ok(Redirect::to(&format!("{}#start", kind.view_url(id))))
+}
@@ -0,0 +1,176 @@
+use crate::db::search::Backend;
+use crate::fun::tag::IMAGE;
+use crate::http::WebContext;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+
+/// 高亮的开始和结束，转义以后再换成 <mark>
+pub const START_SEL: &str = "\u{e000}";
+pub const STOP_SEL: &str = "\u{e001}";
+
+/// 搜索结果的一条，image 没有分类和作者名
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct SearchHit {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub cat: Option<i16>,
+    pub user_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub rank: f32,
+    pub snippet: Option<String>,
+}
+
+/// 按类型和分类的结果数
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Facet {
+    pub kind: String,
+    pub cat: Option<i16>,
+    pub total: i64,
+}
+
+/// 参与搜索的表
+struct Source {
+    kind: &'static str,
+    id: &'static str,
+    cat: &'static str,
+    user_name: &'static str,
+    /// 生成摘要的文字
+    doc: &'static str,
+}
+
+const SOURCES: [Source; 4] = [
+    Source {
+        kind: "hu",
+        id: "hu_id",
+        cat: "i_category",
+        user_name: "user_name",
+        doc: "concat_ws(' ', brief, body)",
+    },
+    Source {
+        kind: "xi",
+        id: "xi_id",
+        cat: "i_category",
+        user_name: "user_name",
+        doc: "body",
+    },
+    Source {
+        kind: "book",
+        id: "book_id",
+        cat: "i_category",
+        user_name: "user_name",
+        doc: "concat_ws(' ', brief, body)",
+    },
+    Source {
+        kind: IMAGE,
+        id: "id",
+        cat: "null::smallint",
+        user_name: "null::text",
+        doc: "concat_ws(' ', tags, brief)",
+    },
+];
+
+/// 公布的文章、微博、好书，和自己的图片里匹配的记录
+fn push_hits(
+    qb: &mut QueryBuilder<'static, Postgres>,
+    backend: Backend,
+    key: &str,
+    user_id: Option<i32>,
+    full: bool,
+) {
+    let sources = SOURCES
+        .iter()
+        .filter(|s| s.kind != IMAGE || user_id.is_some());
+    for (i, s) in sources.enumerate() {
+        if i > 0 {
+            qb.push(" union all ");
+        }
+        qb.push("select '")
+            .push(s.kind)
+            .push("'::text as kind, ")
+            .push(s.cat)
+            .push(" as cat");
+        if full {
+            qb.push(", ")
+                .push(s.id)
+                .push(" as id, title, ")
+                .push(s.user_name)
+                .push(" as user_name, created_at, ");
+            backend.push_rank(qb, s.kind, key);
+            qb.push(" as rank, ").push(s.doc).push(" as doc");
+        }
+        qb.push(" from ").push(s.kind).push(" where ");
+        match user_id {
+            Some(user_id) if s.kind == IMAGE => {
+                qb.push("user_id=").push_bind(user_id);
+            }
+            _ => {
+                qb.push("i_public=1");
+            }
+        }
+        qb.push(" and ");
+        backend.push_match(qb, s.kind, key);
+    }
+}
+
+/// 各类型、分类的结果数
+pub async fn db_facets(
+    ctx: &WebContext,
+    key: &str,
+    user_id: Option<i32>,
+) -> anyhow::Result<Vec<Facet>> {
+    let mut qb = QueryBuilder::new("select kind, cat, count(*) as total from (");
+    push_hits(&mut qb, ctx.config.search.backend, key, user_id, false);
+    qb.push(") t group by kind, cat order by 1, 2");
+    let rows = qb.build_query_as().fetch_all(&ctx.db).await?;
+    Ok(rows)
+}
+
+/// 按相关度排序的一页结果，kind、cat 为空时不限
+pub async fn db_search(
+    ctx: &WebContext,
+    key: &str,
+    user_id: Option<i32>,
+    kind: Option<&str>,
+    cat: Option<i16>,
+    size: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<SearchHit>> {
+    let backend = ctx.config.search.backend;
+    let mut qb = QueryBuilder::new("select kind, id, title, cat, user_name, created_at, rank, ");
+    // 只对当前这一页生成摘要
+    backend.push_headline(&mut qb, "doc", key, START_SEL, STOP_SEL);
+    qb.push(" as snippet from (select * from (");
+    push_hits(&mut qb, backend, key, user_id, true);
+    qb.push(") t where true");
+    if let Some(kind) = kind {
+        qb.push(" and kind=").push_bind(kind.to_string());
+    }
+    if let Some(cat) = cat {
+        qb.push(" and cat=").push_bind(cat);
+    }
+    qb.push(" order by rank desc, created_at desc limit ")
+        .push_bind(size)
+        .push(" offset ")
+        .push_bind(offset)
+        .push(") t order by rank desc, created_at desc");
+    let mut rows: Vec<SearchHit> = qb.build_query_as().fetch_all(&ctx.db).await?;
+    if backend.ts_config().is_none() {
+        mark(&mut rows, key);
+    }
+    Ok(rows)
+}
+
+/// 没有 ts_headline 时自己标出搜索词，不分大小写
+fn mark(rows: &mut [SearchHit], key: &str) {
+    let re = match regex::Regex::new(&format!("(?i){}", regex::escape(key))) {
+        Ok(re) => re,
+        Err(_) => return,
+    };
+    for row in rows {
+        if let Some(snippet) = &row.snippet {
+            let marked = re.replace_all(snippet, format!("{}$0{}", START_SEL, STOP_SEL));
+            row.snippet = Some(marked.into_owned());
+        }
+    }
+}
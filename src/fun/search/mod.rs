@@ -0,0 +1,218 @@
+//! 全站搜索
+//!
+//! 在公布的文章、微博、好书和自己的图片里全文搜索，按 ts_rank 排序，
+//! 用 ts_headline 生成高亮摘要，右侧显示按类型、分类的结果数。
+
+pub mod db;
+
+use axum::extract::{Query, State};
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use maud::{html, Markup, PreEscaped};
+use serde::Deserialize;
+
+use crate::db::filter;
+use crate::fun::kind::Kind;
+use crate::fun::layout::{split, Html};
+use crate::fun::tag::IMAGE;
+use crate::fun::user::get_user_from;
+use crate::fun::widget::*;
+use crate::http::types::Page;
+use crate::http::{Result, WebContext};
+
+use db::*;
+
+const PAGE_SIZE: u8 = 20;
+
+pub fn router() -> Router<WebContext> {
+    Router::new().route("/search", get(search))
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct SearchArgs {
+    pub key: Option<String>,
+    /// hu、xi、book、image
+    pub kind: Option<String>,
+    pub cat: Option<i16>,
+    pub page: u32,
+}
+
+fn kind_name(kind: &str) -> &'static str {
+    Kind::from_path(kind).map_or("图片", |k| k.name())
+}
+
+fn view_url(kind: &str, id: i32) -> String {
+    match Kind::from_path(kind) {
+        Some(k) => format!("{}#start", k.view_url(id)),
+        None => format!("/image/view/{}", id),
+    }
+}
+
+fn cat_name(ctx: &WebContext, kind: &str, cat: i16) -> Option<String> {
+    let kind = Kind::from_path(kind)?;
+    kind.category(ctx)
+        .iter()
+        .find(|c| c.0 as i16 == cat)
+        .map(|c| c.2.clone())
+}
+
+fn search_url(key: &str, kind: Option<&str>, cat: Option<i16>) -> String {
+    let mut url = format!("/search?key={}", urlencoding::encode(key));
+    if let Some(kind) = kind {
+        url.push_str(&format!("&kind={}", kind));
+    }
+    if let Some(cat) = cat {
+        url.push_str(&format!("&cat={}", cat));
+    }
+    url
+}
+
+/// 先转义，再把 ts_headline 的标记换成 <mark>
+fn highlight(snippet: &str) -> Markup {
+    let escaped = html! {(snippet)}.into_string();
+    PreEscaped(
+        escaped
+            .replace(START_SEL, "<mark>")
+            .replace(STOP_SEL, "</mark>"),
+    )
+}
+
+fn facets_html(
+    ctx: &WebContext,
+    key: &str,
+    facets: &[Facet],
+    kind: Option<&str>,
+    cat: Option<i16>,
+) -> Markup {
+    let all: i64 = facets.iter().map(|f| f.total).sum();
+    let mut kinds: Vec<(&str, i64)> = Vec::new();
+    for f in facets {
+        match kinds.iter_mut().find(|k| k.0 == f.kind) {
+            Some(k) => k.1 += f.total,
+            None => kinds.push((&f.kind, f.total)),
+        }
+    }
+    let item = |active: bool| {
+        if active {
+            "list-group-item d-flex justify-content-between active"
+        } else {
+            "list-group-item d-flex justify-content-between"
+        }
+    };
+    html! {
+        div class="border m-2 p-1" {
+            h6 class="p-2 border-bottom" {"类型"}
+            div class="list-group list-group-flush" {
+                a class=(item(kind.is_none())) href=(search_url(key, None, None)) {
+                    span {"全部"} span {(all)}
+                }
+                @for (k, total) in &kinds {
+                    a class=(item(kind == Some(*k) && cat.is_none())) href=(search_url(key, Some(k), None)) {
+                        span {(kind_name(k))} span {(total)}
+                    }
+                }
+            }
+        }
+        @for (k, _) in kinds.iter().filter(|k| Kind::from_path(k.0).is_some()) {
+            div class="border m-2 p-1" {
+                h6 class="p-2 border-bottom" {(kind_name(k)) "分类"}
+                div class="list-group list-group-flush" {
+                    @for f in facets.iter().filter(|f| f.kind == *k) {
+                        @if let Some(c) = f.cat {
+                            a class=(item(kind == Some(*k) && cat == Some(c))) href=(search_url(key, Some(k), Some(c))) {
+                                span {(cat_name(ctx, k, c).unwrap_or_else(|| c.to_string()))}
+                                span {(f.total)}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn search(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    args: Option<Query<SearchArgs>>,
+) -> Result<Page> {
+    let Query(args) = args.unwrap_or_default();
+    let key = args.key.as_deref().map(str::trim).unwrap_or_default();
+    let kind = args
+        .kind
+        .as_deref()
+        .filter(|k| *k == IMAGE || Kind::from_path(k).is_some());
+    // 图片没有分类
+    let cat = args.cat.filter(|_| kind.map_or(false, |k| k != IMAGE));
+    let page = args.page.max(1);
+    let form = html! {
+        form class="d-flex m-2" role="search" action="/search" method="get" {
+            input class="form-control me-2" type="search" name="key" value=(key) placeholder="文章、微博、好书、图片";
+            button class="btn btn-outline-success flex-shrink-0" type="submit" {"搜索"}
+        }
+    };
+    if key.is_empty() {
+        let main = html! {
+            (form)
+            (tip("输入要搜索的词"))
+        };
+        return Ok(Html::new("搜索", main).path(Some("/search")).page(&ctx));
+    }
+
+    // 图片不公开，登录以后只搜自己的
+    let user_id = get_user_from(&session).await.ok().map(|u| u.id);
+    let facets = db_facets(&ctx, key, user_id).await?;
+    let total: i64 = facets
+        .iter()
+        .filter(|f| kind.map_or(true, |k| f.kind == k) && cat.map_or(true, |c| f.cat == Some(c)))
+        .map(|f| f.total)
+        .sum();
+    let hits = db_search(
+        &ctx,
+        key,
+        user_id,
+        kind,
+        cat,
+        PAGE_SIZE as i64,
+        filter::offset(page, PAGE_SIZE),
+    )
+    .await?;
+
+    let left = html! {
+        (form)
+        div class="container" {
+            div class="m-2 text-secondary" {"找到 " (total) " 条"}
+            @if hits.is_empty() {
+                (tip("没有找到相关的内容"))
+            }
+            @for h in &hits {
+                div class="border-bottom m-2 p-2" {
+                    div {
+                        span class="badge text-bg-light border me-2" {(kind_name(&h.kind))}
+                        a href=(view_url(&h.kind, h.id)) {(h.title)}
+                    }
+                    @if let Some(snippet) = &h.snippet {
+                        div class="text-secondary small mt-1" {(highlight(snippet))}
+                    }
+                    div class="text-secondary small mt-1" {
+                        @if let Some(c) = h.cat.and_then(|c| cat_name(&ctx, &h.kind, c)) {
+                            span class="me-3" {(c)}
+                        }
+                        @if let Some(name) = &h.user_name {
+                            span class="me-3" {(name)}
+                        }
+                        span {(show_date(h.created_at))}
+                    }
+                }
+            }
+            (pager(&search_url(key, kind, cat), total, PAGE_SIZE, page))
+        }
+    };
+    let right = facets_html(&ctx, key, &facets, kind, cat);
+    let title = format!("搜索：{}", key);
+    Ok(Html::new(&title, split(left, right))
+        .path(Some("/search"))
+        .page(&ctx))
+}
@@ -0,0 +1,161 @@
+//! sitemap.xml 和 robots.txt
+//!
+//! /sitemap.xml 是索引，每种内容按 SITEMAP_SIZE 分页：/sitemap/hu/1.xml。
+//! 图片库的页面只有作者能查看，没有公开的地址；公布的内容里引用的图片
+//! 用 sitemap 的图片扩展列在引用它的页面下面。
+
+use axum::extract::{Path, State};
+use axum::{routing::get, Router};
+use chrono::{DateTime, Utc};
+use maud::{html, Markup, PreEscaped};
+
+use crate::db;
+use crate::fun::kind::Kind;
+use crate::http::types::Xml;
+use crate::http::{error::AppError, Result, WebContext};
+
+const SITEMAP_SIZE: i64 = 10000;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/sitemap.xml", get(sitemap_index))
+        .route("/sitemap/:kind/:page", get(sitemap_page))
+        .route("/robots.txt", get(robots))
+}
+
+#[derive(sqlx::FromRow, Debug)]
+struct Url {
+    id: i32,
+    lastmod: DateTime<Utc>,
+    /// 所有的 HTML，用来找引用的图片
+    html: Option<String>,
+}
+
+fn columns(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Hu => {
+            "hu_id as id, coalesce(updated_at, created_at) as lastmod,
+             concat_ws(' ', html, html2, brief_html, log_html) as html"
+        }
+        Kind::Xi => "xi_id as id, coalesce(updated_at, created_at) as lastmod, html",
+        Kind::Book => {
+            "book_id as id, coalesce(updated_at, created_at) as lastmod,
+             concat_ws(' ', html, html2, brief_html, log_html) as html"
+        }
+    }
+}
+
+/// HTML 里图片库的图片，完整的地址
+fn images(www: &str, public_url: &str, html: &str) -> Vec<String> {
+    let prefix = format!("src=\"{}/", public_url);
+    let mut found: Vec<String> = Vec::new();
+    for (i, _) in html.match_indices(&prefix) {
+        let src = &html[i + 5..];
+        let src = match src.find('"') {
+            Some(end) => &src[..end],
+            None => continue,
+        };
+        let url = if src.starts_with('/') {
+            format!("{}{}", www, src)
+        } else {
+            src.to_string()
+        };
+        if !found.contains(&url) {
+            found.push(url);
+        }
+    }
+    found
+}
+
+fn xml(body: Markup) -> Xml {
+    Xml {
+        content_type: "application/xml; charset=utf-8",
+        body: html! {
+            (PreEscaped(r#"<?xml version="1.0" encoding="UTF-8"?>"#))
+            (body)
+        }
+        .into_string(),
+    }
+}
+
+async fn sitemap_index(State(ctx): State<WebContext>) -> Result<Xml> {
+    let www = &ctx.config.host.www;
+    let mut pages: Vec<(Kind, i64)> = Vec::new();
+    for kind in Kind::ALL {
+        let total = kind
+            .public()
+            .count()
+            .build_query_as::<db::Total>()
+            .fetch_one(&ctx.db)
+            .await?
+            .total;
+        let n = (total + SITEMAP_SIZE - 1) / SITEMAP_SIZE;
+        pages.extend((1..=n).map(|p| (kind, p)));
+    }
+    Ok(xml(html! {
+        sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" {
+            @for (kind, page) in pages {
+                sitemap {
+                    loc { (www) "/sitemap/" (kind.table()) "/" (page) ".xml" }
+                }
+            }
+        }
+    }))
+}
+
+async fn sitemap_page(
+    State(ctx): State<WebContext>,
+    Path((kind, page)): Path<(String, String)>,
+) -> Result<Xml> {
+    let kind = Kind::from_path(&kind).ok_or(AppError::NotFound("sitemap"))?;
+    let page = page
+        .strip_suffix(".xml")
+        .and_then(|p| p.parse::<i64>().ok())
+        .filter(|p| *p > 0)
+        .ok_or(AppError::NotFound("sitemap"))?;
+    let offset = (page - 1)
+        .checked_mul(SITEMAP_SIZE)
+        .ok_or(AppError::NotFound("sitemap"))?;
+    let rows = kind
+        .public()
+        .select(columns(kind), kind.order_by(), SITEMAP_SIZE, offset)
+        .build_query_as::<Url>()
+        .fetch_all(&ctx.db)
+        .await?;
+    let www = &ctx.config.host.www;
+    let public_url = &ctx.config.image.public_url;
+    Ok(xml(html! {
+        urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+            xmlns:image="http://www.google.com/schemas/sitemap-image/1.1" {
+            @for u in rows {
+                url {
+                    loc { (www) (kind.view_url(u.id)) }
+                    lastmod { (u.lastmod.format("%Y-%m-%d")) }
+                    @for image in images(www, public_url, u.html.as_deref().unwrap_or_default()) {
+                        image:image {
+                            image:loc { (image) }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+async fn robots(State(ctx): State<WebContext>) -> String {
+    let robots = &ctx.config.robots;
+    let mut out = format!("User-agent: {}\n", robots.user_agent);
+    for a in &robots.allow {
+        out.push_str(&format!("Allow: {}\n", a));
+    }
+    for d in &robots.disallow {
+        out.push_str(&format!("Disallow: {}\n", d));
+    }
+    if let Some(delay) = robots.crawl_delay {
+        out.push_str(&format!("Crawl-delay: {}\n", delay));
+    }
+    if robots.sitemap {
+        out.push_str(&format!("\nSitemap: {}/sitemap.xml\n", ctx.config.host.www));
+    }
+    out
+}
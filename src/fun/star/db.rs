@@ -0,0 +1,156 @@
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+use chrono::{DateTime, Utc};
+
+/// 加星，同时 star+1，不改 updated_at。内容要已经公布或者是自己的。
+/// 已经加过或者内容不存在时返回 false
+pub async fn db_star(ctx: &WebContext, user_id: i32, kind: Kind, id: i32) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
+    sqlx::query("set local huxi.keep_updated_at = 'on'")
+        .execute(&mut *tx)
+        .await?;
+    let rows = sqlx::query!(
+        r#"insert into user_star (user_id, kind, obj_id)
+           values ($1, $2, $3)
+           on conflict do nothing"#,
+        user_id,
+        kind.table(),
+        id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if rows != 1 {
+        return Ok(false);
+    }
+    let sql = match kind {
+        Kind::Hu => "update hu set star=star+1 where hu_id=$1 and (i_public=1 or user_id=$2)",
+        Kind::Xi => "update xi set star=star+1 where xi_id=$1 and (i_public=1 or user_id=$2)",
+        Kind::Book => "update book set star=star+1 where book_id=$1 and (i_public=1 or user_id=$2)",
+    };
+    let rows = sqlx::query(sql)
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if rows != 1 {
+        return Ok(false);
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// 取消星，同时 star-1，不改 updated_at
+pub async fn db_unstar(
+    ctx: &WebContext,
+    user_id: i32,
+    kind: Kind,
+    id: i32,
+) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
+    sqlx::query("set local huxi.keep_updated_at = 'on'")
+        .execute(&mut *tx)
+        .await?;
+    let rows = sqlx::query!(
+        r#"delete from user_star
+           where user_id=$1 and kind=$2 and obj_id=$3"#,
+        user_id,
+        kind.table(),
+        id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if rows != 1 {
+        return Ok(false);
+    }
+    let sql = match kind {
+        Kind::Hu => "update hu set star=greatest(star-1, 0) where hu_id=$1",
+        Kind::Xi => "update xi set star=greatest(star-1, 0) where xi_id=$1",
+        Kind::Book => "update book set star=greatest(star-1, 0) where book_id=$1",
+    };
+    sqlx::query(sql).bind(id).execute(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn db_is_starred(
+    ctx: &WebContext,
+    user_id: i32,
+    kind: Kind,
+    id: i32,
+) -> anyhow::Result<bool> {
+    let rec = sqlx::query_scalar!(
+        r#"select exists(
+             select 1 from user_star
+             where user_id=$1 and kind=$2 and obj_id=$3
+           ) as "starred!""#,
+        user_id,
+        kind.table(),
+        id
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    Ok(rec)
+}
+
+/// 我加星的内容，created_at 是加星的时间
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct StarRow {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub user_name: String,
+    pub star: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 已经不公布的内容不显示，除非是自己的
+pub async fn db_my_stars(
+    ctx: &WebContext,
+    user_id: i32,
+    size: i64,
+    offset: i64,
+) -> anyhow::Result<(i64, Vec<StarRow>)> {
+    let total = sqlx::query_scalar!(
+        r#"select count(*) as "total!"
+           from user_star s
+           left join hu on s.kind='hu' and hu.hu_id=s.obj_id
+           left join xi on s.kind='xi' and xi.xi_id=s.obj_id
+           left join book on s.kind='book' and book.book_id=s.obj_id
+           where s.user_id=$1
+             and (hu.i_public=1 or hu.user_id=$1
+                  or xi.i_public=1 or xi.user_id=$1
+                  or book.i_public=1 or book.user_id=$1)"#,
+        user_id
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    let rows = sqlx::query_as!(
+        StarRow,
+        r#"select kind as "kind!", id as "id!", title as "title!", user_name as "user_name!",
+                  star as "star!", created_at as "created_at!"
+           from (
+             select 'hu' as kind, hu_id as id, title, user_name, star, s.created_at
+               from user_star s join hu on s.kind='hu' and hu.hu_id=s.obj_id
+               where s.user_id=$1 and (i_public=1 or hu.user_id=$1)
+             union all
+             select 'xi', xi_id, title, user_name, star, s.created_at
+               from user_star s join xi on s.kind='xi' and xi.xi_id=s.obj_id
+               where s.user_id=$1 and (i_public=1 or xi.user_id=$1)
+             union all
+             select 'book', book_id, title, user_name, star, s.created_at
+               from user_star s join book on s.kind='book' and book.book_id=s.obj_id
+               where s.user_id=$1 and (i_public=1 or book.user_id=$1)
+           ) t
+           order by created_at desc
+           limit $2 offset $3"#,
+        user_id,
+        size,
+        offset
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok((total, rows))
+}
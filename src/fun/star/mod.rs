@@ -0,0 +1,136 @@
+//! 星标
+//!
+//! 登录用户可以给文章、微博、好书加星，每人每条一次，记录在 user_star。
+//! 内容表的 star 字段是加星的人数。
+
+pub mod db;
+
+use axum::extract::{MatchedPath, Path, Query, State};
+use axum::response::Redirect;
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use maud::{html, Markup};
+
+use crate::db::filter;
+use crate::fun::kind::Kind;
+use crate::fun::layout::Html;
+use crate::fun::user::{get_user_from, SessUser};
+use crate::fun::widget::*;
+use crate::http::types::Page;
+use crate::http::{error::AppError, Result, WebContext};
+
+use db::*;
+
+const PAGE_SIZE: u8 = 30;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/my/star", get(star_my))
+        .route("/my/star/:kind/:id", get(star_add))
+        .route("/my/star/cancel/:kind/:id", get(star_cancel))
+}
+
+/// 内容页的星标按钮
+pub async fn star_button(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    total: i32,
+    user: Option<&SessUser>,
+) -> Result<Markup> {
+    let starred = match user {
+        Some(user) => db_is_starred(ctx, user.id, kind, id).await?,
+        None => false,
+    };
+    let login = format!(
+        "/user/login?from={}",
+        urlencoding::encode(&kind.view_url(id))
+    );
+    Ok(html! {
+        span id="star" {
+            @if user.is_none() {
+                a href=(login) title="登录后加星" {"☆ " (total)}
+            } @else if starred {
+                a href={"/my/star/cancel/" (kind.table()) "/" (id)} title="取消星标" {"★ " (total)}
+            } @else {
+                a href={"/my/star/" (kind.table()) "/" (id)} title="加星" {"☆ " (total)}
+            }
+        }
+    })
+}
+
+fn parse_kind(kind: &str) -> Result<Kind> {
+    Kind::from_path(kind).ok_or(AppError::NotFound("kind"))
+}
+
+async fn star_add(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+) -> Result<Redirect> {
+    let user = get_user_from(&session).await?;
+    let kind = parse_kind(&kind)?;
+    db_star(&ctx, user.id, kind, id).await?;
+    Ok(Redirect::to(&format!("{}#star", kind.view_url(id))))
+}
+
+async fn star_cancel(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+    back: Option<Query<Back>>,
+) -> Result<Redirect> {
+    let user = get_user_from(&session).await?;
+    let kind = parse_kind(&kind)?;
+    db_unstar(&ctx, user.id, kind, id).await?;
+    let Query(back) = back.unwrap_or_default();
+    let url = back.url_or(format!("{}#star", kind.view_url(id)));
+    Ok(Redirect::to(&url))
+}
+
+async fn star_my(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    pagination: Option<Query<Pagination>>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let Query(pagination) = pagination.unwrap_or_default();
+    let page = pagination.page.max(1);
+    let (total, rows) = db_my_stars(
+        &ctx,
+        user.id,
+        PAGE_SIZE as i64,
+        filter::offset(page, PAGE_SIZE),
+    )
+    .await?;
+    let back = urlencoding::encode("/my/star");
+    let main = html! {
+        div class="container" {
+            @if rows.is_empty() {
+                (tip("还没有加星的内容"))
+            }
+            @for s in rows {
+                @if let Some(kind) = Kind::from_path(&s.kind) {
+                    div class="row border-bottom m-2 p-2 align-items-center" {
+                        div class="col col-md-1" {(kind.name())}
+                        div class="col col-md-6" {
+                            a href={(kind.view_url(s.id)) "#start"} {(s.title)}
+                        }
+                        div class="col col-md-2" {(s.user_name)}
+                        div class="col col-md-2" {(show_date(s.created_at))}
+                        div class="col col-md-1" {
+                            a href={"/my/star/cancel/" (s.kind) "/" (s.id) "?url=" (back)} title="取消星标" {"★ " (s.star)}
+                        }
+                    }
+                }
+            }
+            (pager(path.as_str(), total, PAGE_SIZE, page))
+        }
+    };
+    Ok(Html::new("我的星标", main)
+        .path(Some(path.as_str()))
+        .my_huxi(&user)
+        .page(&ctx))
+}
@@ -0,0 +1,186 @@
+use crate::db::filter::escape_like;
+use crate::http::WebContext;
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+
+use super::parse_tags;
+
+/// 在保存内容的事务里重新设置它的标签，tags 是输入框里的原文
+pub async fn db_set_tags(
+    tx: &mut PgConnection,
+    kind: &str,
+    id: i32,
+    tags: Option<&str>,
+) -> anyhow::Result<()> {
+    let names = tags.map(parse_tags).unwrap_or_default();
+    sqlx::query!(
+        r#"delete from tagging where kind=$1 and obj_id=$2"#,
+        kind,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if !names.is_empty() {
+        sqlx::query!(
+            r#"insert into tag (name)
+               select unnest($1::text[])
+               on conflict (name) do nothing"#,
+            &names
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"insert into tagging (tag_id, kind, obj_id)
+               select id, $1, $2 from tag where name = any($3)"#,
+            kind,
+            id,
+            &names
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// 标签下公布的文章、微博、好书
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct TagItem {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub user_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn db_tag_items(
+    ctx: &WebContext,
+    name: &str,
+    size: i64,
+    offset: i64,
+) -> anyhow::Result<(i64, Vec<TagItem>)> {
+    let total = sqlx::query_scalar!(
+        r#"select count(*) as "total!"
+           from tagging g
+           join tag t on t.id=g.tag_id
+           left join hu on g.kind='hu' and hu.hu_id=g.obj_id
+           left join xi on g.kind='xi' and xi.xi_id=g.obj_id
+           left join book on g.kind='book' and book.book_id=g.obj_id
+           where t.name=$1 and (hu.i_public=1 or xi.i_public=1 or book.i_public=1)"#,
+        name
+    )
+    .fetch_one(&ctx.db)
+    .await?;
+    let rows = sqlx::query_as!(
+        TagItem,
+        r#"select kind as "kind!", id as "id!", title as "title!", user_name as "user_name!",
+                  created_at as "created_at!"
+           from (
+             select 'hu' as kind, hu_id as id, title, user_name, created_at
+               from hu join tagging g on g.kind='hu' and g.obj_id=hu_id
+               where i_public=1 and g.tag_id=(select id from tag where name=$1)
+             union all
+             select 'xi', xi_id, title, user_name, created_at
+               from xi join tagging g on g.kind='xi' and g.obj_id=xi_id
+               where i_public=1 and g.tag_id=(select id from tag where name=$1)
+             union all
+             select 'book', book_id, title, user_name, created_at
+               from book join tagging g on g.kind='book' and g.obj_id=book_id
+               where i_public=1 and g.tag_id=(select id from tag where name=$1)
+           ) t
+           order by created_at desc
+           limit $2 offset $3"#,
+        name,
+        size,
+        offset
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok((total, rows))
+}
+
+/// 图片不公开，只列出自己的
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct TagImage {
+    pub id: i32,
+    pub title: String,
+    pub file: Option<String>,
+}
+
+pub async fn db_tag_images(
+    ctx: &WebContext,
+    name: &str,
+    user_id: i32,
+) -> anyhow::Result<Vec<TagImage>> {
+    let rows = sqlx::query_as!(
+        TagImage,
+        r#"select image.id, title, file
+           from image
+           join tagging g on g.kind='image' and g.obj_id=image.id
+           join tag t on t.id=g.tag_id
+           where t.name=$1 and image.user_id=$2
+           order by image.id desc"#,
+        name,
+        user_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct TagCount {
+    pub name: String,
+    pub total: i64,
+}
+
+/// 公布的内容里用得最多的标签
+pub async fn db_cloud(ctx: &WebContext, size: i64) -> anyhow::Result<Vec<TagCount>> {
+    let rows = sqlx::query_as!(
+        TagCount,
+        r#"select t.name, count(*) as "total!"
+           from tagging g
+           join tag t on t.id=g.tag_id
+           left join hu on g.kind='hu' and hu.hu_id=g.obj_id
+           left join xi on g.kind='xi' and xi.xi_id=g.obj_id
+           left join book on g.kind='book' and book.book_id=g.obj_id
+           where hu.i_public=1 or xi.i_public=1 or book.i_public=1
+           group by t.name
+           order by 2 desc, t.name
+           limit $1"#,
+        size
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+/// 输入框自动完成：公布的内容或者自己的内容里，以 prefix 开头的标签
+pub async fn db_suggest(
+    ctx: &WebContext,
+    prefix: &str,
+    user_id: i32,
+    size: i64,
+) -> anyhow::Result<Vec<String>> {
+    let like = format!("{}%", escape_like(prefix));
+    let rows = sqlx::query_scalar!(
+        r#"select t.name
+           from tagging g
+           join tag t on t.id=g.tag_id
+           left join hu on g.kind='hu' and hu.hu_id=g.obj_id
+           left join xi on g.kind='xi' and xi.xi_id=g.obj_id
+           left join book on g.kind='book' and book.book_id=g.obj_id
+           left join image on g.kind='image' and image.id=g.obj_id
+           where t.name like $1
+             and (hu.i_public=1 or xi.i_public=1 or book.i_public=1
+                  or hu.user_id=$2 or xi.user_id=$2 or book.user_id=$2 or image.user_id=$2)
+           group by t.name
+           order by count(*) desc, t.name
+           limit $3"#,
+        like,
+        user_id,
+        size
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
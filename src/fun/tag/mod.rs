@@ -0,0 +1,202 @@
+//! 标签
+//!
+//! hu、xi、book、image 的 tags 字段保存时拆开写入 tag 和 tagging，
+//! tags 字段保留原文，用于全文搜索。
+
+pub mod db;
+
+use axum::extract::{MatchedPath, Path, Query, State};
+use axum::{routing::get, Json, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::db::filter;
+use crate::fun::kind::Kind;
+use crate::fun::layout::{split, Html};
+use crate::fun::user::get_user_from;
+use crate::fun::widget::*;
+use crate::http::types::Page;
+use crate::http::{Result, WebContext};
+
+use db::*;
+
+/// tagging.kind，文章、微博、好书用 Kind::table()
+pub const IMAGE: &str = "image";
+/// 每条内容最多几个标签
+pub const MAX_TAGS: usize = 10;
+/// 标签最长几个字
+pub const MAX_LEN: usize = 32;
+const PAGE_SIZE: u8 = 30;
+const CLOUD_SIZE: i64 = 40;
+const ALL_SIZE: i64 = 500;
+const SUGGEST_SIZE: i64 = 10;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/tag", get(tag_all))
+        .route("/tag/:name", get(tag_view))
+        .route("/my/tag/suggest", get(tag_suggest))
+}
+
+/// 逗号、分号、顿号或空格分隔，转小写，去掉开头的 #
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let parts =
+        tags.split(|c: char| matches!(c, ',' | '，' | ';' | '；' | '、') || c.is_whitespace());
+    for part in parts {
+        let name = part.trim_start_matches('#').to_lowercase();
+        if name.is_empty() || name.chars().count() > MAX_LEN || names.contains(&name) {
+            continue;
+        }
+        names.push(name);
+        if names.len() == MAX_TAGS {
+            break;
+        }
+    }
+    names
+}
+
+pub fn tag_url(name: &str) -> String {
+    format!("/tag/{}", urlencoding::encode(name))
+}
+
+/// 内容页显示的标签链接
+pub fn tag_links(tags: &str) -> Markup {
+    html! {
+        @for name in parse_tags(tags) {
+            a class="badge text-bg-light border text-decoration-none me-1" href=(tag_url(&name)) {(name)}
+        }
+    }
+}
+
+/// 输入表单里带自动完成的标签输入框，见 htdocs/js/main.js
+pub fn tag_input(value: Option<&String>) -> Markup {
+    html! {
+        div class="position-relative" {
+            input class="form-control mb-3 tag-input" id="tags" type="text" name="tags"
+                value=[value] autocomplete="off" placeholder="用逗号或空格分隔";
+            div class="list-group position-absolute w-100 shadow tag-suggest" style="z-index: 10" {}
+        }
+    }
+}
+
+fn cloud_html(tags: &[TagCount]) -> Markup {
+    let max = tags.iter().map(|t| t.total).max().unwrap_or(1);
+    let min = tags.iter().map(|t| t.total).min().unwrap_or(1);
+    html! {
+        div class="p-2" {
+            @for t in tags {
+                // fs-6 到 fs-3
+                @let size = 6 - (t.total - min) * 3 / (max - min).max(1);
+                a class={"text-decoration-none me-2 fs-" (size)} href=(tag_url(&t.name)) title=(t.total) {(t.name)}
+            }
+        }
+    }
+}
+
+/// 侧栏的标签云
+pub async fn tag_cloud(ctx: &WebContext) -> Result<Markup> {
+    let tags = db_cloud(ctx, CLOUD_SIZE).await?;
+    Ok(html! {
+        @if !tags.is_empty() {
+            div class="border m-2 p-1" {
+                h6 class="p-2 border-bottom" {
+                    a class="text-decoration-none text-dark" href="/tag" {"标签"}
+                }
+                (cloud_html(&tags))
+            }
+        }
+    })
+}
+
+async fn tag_all(State(ctx): State<WebContext>, path: MatchedPath) -> Result<Page> {
+    let tags = db_cloud(&ctx, ALL_SIZE).await?;
+    let main = html! {
+        div class="container border shadow-lg p-3 mb-5 bg-body rounded" {
+            @if tags.is_empty() {
+                (tip("还没有标签"))
+            }
+            (cloud_html(&tags))
+        }
+    };
+    Ok(Html::new("标签", main).path(Some(path.as_str())).page(&ctx))
+}
+
+async fn tag_view(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path(name): Path<String>,
+    pagination: Option<Query<Pagination>>,
+) -> Result<Page> {
+    let name = name.trim_start_matches('#').to_lowercase();
+    let Query(pagination) = pagination.unwrap_or_default();
+    let page = pagination.page.max(1);
+    let (total, items) = db_tag_items(
+        &ctx,
+        &name,
+        PAGE_SIZE as i64,
+        filter::offset(page, PAGE_SIZE),
+    )
+    .await?;
+    let images = match get_user_from(&session).await {
+        Ok(user) => db_tag_images(&ctx, &name, user.id).await?,
+        Err(_) => Vec::new(),
+    };
+    let url = tag_url(&name);
+    let left = html! {
+        div class="container" {
+            h4 class="m-2" {"标签：" (name) span class="ms-3 fs-6 text-secondary" {"共 " (total) " 条"}}
+            @for item in items {
+                @if let Some(kind) = Kind::from_path(&item.kind) {
+                    div class="row border-bottom m-2 p-2" {
+                        div class="col col-md-1 text-secondary" {(kind.name())}
+                        div class="col col-md-7" {
+                            a href={(kind.view_url(item.id)) "#start"} {(item.title)}
+                        }
+                        div class="col col-md-2" {(item.user_name)}
+                        div class="col col-md-2" {(show_date(item.created_at))}
+                    }
+                }
+            }
+            (pager(&url, total, PAGE_SIZE, page))
+            @if !images.is_empty() {
+                h5 class="m-2 mt-4" {"我的图片"}
+                div class="d-flex flex-wrap" {
+                    @for img in &images {
+                        a class="m-2" href={"/image/view/" (img.id)} title=(img.title) {
+                            img class="border" src={(ctx.config.image.public_url) "/s-" (img.file.as_deref().unwrap_or_default())};
+                        }
+                    }
+                }
+            }
+        }
+    };
+    let right = tag_cloud(&ctx).await?;
+    let title = format!("标签：{}", name);
+    Ok(Html::new(&title, split(left, right))
+        .path(Some("/tag"))
+        .page(&ctx))
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct SuggestArgs {
+    q: Option<String>,
+}
+
+/// 自动完成，返回标签名数组
+async fn tag_suggest(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    args: Option<Query<SuggestArgs>>,
+) -> Result<Json<Vec<String>>> {
+    let user = get_user_from(&session).await?;
+    let Query(args) = args.unwrap_or_default();
+    let prefix = args.q.as_deref().map(parse_tags).unwrap_or_default();
+    let names = match prefix.last() {
+        Some(prefix) => db_suggest(&ctx, prefix, user.id, SUGGEST_SIZE).await?,
+        None => Vec::new(),
+    };
+    Ok(Json(names))
+}
@@ -0,0 +1,101 @@
+//! 用户管理命令：huxi-server user ...
+
+use anyhow::{anyhow, bail};
+use std::io::{BufRead, Write};
+
+use crate::config::UserCommand;
+use crate::fun::user::db::*;
+use crate::fun::user::Role;
+use crate::http::WebContext;
+
+pub async fn run(ctx: &WebContext, cmd: UserCommand) -> anyhow::Result<()> {
+    match cmd {
+        UserCommand::Create {
+            name,
+            email,
+            password,
+            role,
+        } => {
+            let password = match password {
+                Some(pw) => pw,
+                None => read_password()?,
+            };
+            let mut input = RegInput {
+                name,
+                email,
+                password,
+                ..Default::default()
+            };
+            input.trim();
+            let error = input.check();
+            if !error.is_empty() {
+                bail!(error.join("\n"));
+            }
+            if db_find_user(ctx, &input.name).await?.is_some() {
+                bail!("用户名已被使用：{}", input.name);
+            }
+            if db_find_user(ctx, &input.email).await?.is_some() {
+                bail!("邮箱已被使用：{}", input.email);
+            }
+            let name = input.name.clone();
+            db_add_user(ctx, input).await?;
+            let user = find(ctx, &name).await?;
+            db_set_role(ctx, user.user_id, role as i16).await?;
+            println!(
+                "created user {} ({}), role {}",
+                user.name, user.user_id, role
+            );
+        }
+        UserCommand::SetRole { user, role } => {
+            let user = find(ctx, &user).await?;
+            db_set_role(ctx, user.user_id, role as i16).await?;
+            println!(
+                "{}: role {} -> {}",
+                user.name,
+                Role::from(user.i_role),
+                role
+            );
+        }
+        UserCommand::ResetPassword { user, password } => {
+            let user = find(ctx, &user).await?;
+            let password = match password {
+                Some(pw) => pw,
+                None => read_password()?,
+            };
+            let password = password.trim().to_string();
+            if password.is_empty() {
+                bail!("请输入密码");
+            }
+            db_update_user_pw(ctx, user.user_id, password).await?;
+            println!("{}: password updated", user.name);
+        }
+        UserCommand::Disable { user } => {
+            let user = find(ctx, &user).await?;
+            db_set_role(ctx, user.user_id, Role::Disabled as i16).await?;
+            println!("{}: disabled", user.name);
+        }
+        UserCommand::List => {
+            println!("{:>6}  {:<10}  {:<20}  email", "id", "role", "name");
+            for u in db_user_list(ctx).await? {
+                let role = Role::from(u.i_role).key();
+                println!("{:>6}  {:<10}  {:<20}  {}", u.id, role, u.name, u.email);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn find(ctx: &WebContext, key: &str) -> anyhow::Result<User> {
+    db_find_user(ctx, key)
+        .await?
+        .ok_or_else(|| anyhow!("没有这个用户：{}", key))
+}
+
+/// 密码不放在命令行里，避免留在 shell 历史中
+fn read_password() -> anyhow::Result<String> {
+    eprint!("password: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
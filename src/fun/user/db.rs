@@ -5,8 +5,14 @@ use argon2::{
 };
 
 use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use sqlx::types::Uuid;
 
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$").unwrap()
+});
+
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct User {
     pub user_id: i32,
@@ -31,6 +37,28 @@ impl RegInput {
         self.password = self.password.trim().to_string();
         self.password2 = self.password2.trim().to_string();
     }
+    /// 检查用户名、邮箱和密码，注册和命令行新建用户共用
+    pub fn check(&self) -> Vec<String> {
+        let mut error = Vec::new();
+        if self.name.is_empty() {
+            error.push("请输入用户名".to_string());
+        } else if self.name.contains('@') {
+            error.push("用户名中不能包含字符 @".to_string());
+        } else if is_digits(&self.name) {
+            error.push("用户名不能全是数字".to_string());
+        }
+        if self.email.is_empty() {
+            error.push("请输入邮箱".to_string());
+        } else {
+            if !EMAIL.is_match(&self.email) {
+                error.push("邮箱格式错误".to_string());
+            }
+        }
+        if self.password.is_empty() {
+            error.push("请输入密码".to_string());
+        }
+        error
+    }
 }
 
 pub async fn db_add_user(ctx: &WebContext, input: RegInput) -> anyhow::Result<Uuid> {
@@ -57,21 +85,118 @@ pub enum By {
 }
 
 pub async fn db_get_user(ctx: &WebContext, by: By) -> anyhow::Result<Option<User>> {
-    let filter = match by {
-        By::Email(s) => format!("email='{}'", s),
-        By::Name(s) => format!("name='{}'", s),
-        By::Id(i) => format!("user_id={}", i),
+    let rec = match by {
+        By::Email(s) => {
+            sqlx::query_as!(
+                User,
+                r#"select user_id, name, email, password, i_role
+                   from users
+                   where email=$1 and i_role > 0"#,
+                s
+            )
+            .fetch_optional(&ctx.db)
+            .await?
+        }
+        By::Name(s) => {
+            sqlx::query_as!(
+                User,
+                r#"select user_id, name, email, password, i_role
+                   from users
+                   where name=$1 and i_role > 0"#,
+                s
+            )
+            .fetch_optional(&ctx.db)
+            .await?
+        }
+        By::Id(i) => {
+            sqlx::query_as!(
+                User,
+                r#"select user_id, name, email, password, i_role
+                   from users
+                   where user_id=$1 and i_role > 0"#,
+                i
+            )
+            .fetch_optional(&ctx.db)
+            .await?
+        }
+    };
+    Ok(rec)
+}
+
+/// 按编号、用户名或邮箱查找，包括未激活和停用的用户。
+/// 全是数字时是编号，有 @ 时是邮箱，其他是用户名；找到多个时出错
+pub async fn db_find_user(ctx: &WebContext, key: &str) -> anyhow::Result<Option<User>> {
+    let rows = if is_digits(key) {
+        let id = match key.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => return Ok(None),
+        };
+        sqlx::query_as!(
+            User,
+            r#"select user_id, name, email, password, i_role
+               from users
+               where user_id=$1"#,
+            id
+        )
+        .fetch_all(&ctx.db)
+        .await?
+    } else if key.contains('@') {
+        sqlx::query_as!(
+            User,
+            r#"select user_id, name, email, password, i_role
+               from users
+               where email=$1
+               order by user_id"#,
+            key
+        )
+        .fetch_all(&ctx.db)
+        .await?
+    } else {
+        sqlx::query_as!(
+            User,
+            r#"select user_id, name, email, password, i_role
+               from users
+               where name=$1
+               order by user_id"#,
+            key
+        )
+        .fetch_all(&ctx.db)
+        .await?
     };
-    let sql = format!(
-        r#"select user_id, name, email, password, i_role
-           from users
-           where {} and i_role > 0;"#,
-        filter
-    );
-    let rec = sqlx::query_as::<_, User>(&sql)
+    if rows.len() > 1 {
+        let ids: Vec<String> = rows.iter().map(|u| u.user_id.to_string()).collect();
+        anyhow::bail!("有多个用户是 {}：{}，请用用户编号", key, ids.join(", "));
+    }
+    Ok(rows.into_iter().next())
+}
+
+/// 用户名不能全是数字，避免和用户编号混淆
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// 当前的权限，用户删除时是 None
+pub async fn db_user_role(ctx: &WebContext, id: i32) -> anyhow::Result<Option<i16>> {
+    let role = sqlx::query_scalar!("select i_role from users where user_id=$1", id)
         .fetch_optional(&ctx.db)
         .await?;
-    Ok(rec)
+    Ok(role)
+}
+
+/// role 是 Role，小于 Role::Reader 的用户不能登录
+pub async fn db_set_role(ctx: &WebContext, id: i32, role: i16) -> anyhow::Result<bool> {
+    let rows_affected = sqlx::query!(
+        r#"update users
+           set i_role=$1
+           where user_id=$2"#,
+        role,
+        id,
+    )
+    .execute(&ctx.db)
+    .await?
+    .rows_affected();
+
+    Ok(rows_affected > 0)
 }
 
 pub async fn db_add_pw_reset(ctx: &WebContext, user: &User) -> anyhow::Result<Uuid> {
@@ -101,7 +226,7 @@ pub async fn pw2_db_get_pwreq_by_id(ctx: &WebContext, id: &str) -> anyhow::Resul
         PwReq,
         r#"select created_at, user_name, user_id
            from reset_pw_req where id=$1"#,
-        Uuid::parse_str(&id).unwrap()
+        Uuid::parse_str(id)?
     )
     .fetch_one(&ctx.db)
     .await?;
@@ -129,7 +254,7 @@ pub async fn db_update_pw_reset(ctx: &WebContext, id: &str) -> anyhow::Result<bo
         r#"update reset_pw_req
            set i_status = 1
            where id = $1"#,
-        Uuid::parse_str(id).unwrap()
+        Uuid::parse_str(id)?
     )
     .execute(&ctx.db)
     .await?
@@ -146,7 +271,7 @@ pub struct UserSimple {
     pub i_role: i16,
 }
 
-pub async fn _db_user_list(ctx: &WebContext) -> anyhow::Result<Vec<UserSimple>> {
+pub async fn db_user_list(ctx: &WebContext) -> anyhow::Result<Vec<UserSimple>> {
     let rows = sqlx::query_as!(
         UserSimple,
         r#"
@@ -162,7 +287,7 @@ pub async fn _db_user_list(ctx: &WebContext) -> anyhow::Result<Vec<UserSimple>>
 }
 
 /// hash 密码
-async fn hash_password(password: String) -> Result<String> {
+pub async fn hash_password(password: String) -> Result<String> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     Ok(argon2
@@ -1,9 +1,13 @@
 //! 用户
 
+pub mod cli;
 pub mod db;
+pub mod role;
+pub mod token;
 
-use axum::extract::{Form, MatchedPath, Multipart, Path, Query, State};
-use axum::response::Redirect;
+use axum::extract::{Form, MatchedPath, Multipart, Path, Query, Request, State};
+use axum::middleware::Next;
+use axum::response::{Redirect, Response};
 use axum::{routing::get, Router};
 
 use axum_session::Session;
@@ -27,15 +31,10 @@ use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
 
 use db::*;
+pub use role::{Cap, Role};
 
 const RESET_PW_INVALID_SECS: i64 = 1800;
 
-#[allow(dead_code)]
-enum Role {
-    Normal = 1,
-    Sudo = 5,
-}
-
 pub fn router() -> Router<WebContext> {
     Router::new()
         .route("/user/reg", get(reg_input).post(reg_do))
@@ -49,6 +48,7 @@ pub fn router() -> Router<WebContext> {
         .route("/my/img", get(img_update_input).post(img_update_do))
         .route("/my/pw/new/:id", get(pw2_input).post(pw2_do))
         .route("/user/logout", get(logout))
+        .merge(token::router())
 }
 
 /// 注册
@@ -150,24 +150,8 @@ async fn reg_do(
     path: MatchedPath,
     Form(mut input): Form<RegInput>,
 ) -> Result<Page> {
-    let mut error: Vec<String> = Vec::new();
     input.trim();
-    if input.name.is_empty() {
-        error.push("请输入用户名".to_string());
-    } else if input.name.contains('@') {
-        error.push("用户名中不能包含字符 @".to_string());
-    }
-    if input.email.is_empty() {
-        error.push("请输入邮箱".to_string());
-    } else {
-        let email_regex = regex::Regex::new(r"^[a-zA-Z0-9.!#$%&'*+/=?^_`{|}~-]+@[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$").unwrap();
-        if !email_regex.is_match(&input.email) {
-            error.push("邮箱格式错误".to_string());
-        }
-    }
-    if input.password.is_empty() {
-        error.push("请输入密码".to_string());
-    }
+    let mut error = input.check();
     if input.password2.is_empty() {
         error.push("请输入确认密码".to_string());
     }
@@ -276,10 +260,12 @@ async fn reg_ok(
 async fn db_update_user_reg(ctx: &WebContext, uid: Uuid) -> anyhow::Result<String> {
     let rec = sqlx::query!(
         r#"update users
-           set i_role=1
-           where uid=$1
+           set i_role=$2
+           where uid=$1 and i_role=$3
            returning name"#,
-        uid
+        uid,
+        Role::Author as i16,
+        Role::Unverified as i16
     )
     .fetch_one(&ctx.db)
     .await?;
@@ -357,7 +343,7 @@ struct LoginInput {
     remember: Option<u8>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct SessUser {
     pub id: i32,
     pub name: String,
@@ -421,6 +407,7 @@ async fn my_huxi(
 ) -> Result<Page> {
     let user = get_user_from(&session).await?;
     let new_total = message::db::db_new_total(&ctx, user.id).await?;
+    let new_reply = message::db::db_new_reply_total(&ctx, user.id).await?;
     let main = html! {
 
         div class="container" {
@@ -429,9 +416,14 @@ async fn my_huxi(
                     div class="m-2 p3" {
                         h3 {"你好，" (&user.name) "， " span class="mx-2" {"欢迎！"}}
                         br;
-                        @if new_total > 0 {
+                        @if crate::fun::admin::is_staff(&user) {
+                            a class="btn btn-outline-primary m-2" href="/admin" {"网站管理"}
+                        }
+                        @if new_total > 0 || new_reply > 0 {
                             div class="text-dark bg-light m-3 p-2 border border-info" {
-                                "提示：发现新信件 " (new_total) " 封。"
+                                "提示："
+                                @if new_total > 0 {"发现新信件 " (new_total) " 封。"}
+                                @if new_reply > 0 {"信件有新回复 " (new_reply) " 条。"}
 
                                     a class="btn btn-primary m-2" href="/my/inbox#start" {"打开收信箱"}
                             }
@@ -469,6 +461,10 @@ async fn my_info(
                        h5 class="mb-2 p-2 border-bottom border-secondary border-2" {"头像"}
                        a href="/my/img" {"修改头像"}
                     }
+                    div class="m-2 p3" {
+                       h5 class="mb-2 p-2 border-bottom border-secondary border-2" {"访问令牌"}
+                       a href="/my/tokens" {"管理访问令牌"}
+                    }
                 }
             }
         }
@@ -753,26 +749,56 @@ async fn logout(session: Session<SessionPgPool>) -> Redirect {
     Redirect::temporary("/")
 }
 
+/// 每次请求按数据库中的权限更新 session 里的用户，修改权限、停用以后立即生效
+pub async fn refresh_user(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let user: Option<SessUser> = session.get("user");
+    let path = req.uri().path();
+    let assets = ["/css/", "/js/", "/img/"]
+        .iter()
+        .any(|p| path.starts_with(p));
+    if let (Some(mut user), false) = (user, assets) {
+        match db_user_role(&ctx, user.id).await {
+            Ok(Some(role)) if Role::from(role).active() => {
+                if role != user.role {
+                    user.role = role;
+                    session.set("user", user);
+                }
+            }
+            Ok(_) => session.remove("user"),
+            Err(e) => tracing::error!("refresh user {}: {:?}", user.id, e),
+        }
+    }
+    next.run(req).await
+}
+
 // 常用函数
-/// 获得登录用户
+/// 获得登录用户，请求带 Bearer 令牌时是令牌的用户
 pub async fn get_user_from(session: &Session<SessionPgPool>) -> Result<SessUser> {
+    if let Some(user) = token::token_user() {
+        return user.ok_or(AppError::Unauthorized);
+    }
     let v: Option<SessUser> = session.get("user");
     v.ok_or_else(|| AppError::InvalidLogin("/user/login".into()))
 }
 
-/// 检查管理员权限
-pub async fn check_sudo(session: &Session<SessionPgPool>) -> bool {
-    let user: Option<SessUser> = session.get("user");
-    if let Some(u) = user {
-        if u.role >= Role::Sudo as i16 {
-            return true;
-        }
+/// 获得有 cap 权限的登录用户
+pub async fn get_user_can(session: &Session<SessionPgPool>, cap: Cap) -> Result<SessUser> {
+    let user = get_user_from(session).await?;
+    if user.can(cap) {
+        Ok(user)
+    } else {
+        Err(AppError::Unauthorized)
     }
-    false
 }
 
-pub fn is_sudo_role(user_role: i16) -> bool {
-    user_role >= Role::Sudo as i16
+/// 检查登录用户的权限
+pub async fn check_cap(session: &Session<SessionPgPool>, cap: Cap) -> bool {
+    get_user_from(session).await.map_or(false, |u| u.can(cap))
 }
 
 /// 验证密码
@@ -0,0 +1,141 @@
+//! 用户权限
+//!
+//! users.i_role 保存 Role，每个 Role 有一组 Cap。
+//! 所有权限检查都通过 SessUser::can 和 SessUser::can_manage。
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::SessUser;
+
+/// 能做的事情
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cap {
+    /// 写文章、微博、好书
+    Publish,
+    /// 上传图片
+    UploadImage,
+    /// 隐藏、删除别人的评论
+    ModerateComment,
+    /// 修改、删除别人的内容
+    EditAll,
+    /// 推荐
+    Recommend,
+    /// 用户管理
+    ManageUser,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Disabled = -1,
+    /// 注册后还没有激活
+    Unverified = 0,
+    Reader = 1,
+    Author = 2,
+    Moderator = 3,
+    Editor = 4,
+    Admin = 5,
+}
+
+impl Role {
+    pub const ALL: [Role; 7] = [
+        Role::Disabled,
+        Role::Unverified,
+        Role::Reader,
+        Role::Author,
+        Role::Moderator,
+        Role::Editor,
+        Role::Admin,
+    ];
+
+    pub fn caps(&self) -> &'static [Cap] {
+        use Cap::*;
+        match self {
+            Role::Disabled | Role::Unverified | Role::Reader => &[],
+            Role::Author => &[Publish, UploadImage],
+            Role::Moderator => &[Publish, UploadImage, ModerateComment],
+            Role::Editor => &[Publish, UploadImage, ModerateComment, EditAll, Recommend],
+            Role::Admin => &[
+                Publish,
+                UploadImage,
+                ModerateComment,
+                EditAll,
+                Recommend,
+                ManageUser,
+            ],
+        }
+    }
+    pub fn can(&self, cap: Cap) -> bool {
+        self.caps().contains(&cap)
+    }
+    /// 可以登录
+    pub fn active(&self) -> bool {
+        *self >= Role::Reader
+    }
+    pub fn key(&self) -> &'static str {
+        match self {
+            Role::Disabled => "disabled",
+            Role::Unverified => "unverified",
+            Role::Reader => "reader",
+            Role::Author => "author",
+            Role::Moderator => "moderator",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Disabled => "停用",
+            Role::Unverified => "未激活",
+            Role::Reader => "读者",
+            Role::Author => "作者",
+            Role::Moderator => "评论管理",
+            Role::Editor => "编辑",
+            Role::Admin => "管理员",
+        }
+    }
+}
+
+/// 数据库中不认识的值当作停用
+impl From<i16> for Role {
+    fn from(v: i16) -> Self {
+        Role::ALL
+            .into_iter()
+            .find(|r| *r as i16 == v)
+            .unwrap_or(Role::Disabled)
+    }
+}
+
+/// 命令行里可以写名称或数字
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|r| r.key() == s || (*r as i16).to_string() == s)
+            .ok_or_else(|| {
+                let keys: Vec<&str> = Role::ALL.iter().map(|r| r.key()).collect();
+                format!("unknown role `{}`, expected one of: {}", s, keys.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+impl SessUser {
+    pub fn role(&self) -> Role {
+        Role::from(self.role)
+    }
+    pub fn can(&self, cap: Cap) -> bool {
+        self.role().can(cap)
+    }
+    /// 自己的内容，或者有 cap 权限
+    pub fn can_manage(&self, owner_id: i32, cap: Cap) -> bool {
+        owner_id == self.id || self.can(cap)
+    }
+}
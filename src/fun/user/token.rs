@@ -0,0 +1,269 @@
+//! 个人访问令牌
+//!
+//! 令牌只在新建时显示一次，数据库中保存 sha256。
+//! 请求头 `Authorization: Bearer <token>` 和登录 session 得到同样的 SessUser：
+//! 中间件 bearer_user 查出令牌的用户，处理这个请求时 get_user_from 先用它。
+//! 令牌的用户不写进 session，撤销以后立即失效。
+
+use axum::async_trait;
+use axum::extract::{Form, FromRequestParts, MatchedPath, Path, Request, State};
+use axum::http::{header::AUTHORIZATION, request::Parts, HeaderMap};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use chrono::{DateTime, Utc};
+use maud::{html, Markup};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::fun::layout::Html;
+use crate::fun::user::{get_user_from, SessUser};
+use crate::fun::widget::*;
+use crate::http::types::Page;
+use crate::http::{error::AppError, Result, WebContext};
+
+const TOKEN_PREFIX: &str = "hx_";
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/my/tokens", get(token_my).post(token_add))
+        .route("/my/tokens/rm/:id", get(token_rm))
+}
+
+tokio::task_local! {
+    /// 这个请求的令牌的用户，令牌无效时是 None
+    static BEARER_USER: Option<SessUser>;
+}
+
+/// 请求带 Bearer 令牌时查出用户，在处理请求期间代替 session 里的用户
+pub async fn bearer_user(State(ctx): State<WebContext>, req: Request, next: Next) -> Response {
+    let token = match bearer(req.headers()) {
+        Some(token) => token.to_string(),
+        None => return next.run(req).await,
+    };
+    match db_get_user_by_token(&ctx, &token).await {
+        Ok(user) => BEARER_USER.scope(user, next.run(req)).await,
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+/// 令牌的用户：请求没有令牌时是 None，令牌无效时是 Some(None)
+pub fn token_user() -> Option<Option<SessUser>> {
+    BEARER_USER.try_with(|user| user.clone()).ok()
+}
+
+/// 登录用户，Bearer 令牌或 session
+pub struct AuthUser(pub SessUser);
+
+#[async_trait]
+impl FromRequestParts<WebContext> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, ctx: &WebContext) -> Result<Self> {
+        let session = Session::<SessionPgPool>::from_request_parts(parts, ctx)
+            .await
+            .map_err(|_| AppError::Unauthorized)?;
+        Ok(AuthUser(get_user_from(&session).await?))
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn new_token() -> String {
+    let bytes: [u8; 24] = rand::random();
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", TOKEN_PREFIX, hex)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct Token {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn db_get_user_by_token(
+    ctx: &WebContext,
+    token: &str,
+) -> anyhow::Result<Option<SessUser>> {
+    let rec = sqlx::query_as!(
+        SessUser,
+        r#"update api_token t set last_used_at=now()
+           from users u
+           where t.token_hash=$1 and u.user_id=t.user_id and u.i_role > 0
+           returning u.user_id as id, u.name, u.email, u.i_role as role"#,
+        hash_token(token)
+    )
+    .fetch_optional(&ctx.db)
+    .await?;
+    Ok(rec)
+}
+
+pub async fn db_list(ctx: &WebContext, user_id: i32) -> anyhow::Result<Vec<Token>> {
+    let rows = sqlx::query_as!(
+        Token,
+        r#"select id, name, prefix, last_used_at, created_at
+           from api_token where user_id=$1
+           order by id desc"#,
+        user_id
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+/// 返回新令牌，只有这一次能看到
+pub async fn db_insert(ctx: &WebContext, user_id: i32, name: &str) -> anyhow::Result<String> {
+    let token = new_token();
+    sqlx::query!(
+        r#"insert into api_token (user_id, name, token_hash, prefix)
+           values ($1, $2, $3, $4)"#,
+        user_id,
+        name,
+        hash_token(&token),
+        &token[..TOKEN_PREFIX.len() + 6]
+    )
+    .execute(&ctx.db)
+    .await?;
+    Ok(token)
+}
+
+pub async fn db_rm(ctx: &WebContext, user_id: i32, id: i32) -> anyhow::Result<bool> {
+    let rows = sqlx::query!(
+        r#"delete from api_token
+           where id=$1 and user_id=$2"#,
+        id,
+        user_id
+    )
+    .execute(&ctx.db)
+    .await?
+    .rows_affected();
+
+    Ok(rows == 1)
+}
+
+async fn token_my(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let tokens = db_list(&ctx, user.id).await?;
+    let main = token_html(tokens, None, None);
+    Ok(Html::new("访问令牌", main)
+        .my_huxi(&user)
+        .path(Some(path.as_str()))
+        .page(&ctx))
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenInput {
+    name: String,
+}
+
+async fn token_add(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+    Form(input): Form<TokenInput>,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let name = input.name.trim();
+    let (new, error) = if name.is_empty() {
+        (None, Some(vec!["请输入令牌名称".to_string()]))
+    } else {
+        (Some(db_insert(&ctx, user.id, name).await?), None)
+    };
+    let tokens = db_list(&ctx, user.id).await?;
+    let main = token_html(tokens, new, error);
+    Ok(Html::new("访问令牌", main)
+        .my_huxi(&user)
+        .path(Some(path.as_str()))
+        .page(&ctx))
+}
+
+async fn token_rm(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path(id): Path<i32>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let main = if db_rm(&ctx, user.id, id).await? {
+        tip("令牌已撤销")
+    } else {
+        tip("数据错误")
+    };
+    let tokens = db_list(&ctx, user.id).await?;
+    let main = html! {
+        (main)
+        (token_html(tokens, None, None))
+    };
+    Ok(Html::new("访问令牌", main)
+        .my_huxi(&user)
+        .path(Some(path.as_str()))
+        .page(&ctx))
+}
+
+fn token_html(tokens: Vec<Token>, new: Option<String>, error: ErrorMessage) -> Markup {
+    html! {
+        div class="container" {
+            div class="row justify-content-center" {
+                div class="col col-md-10 col-xl-10 border shadow-lg p-3 mb-5 bg-body rounded" {
+                    @if let Some(token) = new {
+                        div class="alert alert-success m-2" {
+                            p {"新令牌只显示这一次，请复制保存："}
+                            code class="user-select-all" {(token)}
+                        }
+                    }
+                    (error_message(error))
+                    form action="/my/tokens" method="post" class="d-flex m-2" {
+                        (TextInput::new("name", "name", true).placeholder("令牌名称，例如：编辑器").show())
+                        (submit("新建令牌"))
+                    }
+                    div class="m-2 text-secondary" {
+                        "请求时加上 HTTP 头 " code {"Authorization: Bearer <令牌>"}
+                    }
+                    @for t in tokens {
+                        div class="row border-bottom m-2 p-2" {
+                            div class="col col-md-3" {(t.name)}
+                            div class="col col-md-2" { code {(t.prefix) "…"} }
+                            div class="col col-md-3" {"新建：" (show_date(t.created_at))}
+                            div class="col col-md-3" {
+                                "使用："
+                                @if let Some(used) = t.last_used_at {
+                                    (show_time(used))
+                                } @else {
+                                    "未使用"
+                                }
+                            }
+                            div class="col col-md-1" {
+                                @let cfm = format!("javascript:if(confirm('确实要撤销吗?'))location='/my/tokens/rm/{}'", t.id);
+                                a href=(cfm) {"撤销"}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
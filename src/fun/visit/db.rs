@@ -0,0 +1,167 @@
+use crate::fun::kind::Kind;
+use crate::http::WebContext;
+use chrono::NaiveDate;
+
+/// 一条内容一天的访问次数
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub kind: Kind,
+    pub id: i32,
+    pub day: NaiveDate,
+    pub views: i32,
+}
+
+/// 写入 visit_day，同时增加内容的 click，不改 updated_at
+pub async fn db_flush(ctx: &WebContext, hits: &[Hit]) -> anyhow::Result<()> {
+    let mut tx = ctx.db.begin().await?;
+    sqlx::query("set local huxi.keep_updated_at = 'on'")
+        .execute(&mut *tx)
+        .await?;
+    for kind in Kind::ALL {
+        let (ids, views): (Vec<i32>, Vec<i32>) = hits
+            .iter()
+            .filter(|h| h.kind == kind)
+            .map(|h| (h.id, h.views))
+            .unzip();
+        if ids.is_empty() {
+            continue;
+        }
+        // 同一条内容可能有两天的记录，先合并
+        let sql = match kind {
+            Kind::Hu => {
+                r#"update hu set click=click+v.n
+                   from (select id, sum(n)::int as n from unnest($1::int[], $2::int[]) as t(id, n) group by id) v
+                   where hu_id=v.id"#
+            }
+            Kind::Xi => {
+                r#"update xi set click=click+v.n
+                   from (select id, sum(n)::int as n from unnest($1::int[], $2::int[]) as t(id, n) group by id) v
+                   where xi_id=v.id"#
+            }
+            Kind::Book => {
+                r#"update book set click=click+v.n
+                   from (select id, sum(n)::int as n from unnest($1::int[], $2::int[]) as t(id, n) group by id) v
+                   where book_id=v.id"#
+            }
+        };
+        sqlx::query(sql)
+            .bind(&ids)
+            .bind(&views)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let kinds: Vec<String> = hits.iter().map(|h| h.kind.table().to_string()).collect();
+    let ids: Vec<i32> = hits.iter().map(|h| h.id).collect();
+    let days: Vec<NaiveDate> = hits.iter().map(|h| h.day).collect();
+    let views: Vec<i32> = hits.iter().map(|h| h.views).collect();
+    sqlx::query!(
+        r#"insert into visit_day (kind, obj_id, day, views)
+           select * from unnest($1::text[], $2::int[], $3::date[], $4::int[])
+           on conflict (kind, obj_id, day)
+           do update set views=visit_day.views+excluded.views"#,
+        &kinds,
+        &ids,
+        &days,
+        &views
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct DayViews {
+    pub day: NaiveDate,
+    pub views: i64,
+}
+
+/// 最近 days 天，一个用户所有内容每天的访问次数
+pub async fn db_user_days(
+    ctx: &WebContext,
+    user_id: i32,
+    days: i32,
+) -> anyhow::Result<Vec<DayViews>> {
+    let rows = sqlx::query_as!(
+        DayViews,
+        r#"select v.day, sum(v.views)::bigint as "views!"
+           from visit_day v
+           where v.day > current_date - $2::int
+             and ((v.kind='hu' and v.obj_id in (select hu_id from hu where user_id=$1))
+               or (v.kind='xi' and v.obj_id in (select xi_id from xi where user_id=$1))
+               or (v.kind='book' and v.obj_id in (select book_id from book where user_id=$1)))
+           group by v.day
+           order by v.day desc"#,
+        user_id,
+        days
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+/// 最近 days 天，一条内容每天的访问次数
+pub async fn db_item_days(
+    ctx: &WebContext,
+    kind: Kind,
+    id: i32,
+    days: i32,
+) -> anyhow::Result<Vec<DayViews>> {
+    let rows = sqlx::query_as!(
+        DayViews,
+        r#"select day, views::bigint as "views!"
+           from visit_day
+           where kind=$1 and obj_id=$2 and day > current_date - $3::int
+           order by day desc"#,
+        kind.table(),
+        id,
+        days
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
+pub struct TopRow {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub views: i64,
+}
+
+/// 最近 days 天，一个用户访问最多的内容
+pub async fn db_user_top(
+    ctx: &WebContext,
+    user_id: i32,
+    days: i32,
+    size: i64,
+) -> anyhow::Result<Vec<TopRow>> {
+    let rows = sqlx::query_as!(
+        TopRow,
+        r#"select kind as "kind!", id as "id!", title as "title!", sum(views)::bigint as "views!"
+           from (
+             select 'hu' as kind, hu_id as id, title, v.views
+               from visit_day v join hu on v.kind='hu' and hu_id=v.obj_id
+               where user_id=$1 and v.day > current_date - $2::int
+             union all
+             select 'xi', xi_id, title, v.views
+               from visit_day v join xi on v.kind='xi' and xi_id=v.obj_id
+               where user_id=$1 and v.day > current_date - $2::int
+             union all
+             select 'book', book_id, title, v.views
+               from visit_day v join book on v.kind='book' and book_id=v.obj_id
+               where user_id=$1 and v.day > current_date - $2::int
+           ) t
+           group by kind, id, title
+           order by 4 desc, 2 desc
+           limit $3"#,
+        user_id,
+        days,
+        size
+    )
+    .fetch_all(&ctx.db)
+    .await?;
+    Ok(rows)
+}
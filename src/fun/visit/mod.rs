@@ -0,0 +1,301 @@
+//! 访问统计
+//!
+//! 打开内容页时只在内存里计数，后台任务定时写入 visit_day 并增加 click。
+//! 同一个 session 或 IP 在 visit.window_minutes 内只算一次，
+//! 爬虫和作者自己不计数。
+
+pub mod db;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts, MatchedPath, Path, State};
+use axum::http::{header::USER_AGENT, request::Parts};
+use axum::{routing::get, Router};
+use axum_session::Session;
+use axum_session_sqlx::SessionPgPool;
+use chrono::{Local, NaiveDate};
+use maud::{html, Markup};
+
+use crate::config::Visit;
+use crate::fun::kind::{self, Kind};
+use crate::fun::layout::Html;
+use crate::fun::user::{get_user_from, Cap};
+use crate::http::types::Page;
+use crate::http::{error::AppError, Result, WebContext};
+
+use db::*;
+
+const MY_DAYS: i32 = 30;
+const ITEM_DAYS: i32 = 90;
+const TOP_SIZE: i64 = 20;
+
+pub fn router() -> Router<WebContext> {
+    Router::new()
+        .route("/my/visit", get(visit_my))
+        .route("/my/visit/:kind/:id", get(visit_item))
+}
+
+/// 访问者：session 和 IP，是否是爬虫
+#[derive(Debug, Clone, Default)]
+pub struct Visitor {
+    pub keys: Vec<String>,
+    pub bot: bool,
+}
+
+#[async_trait]
+impl FromRequestParts<WebContext> for Visitor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        ctx: &WebContext,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let bot = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map_or(true, |agent| is_bot(agent, &ctx.config.visit.bots));
+        let mut keys = Vec::new();
+        if let Ok(session) = Session::<SessionPgPool>::from_request_parts(parts, ctx).await {
+            keys.push(format!("s:{}", session.get_session_id()));
+        }
+        if let Some(ip) = client_ip(parts) {
+            keys.push(format!("i:{}", ip));
+        }
+        Ok(Visitor { keys, bot })
+    }
+}
+
+/// 没有 User-Agent 的也当作爬虫
+pub fn is_bot(agent: &str, bots: &[String]) -> bool {
+    let agent = agent.to_lowercase();
+    agent.trim().is_empty() || bots.iter().any(|b| agent.contains(&b.to_lowercase()))
+}
+
+/// 直接连接时用对方地址；本机或内网的反向代理转发时用 X-Real-IP、X-Forwarded-For
+fn client_ip(parts: &Parts) -> Option<IpAddr> {
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0.ip());
+    if peer.map_or(true, is_proxy) {
+        let forwarded = ["x-real-ip", "x-forwarded-for"].iter().find_map(|name| {
+            parts
+                .headers
+                .get(*name)?
+                .to_str()
+                .ok()?
+                .split(',')
+                .next()?
+                .trim()
+                .parse()
+                .ok()
+        });
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    peer
+}
+
+fn is_proxy(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private(),
+        IpAddr::V6(v6) => v6.is_loopback(),
+    }
+}
+
+#[derive(Default)]
+struct Buffer {
+    /// (内容, 访问者) 最后一次计数的时间
+    seen: HashMap<(Kind, i32, String), Instant>,
+    /// 还没有写入数据库的次数
+    pending: HashMap<(Kind, i32, NaiveDate), i32>,
+}
+
+/// 内存中的访问计数，放在 WebContext 里共享
+#[derive(Clone)]
+pub struct Visits {
+    window: Duration,
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+impl Visits {
+    pub fn new(config: &Visit) -> Self {
+        Visits {
+            window: Duration::from_secs(config.window_minutes * 60),
+            buffer: Arc::default(),
+        }
+    }
+
+    /// 记一次访问，重复的返回 false
+    pub fn hit(&self, kind: Kind, id: i32, visitor: &Visitor) -> bool {
+        if visitor.bot || visitor.keys.is_empty() {
+            return false;
+        }
+        let now = Instant::now();
+        let mut buffer = self.buffer.lock().unwrap();
+        let window = self.window;
+        let repeat = visitor.keys.iter().any(|k| {
+            buffer
+                .seen
+                .get(&(kind, id, k.clone()))
+                .map_or(false, |t| now.duration_since(*t) < window)
+        });
+        if repeat {
+            return false;
+        }
+        for k in &visitor.keys {
+            buffer.seen.insert((kind, id, k.clone()), now);
+        }
+        let today = Local::now().date_naive();
+        *buffer.pending.entry((kind, id, today)).or_insert(0) += 1;
+        true
+    }
+
+    /// 取出还没有写入的次数，同时清理过期的访问者
+    fn take(&self) -> Vec<Hit> {
+        let now = Instant::now();
+        let mut buffer = self.buffer.lock().unwrap();
+        let window = self.window;
+        buffer.seen.retain(|_, t| now.duration_since(*t) < window);
+        buffer
+            .pending
+            .drain()
+            .map(|((kind, id, day), views)| Hit {
+                kind,
+                id,
+                day,
+                views,
+            })
+            .collect()
+    }
+
+    /// 写入失败时放回去，下次再写
+    fn put_back(&self, hits: Vec<Hit>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        for h in hits {
+            *buffer.pending.entry((h.kind, h.id, h.day)).or_insert(0) += h.views;
+        }
+    }
+}
+
+/// 写入数据库，返回写入的记录数
+pub async fn flush(ctx: &WebContext) -> anyhow::Result<usize> {
+    let hits = ctx.visits.take();
+    if hits.is_empty() {
+        return Ok(0);
+    }
+    if let Err(e) = db_flush(ctx, &hits).await {
+        ctx.visits.put_back(hits);
+        return Err(e);
+    }
+    Ok(hits.len())
+}
+
+/// 后台定时写入
+pub async fn flush_task(ctx: WebContext) {
+    let seconds = ctx.config.visit.flush_seconds.max(1);
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Err(e) = flush(&ctx).await {
+            tracing::error!("flush visits: {:#}", e);
+        }
+    }
+}
+
+fn days_table(rows: &[DayViews]) -> Markup {
+    let max = rows.iter().map(|r| r.views).max().unwrap_or(0).max(1);
+    html! {
+        @if rows.is_empty() {
+            div class="m-2 text-secondary" {"还没有访问记录"}
+        }
+        @for r in rows {
+            div class="row m-1 align-items-center" {
+                div class="col-4 col-md-2" {(r.day)}
+                div class="col-2 col-md-1 text-end" {(r.views)}
+                div class="col-6 col-md-9" {
+                    div class="progress" {
+                        div class="progress-bar" style={"width: " (r.views * 100 / max) "%"} {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 我的内容最近的访问
+async fn visit_my(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    path: MatchedPath,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let days = db_user_days(&ctx, user.id, MY_DAYS).await?;
+    let top = db_user_top(&ctx, user.id, MY_DAYS, TOP_SIZE).await?;
+    let main = html! {
+        div class="container" {
+            h5 class="m-2" {"最近 " (MY_DAYS) " 天每天的访问"}
+            (days_table(&days))
+            h5 class="m-2 mt-4" {"最近 " (MY_DAYS) " 天访问最多"}
+            @for t in top {
+                @if let Some(kind) = Kind::from_path(&t.kind) {
+                    div class="row border-bottom m-2 p-1" {
+                        div class="col col-md-1" {(kind.name())}
+                        div class="col col-md-8" {
+                            a href={(kind.view_url(t.id)) "#start"} {(t.title)}
+                        }
+                        div class="col col-md-3 text-end" {
+                            a href={"/my/visit/" (t.kind) "/" (t.id)} {(t.views)}
+                        }
+                    }
+                }
+            }
+        }
+    };
+    Ok(Html::new("访问统计", main)
+        .path(Some(path.as_str()))
+        .my_huxi(&user)
+        .page(&ctx))
+}
+
+/// 一条内容最近的访问，作者或者有 EditAll 权限
+async fn visit_item(
+    State(ctx): State<WebContext>,
+    session: Session<SessionPgPool>,
+    Path((kind, id)): Path<(String, i32)>,
+) -> Result<Page> {
+    let user = get_user_from(&session).await?;
+    let kind = Kind::from_path(&kind).ok_or(AppError::NotFound("kind"))?;
+    let kind::Owner {
+        user_id: owner_id,
+        title,
+        ..
+    } = kind::db_owner(&ctx, kind, id)
+        .await?
+        .ok_or(AppError::NotFound("visit"))?;
+    if !user.can_manage(owner_id, Cap::EditAll) {
+        return Err(AppError::Unauthorized);
+    }
+    let days = db_item_days(&ctx, kind, id, ITEM_DAYS).await?;
+    let main = html! {
+        div class="container" {
+            h5 class="m-2" {
+                a href={(kind.view_url(id)) "#start"} {(title)}
+                span class="ms-3 text-secondary" {"最近 " (ITEM_DAYS) " 天"}
+            }
+            (days_table(&days))
+        }
+    };
+    Ok(Html::new("访问统计", main)
+        .path(Some("/my/visit"))
+        .my_huxi(&user)
+        .page(&ctx))
+}
@@ -1,14 +1,18 @@
 use crate::config::CategoryType;
+use crate::db::filter::{self, Filter};
 use crate::fun::book::db::BookSimple;
 use crate::fun::hu::db::HuSimple;
+use crate::fun::kind::Kind;
+use crate::fun::publish::{scheduled_info, SCHEDULED};
 use crate::fun::widget::*;
 use crate::fun::xi::db::XiSimple;
 use crate::http::WebContext;
 use maud::{html, Markup, PreEscaped};
 
-pub const PUBLIC_STATUS_HTML: [(i16, &str); 2] = [
+pub const PUBLIC_STATUS_HTML: [(i16, &str); 3] = [
     (0, "<span class='text-danger'>草稿</span>"),
     (1, "<span class='text-success'>已公布</span>"),
+    (SCHEDULED, "<span class='text-warning'>定时发布</span>"),
 ];
 
 pub fn get_status_name<const N: usize>(status_map: [(i16, &str); N], idx: i16) -> Option<&str> {
@@ -65,6 +69,8 @@ pub struct List<'a> {
     pub show_search_bar: bool,
     pub show_type: ShowType,
     pub show_cat_type_name: bool,
+    /// None: 不显示排序链接
+    pub sort: Option<Sort>,
 }
 
 impl<'a> List<'a> {
@@ -86,6 +92,7 @@ impl<'a> List<'a> {
             title_search_key: None,
             show_cat_type_name: false,
             show_search_bar: true,
+            sort: None,
         }
     }
     pub fn i_tip(mut self, tip: Option<Markup>) -> Self {
@@ -137,10 +144,66 @@ impl<'a> List<'a> {
         self.title_search_key = k;
         self
     }
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
     pub fn show_type(mut self, show: ShowType) -> Self {
         self.show_type = show;
         self
     }
+    /// 列表的查询条件，hu/xi/book 的 db_list 共用
+    pub fn filter(&self, table: &'static str) -> Filter {
+        let filter = match self.filter {
+            ListBy::All => Filter::new(table),
+            ListBy::UserId(id) => Filter::new(table).eq("user_id", id),
+            ListBy::AllPublic => Filter::new(table).eq("i_public", 1i16),
+        };
+        filter
+            .eq_opt("i_category", self.cat)
+            .eq_opt("i_type", self.i_type)
+            .eq_opt("good", self.good)
+            .web_search(self.ctx.config.search.backend, self.web_search_key)
+            .title_like(self.title_search_key)
+    }
+    pub fn order_by(&self, kind: Kind) -> &'static str {
+        match self.sort.unwrap_or_default() {
+            Sort::New => kind.order_by(),
+            Sort::Star => kind.order_by_star(),
+        }
+    }
+    /// 分页链接带上排序
+    fn pager_url(&self) -> Option<String> {
+        self.pager.map(|path| match self.sort {
+            Some(Sort::Star) => format!("{}?sort=star", path),
+            _ => path.to_string(),
+        })
+    }
+    fn sort_bar(&self) -> Markup {
+        let (path, current) = match (self.pager, self.sort) {
+            (Some(path), Some(sort)) => (path, sort),
+            _ => return html!(),
+        };
+        let star = format!("{}?sort=star", path);
+        let items = [
+            (Sort::New, path, "最新"),
+            (Sort::Star, star.as_str(), "星标最多"),
+        ];
+        html! {
+            div class="text-end small mx-3 mb-2" {
+                @for (sort, url, txt) in items {
+                    @if sort == current {
+                        span class="ms-3 fw-bold" {(txt)}
+                    } @else {
+                        a class="ms-3" href=(url) {(txt)}
+                    }
+                }
+            }
+        }
+    }
+    pub fn offset(&self) -> i64 {
+        filter::offset(self.page, self.size)
+    }
     pub fn show(self, total: i64, db: DbList) -> Markup {
         match db {
             DbList::Book(data) => self.list_book(total, data),
@@ -157,12 +220,13 @@ impl<'a> List<'a> {
         match self.show_type {
             ShowType::Normal => {
                 html! {
-                    @if let Some(tip_markup) = self.tip {
+                    @if let Some(ref tip_markup) = self.tip {
                         (tip_markup)
                     }
                     @if self.show_search_bar {
                         (search_bar("book", self.cat.unwrap_or(0), self.i_type.unwrap_or(0), self.admin))
                     }
+                    (self.sort_bar())
                     div class="container" {
                         @for book in data {
                             div class="row justify-content-center border m-2 p-2 shadow p-3 mb-3 bg-body rounded row-cols-1" {
@@ -184,6 +248,9 @@ impl<'a> List<'a> {
                                             span class="mx-2" {
                                                 (show_date(book.created_at))
                                             }
+                                            @if book.star > 0 {
+                                                span class="me-2 text-warning" {"★ " (book.star)}
+                                            }
                                             @if self.show_cat_type_name {
                                                 @if let Some((cat_path, cat_name)) = self.ctx.config.book.category.path_name(book.i_category as u8) {
                                                     mark class="me-2" {
@@ -203,6 +270,9 @@ impl<'a> List<'a> {
                                                         span class="mx-2" {
                                                             (get_igood_status(book.i_good, book.good))
                                                         }
+                                                    @if book.i_public == SCHEDULED {
+                                                        (scheduled_info(Kind::Book, book.book_id, book.publish_at, self.pager.unwrap_or("/my/book")))
+                                                    }
                                                     br;
                                                     a href={"/my/book/edit/" (book.book_id) "#start"} class="mx-3" {"编辑"}
                                                     @let cfm = format!("javascript:if(confirm('确实要删除吗?'))location='/my/book/rm/{}'", book.book_id);
@@ -244,8 +314,8 @@ impl<'a> List<'a> {
                             }
                         }
 
-                        @if let Some(path) = self.pager {
-                            (pager(path, total, self.size, self.page))
+                        @if let Some(url) = self.pager_url() {
+                            (pager(&url, total, self.size, self.page))
                         }
                     }
                 }
@@ -291,12 +361,13 @@ impl<'a> List<'a> {
         match self.show_type {
             ShowType::Normal => {
                 html! {
-                    @if let Some(tip_markup) = self.tip {
+                    @if let Some(ref tip_markup) = self.tip {
                         (tip_markup)
                     }
                     @if self.show_search_bar {
                         (search_bar("hu", self.cat.unwrap_or(0), self.i_type.unwrap_or(0), self.admin))
                     }
+                    (self.sort_bar())
                     div class="container hu" {
                         @for hu in data {
                             div class="row border p-1 shadow mb-2 bg-body rounded row-cols-1" {
@@ -311,6 +382,9 @@ impl<'a> List<'a> {
                                     span class="" {
                                         (hu.user_name)
                                     }
+                                    @if hu.star > 0 {
+                                        span class="ms-2 text-warning" {"★ " (hu.star)}
+                                    }
                                 }
                                 div class="col col-md-3" {
                                     @if self.show_cat_type_name {
@@ -333,6 +407,9 @@ impl<'a> List<'a> {
                                         span class="mx-2" {
                                             (get_igood_status(hu.i_good, hu.good))
                                         }
+                                        @if hu.i_public == SCHEDULED {
+                                            (scheduled_info(Kind::Hu, hu.hu_id, hu.publish_at, self.pager.unwrap_or("/my/hu")))
+                                        }
                                         br;
                                         a href={"/my/hu/edit/" (hu.hu_id) "#start"} class="ms-3" {"编辑"}
                                         @let cfm = format!("javascript:if(confirm('确实要删除吗?'))location='/my/hu/rm/{}'", hu.hu_id);
@@ -349,8 +426,8 @@ impl<'a> List<'a> {
                             }
                         }
 
-                        @if let Some(path) = self.pager {
-                            (pager(path, total, self.size, self.page))
+                        @if let Some(url) = self.pager_url() {
+                            (pager(&url, total, self.size, self.page))
                         }
                     }
                 }
@@ -401,12 +478,13 @@ impl<'a> List<'a> {
         match self.show_type {
             ShowType::Normal => {
                 html! {
-                    @if let Some(tip_markup) = self.tip {
+                    @if let Some(ref tip_markup) = self.tip {
                         (tip_markup)
                     }
                     @if self.show_search_bar {
                         (search_bar("xi", self.cat.unwrap_or(0), self.i_type.unwrap_or(0), self.admin))
                     }
+                    (self.sort_bar())
                     div class="container weibo" {
                         @for xi in data {
                             div class="row justify-content-center"{
@@ -425,6 +503,9 @@ impl<'a> List<'a> {
                                                 span class="" {
                                                     (xi.user_name)
                                                 }
+                                                @if xi.star > 0 {
+                                                    span class="ms-2 text-warning" {"★ " (xi.star)}
+                                                }
                                             }
                                             div class="col col-md-3" {
                                                 @if let Some((cat_path, cat_name)) = self.ctx.config.xi.category.path_name(xi.i_category as u8) {
@@ -444,6 +525,9 @@ impl<'a> List<'a> {
                                                         span class="mx-2" {
                                                             (get_igood_status(xi.i_good, xi.good))
                                                         }
+                                                    @if xi.i_public == SCHEDULED {
+                                                        (scheduled_info(Kind::Xi, xi.xi_id, xi.publish_at, self.pager.unwrap_or("/my/xi")))
+                                                    }
                                                 }
                                                 div class="col col-md-3" {
                                                     span class="float-none float-md-end text-nowrap" {
@@ -474,8 +558,8 @@ impl<'a> List<'a> {
                                 }
                             }
                         }
-                        @if let Some(path) = self.pager {
-                            (pager(path, total, self.size, self.page))
+                        @if let Some(url) = self.pager_url() {
+                            (pager(&url, total, self.size, self.page))
                         }
                     }
                 }
@@ -172,13 +172,15 @@ pub fn pager(url: &str, total: i64, size: u8, current_page: u32) -> Markup {
     let pages = f64::ceil(total as f64 / size as f64) as u32;
     let cp = current_page - 1;
     let cn = current_page + 1;
+    // url 里已经有查询参数时用 & 连接
+    let url = &format!("{}{}", url, if url.contains('?') { '&' } else { '?' });
     html!(
         div class="d-flex justify-content-center" aria-label="..."{
             ul class="pagination" {
                 @if total >  size as i64 {
                     @if current_page != 1 {
                         li class="page-item" {
-                            a class="page-link" href={(url) "?page=" (cp)} aria-label="上一页" {
+                            a class="page-link" href={(url) "page=" (cp)} aria-label="上一页" {
                                 span aria-hidden="true" {"《"}
                             }
                         }
@@ -190,14 +192,14 @@ pub fn pager(url: &str, total: i64, size: u8, current_page: u32) -> Markup {
                              }
                         } @else {
                             li class="page-item" {
-                                a class="page-link" href={(url) "?page=" (page)} {(page)}
+                                a class="page-link" href={(url) "page=" (page)} {(page)}
                              }
                         }
                     }
 
                     @if current_page < pages {
                       li class="page-item" {
-                          a class="page-link" href={(url) "?page=" (cn)} aria-label="下一页" {
+                          a class="page-link" href={(url) "page=" (cn)} aria-label="下一页" {
                               span aria-hidden="true" {"》"}
                           }
                       }
@@ -209,16 +211,52 @@ pub fn pager(url: &str, total: i64, size: u8, current_page: u32) -> Markup {
 }
 
 #[derive(Deserialize, Debug)]
+#[serde(default)]
 pub struct Pagination {
     pub page: u32,
+    pub sort: Sort,
 }
 
 impl Default for Pagination {
     fn default() -> Self {
-        Self { page: 1 }
+        Self {
+            page: 1,
+            sort: Sort::New,
+        }
     }
 }
 
+/// 列表排序：?sort=star 按星标数
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    New,
+    Star,
+}
+
+/// 操作以后回到的页面：?url=/my/star
+#[derive(Deserialize, Debug, Default)]
+pub struct Back {
+    pub url: Option<String>,
+}
+
+impl Back {
+    /// 只回到本站的页面，没有或者不是本站时用 default
+    pub fn url_or(self, default: String) -> String {
+        self.url.filter(|u| is_local(u)).unwrap_or(default)
+    }
+}
+
+/// 本站的路径：以 / 开头，第二个字符不是 / 或 \（浏览器把 /\ 当作 //），
+/// 也没有浏览器会忽略的控制字符
+pub fn is_local(url: &str) -> bool {
+    let mut chars = url.chars();
+    chars.next() == Some('/')
+        && !matches!(chars.next(), Some('/' | '\\'))
+        && !url.chars().any(char::is_control)
+}
+
 #[derive(Deserialize, Debug, Default)]
 pub struct CatType {
     pub cat: i16,
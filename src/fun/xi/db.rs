@@ -1,10 +1,11 @@
 use chrono::{DateTime, Utc};
 
 use crate::db;
+use crate::fun::kind::{self, Kind};
 use crate::fun::user::SessUser;
-use crate::fun::widget::list::{DbList, List, ListBy};
+use crate::fun::widget::list::{DbList, List};
+use crate::fun::{embed, publish, revision, tag};
 use crate::http::WebContext;
-use crate::md;
 
 #[derive(serde::Serialize, serde::Deserialize, sqlx::FromRow, Debug)]
 pub struct Xi {
@@ -27,6 +28,7 @@ pub struct Xi {
     pub updated_at: Option<DateTime<Utc>>,
     pub good_at: Option<DateTime<Utc>>,
     pub click: i32,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 impl Xi {
@@ -40,6 +42,7 @@ impl Xi {
             url: self.url,
             tags: self.tags,
             body: self.body,
+            publish_at: publish::input_value(self.publish_at),
         }
     }
 }
@@ -54,8 +57,20 @@ pub struct Input {
     pub url: Option<String>,
     pub tags: Option<String>,
     pub body: String,
+    /// 定时发布的时间，表单中的本地时间
+    pub publish_at: Option<String>,
 }
 impl Input {
+    pub fn content(&self) -> revision::db::Content<'_> {
+        revision::db::Content {
+            title: &self.title,
+            brief: None,
+            body: &self.body,
+            body2: None,
+            log: None,
+        }
+    }
+
     pub fn check(&mut self) -> std::result::Result<(), Vec<String>> {
         let mut error: Vec<String> = Vec::new();
         self.title = self.title.trim().to_string();
@@ -65,6 +80,7 @@ impl Input {
         if self.body.is_empty() {
             error.push("请输入正文".to_string());
         }
+        publish::check(self.i_public, &mut self.publish_at, &mut error);
         if !error.is_empty() {
             return Err(error);
         }
@@ -87,66 +103,44 @@ pub struct XiSimple {
     pub url: Option<String>,
     pub i_public: i16,
     pub i_good: i16,
+    pub star: i32,
     pub created_at: DateTime<Utc>,
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 pub async fn db_list<'a>(list: &List<'a>) -> anyhow::Result<(i64, DbList)> {
-    let offset = list.size as u32 * (list.page - 1);
-    let s_web = list.web_search_key.map_or("".to_string(), |k| {
-        format!("and search_ti @@ websearch_to_tsquery('{}') ", k)
-    });
-    let s_title = list.title_search_key.map_or("".to_string(), |k| {
-        format!("and lower(title) like '%{}%' ", k.to_lowercase())
-    });
-    let s_type = list
-        .i_type
-        .map_or("".to_string(), |tid| format!("and i_type={} ", tid));
-    let s_cat = list
-        .cat
-        .map_or("".to_string(), |cid| format!("and i_category={} ", cid));
-    let s_good = list
-        .good
-        .map_or("".to_string(), |gid| format!("and good={} ", gid));
-    let sql_where = match list.filter {
-        ListBy::All => "".to_owned(),
-        ListBy::UserId(id) => format!(
-            "where user_id={id} {} {} {} {} {}",
-            s_cat, s_type, s_good, s_title, s_web
-        ),
-        ListBy::AllPublic => format!(
-            "where i_public=1 {} {} {} {} {}",
-            s_cat, s_type, s_good, s_title, s_web
-        ),
-    };
-    let sql_total = format!("select count(*) as total from xi {};", &sql_where);
-    let row = sqlx::query_as::<_, db::Total>(&sql_total)
+    let filter = list.filter("xi");
+    let total = filter
+        .count()
+        .build_query_as::<db::Total>()
         .fetch_one(&list.ctx.db)
-        .await?;
-    let total = row.total;
-    let sql = format!(
-        r#"
-            select
-                xi_id, title, user_name, body, html, i_public, i_type, i_category, good, created_at, url, i_good
-            from xi
-            {}
-            order by xi_id desc limit {} offset {};"#,
-        sql_where, list.size, offset
-    );
-    let rows = sqlx::query_as::<_, XiSimple>(&sql)
+        .await?
+        .total;
+    let rows = filter
+        .select(
+            "xi_id, title, user_name, body, html, i_public, i_type, i_category, good, created_at, url, i_good, star, publish_at",
+            list.order_by(Kind::Xi),
+            list.size as i64,
+            list.offset(),
+        )
+        .build_query_as::<XiSimple>()
         .fetch_all(&list.ctx.db)
         .await?;
     Ok((total, DbList::Xi(rows)))
 }
 
 pub async fn db_insert(ctx: &WebContext, user: &SessUser, input: Input) -> anyhow::Result<i32> {
-    let html = md::to_html(&input.body);
+    let html = embed::render(ctx, Kind::Xi, user.id, &input.content())
+        .await?
+        .html;
 
     let i_good = input.i_good.map_or(0, |v| v);
 
+    let mut tx = ctx.db.begin().await?;
     let rec = sqlx::query!(
         r#"insert into xi
-             (user_id, user_name, title, body, html, i_category, i_public, url, tags, i_good, i_type)
-           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+             (user_id, user_name, title, body, html, i_category, i_public, url, tags, i_good, i_type, publish_at)
+           values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
            returning xi_id"#,
         user.id,
         user.name,
@@ -159,19 +153,29 @@ pub async fn db_insert(ctx: &WebContext, user: &SessUser, input: Input) -> anyho
         input.tags,
         i_good,
         input.i_type,
+        publish::publish_at(input.i_public, input.publish_at.as_deref()),
     )
-    .fetch_one(&ctx.db)
+    .fetch_one(&mut *tx)
     .await?;
+    tag::db::db_set_tags(&mut tx, Kind::Xi.table(), rec.xi_id, input.tags.as_deref()).await?;
+    tx.commit().await?;
     Ok(rec.xi_id)
 }
 
 pub async fn db_update(ctx: &WebContext, id: i32, input: &Input) -> anyhow::Result<bool> {
-    let html = md::to_html(&input.body);
+    let owner = match kind::db_owner(ctx, Kind::Xi, id).await? {
+        Some(owner) => owner,
+        None => return Ok(false),
+    };
+    let html = embed::render(ctx, Kind::Xi, owner.user_id, &input.content())
+        .await?
+        .html;
     let i_good = input.i_good.map_or(0, |v| v);
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"update xi
-           set body=$1, html=$2, i_category=$3, i_public=$4, i_type=$5, url=$6, tags=$7, i_good=$8, title=$9 
-           where xi_id=$10"#,
+           set body=$1, html=$2, i_category=$3, i_public=$4, i_type=$5, url=$6, tags=$7, i_good=$8, title=$9, publish_at=$10
+           where xi_id=$11"#,
         input.body,
         html,
         input.i_category,
@@ -181,12 +185,17 @@ pub async fn db_update(ctx: &WebContext, id: i32, input: &Input) -> anyhow::Resu
         input.tags,
         i_good,
         input.title,
+        publish::publish_at(input.i_public, input.publish_at.as_deref()),
         id
     )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows > 0 {
+        tag::db::db_set_tags(&mut tx, Kind::Xi.table(), id, input.tags.as_deref()).await?;
+    }
+    tx.commit().await?;
     Ok(rows > 0)
 }
 
@@ -194,7 +203,7 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Xi> {
     let rec = sqlx::query_as!(
         Xi,
         r#"select xi_id as id, title, user_id, user_name, body, html, i_category, i_type, i_public, i_good, star,
-                url, tags, good, created_at, updated_at, good_at, click
+                url, tags, good, created_at, updated_at, good_at, click, publish_at
            from xi where xi_id=$1"#,
         id
     )
@@ -204,30 +213,21 @@ pub async fn db_get_one(ctx: &WebContext, id: i32) -> anyhow::Result<Xi> {
     Ok(rec)
 }
 
-pub async fn db_update_click(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
-    let rows = sqlx::query!(
-        r#"update xi
-           set click=click+1
-           where xi_id=$1"#,
-        id
-    )
-    .execute(&ctx.db)
-    .await?
-    .rows_affected();
-
-    Ok(rows == 1)
-}
-
 pub async fn db_rm(ctx: &WebContext, id: i32) -> anyhow::Result<bool> {
+    let mut tx = ctx.db.begin().await?;
     let rows = sqlx::query!(
         r#"delete from xi
            where xi_id=$1"#,
         id
     )
-    .execute(&ctx.db)
+    .execute(&mut *tx)
     .await?
     .rows_affected();
 
+    if rows == 1 {
+        kind::db_rm_related(&mut tx, Kind::Xi, id).await?;
+    }
+    tx.commit().await?;
     Ok(rows == 1)
 }
 
@@ -12,17 +12,23 @@ use maud::{html, Markup, PreEscaped};
 use serde::Deserialize;
 
 use crate::config::CategoryType;
-use crate::fun::comment;
+use crate::fun::kind::Kind;
 use crate::fun::layout::{split, vsplit, Html};
-use crate::fun::user::{check_sudo, get_user_from, is_sudo_role, SessUser};
+use crate::fun::user::{check_cap, get_user_can, get_user_from, Cap, SessUser};
+use crate::fun::visit::Visitor;
 use crate::fun::widget::list::*;
 use crate::fun::widget::*;
+use crate::fun::{comment, embed, publish, star, tag};
 use crate::http::types::Page;
 use crate::http::{error::AppError, Result, WebContext};
 
 use db::*;
 
-static PUBLIC_STATUS: [(i16, &str, &str); 2] = [(0, "craft", "草稿"), (1, "published", "公布")];
+static PUBLIC_STATUS: [(i16, &str, &str); 3] = [
+    (0, "craft", "草稿"),
+    (1, "published", "公布"),
+    (publish::SCHEDULED, "scheduled", "定时发布"),
+];
 
 pub fn router() -> Router<WebContext> {
     Router::new()
@@ -55,7 +61,8 @@ async fn xi_my(
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .size(10)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role));
+        .sort(pagination.sort)
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     Ok(Html::new("我的微博", main)
@@ -73,15 +80,19 @@ async fn xi_pub(
 ) -> Result<Page> {
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .size(10)
         .pager(Some(path.as_str()))
+        .sort(pagination.sort)
         .sudo(sudo)
         .admin(false);
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
-    let right = list_category_name(&ctx, false);
+    let right = html! {
+        (list_category_name(&ctx, false))
+        (tag::tag_cloud(&ctx).await?)
+    };
     let main = split(left, right);
     Ok(Html::new("微博", main)
         .path(Some(path.as_str()))
@@ -95,7 +106,7 @@ async fn xi_add_input(
     cat_type: Option<Query<CatType>>,
     path: MatchedPath,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
+    let user = get_user_can(&session, Cap::Publish).await?;
     let Query(cat_type) = cat_type.unwrap_or_default();
     let xi: Input = Input {
         i_category: cat_type.cat,
@@ -162,7 +173,7 @@ fn input_form(ctx: &WebContext, xi: &Input, error: ErrorMessage, edit: bool) ->
                             div class="row mb-3 border-bottom" {
                                 label class="col-md-2 col-form-label text-md-end" for="tags" {"Tags："}
                                 div class="col-md-7" {
-                                    (TextInput::new("tags", "tags", false).value(xi.tags.as_ref()).show())
+                                    (tag::tag_input(xi.tags.as_ref()))
                                 }
                                 div class="col-md-3" {
                                 }
@@ -188,6 +199,7 @@ fn input_form(ctx: &WebContext, xi: &Input, error: ErrorMessage, edit: bool) ->
                                     @for (_i, p) in PUBLIC_STATUS.iter().enumerate() {
                                         (radio(p.1, "i_public", &p.0.to_string(), xi.i_public == p.0, p.2))
                                     }
+                                    (publish::publish_input(xi.publish_at.as_ref()))
                                 }
                             }
                             div class="text-center bg-light" {
@@ -211,23 +223,26 @@ async fn xi_add_do(
     path: MatchedPath,
     Form(mut input): Form<Input>,
 ) -> Result<Page> {
-    let user = get_user_from(&session).await?;
-    let check = input.check();
-    if let Err(e) = check {
+    let user = get_user_can(&session, Cap::Publish).await?;
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), false);
         return Ok(Html::new("", main)
             .my_huxi(&user)
             .path(Some(path.as_str()))
             .page(&ctx));
     }
+    let broken = embed::broken(&ctx, user.id, &[input.body.as_str()]).await?;
     let id = db_insert(&ctx, &user, input).await?;
     let main = html!(
         (tip("新微博添加成功"))
+        (embed::warning(&broken))
         div class="text-center" {
             a href={"/xi/view/" (id) "/index.html#start"} class="m-2" {"继续查看"}
             a href="/my/xi" class="m-2" {"显示列表"}
         }
-        (PreEscaped(redirect_script("/my/xi")))
+        @if broken.is_empty() {
+            (PreEscaped(redirect_script("/my/xi")))
+        }
     );
     Ok(Html::new("微博添加成功", main)
         .my_huxi(&user)
@@ -252,7 +267,7 @@ async fn xi_edit_input(
 
 async fn check_owner(xi_user_id: i32, session: &Session<SessionPgPool>) -> Result<SessUser> {
     let user = get_user_from(session).await?;
-    if xi_user_id == user.id || is_sudo_role(user.role) {
+    if user.can_manage(xi_user_id, Cap::EditAll) {
         Ok(user)
     } else {
         Err(AppError::InvalidLogin("/user/error".into()))
@@ -268,8 +283,7 @@ async fn xi_edit_do(
 ) -> Result<Page> {
     let xi = db_get_one(&ctx, id).await?;
     let user = check_owner(xi.user_id, &session).await?;
-    let check = input.check();
-    if let Err(e) = check {
+    if let Err(e) = input.check() {
         let main = input_form(&ctx, &input, Some(e), true);
         return Ok(Html::new("修改微博", main)
             .my_huxi(&user)
@@ -286,13 +300,17 @@ async fn xi_edit_do(
             .path(Some(path.as_str()))
             .page(&ctx));
     }
+    let broken = embed::broken(&ctx, xi.user_id, &[input.body.as_str()]).await?;
     let main = html!(
         (tip("修改微博成功"))
+        (embed::warning(&broken))
         div class="text-center" {
             a href={"/xi/view/" (id) "/index.html#start"} class="m-2" {"继续查看"}
             a href="/my/xi" class="m-2" {"显示列表"}
         }
-        (PreEscaped(redirect_script("/my/xi")))
+        @if broken.is_empty() {
+            (PreEscaped(redirect_script("/my/xi")))
+        }
     );
     Ok(Html::new("修改微博成功", main)
         .my_huxi(&user)
@@ -300,7 +318,7 @@ async fn xi_edit_do(
         .page(&ctx))
 }
 
-fn view_url(id: i32) -> String {
+pub fn view_url(id: i32) -> String {
     format!("/xi/view/{}/index.html", id)
 }
 
@@ -308,6 +326,7 @@ async fn xi_view(
     session: Session<SessionPgPool>,
     State(ctx): State<WebContext>,
     Path(id): Path<i32>,
+    visitor: Visitor,
     path: MatchedPath,
 ) -> Result<Page> {
     let xi = db_get_one(&ctx, id).await?;
@@ -318,17 +337,18 @@ async fn xi_view(
     let mut admin = false;
     let mut sudo = false;
     let mut owner: Option<SessUser> = None;
-    if xi.i_public > 0 {
+    if xi.i_public == 1 {
         allow = true;
     }
     let result = get_user_from(&session).await;
+    let star = star::star_button(&ctx, Kind::Xi, xi.id, xi.star, result.as_ref().ok()).await?;
     if let Ok(user) = result {
         login = true;
-        if is_sudo_role(user.role) {
+        if user.can(Cap::EditAll) {
             allow = true;
             admin = true;
-            sudo = true;
         }
+        sudo = user.can(Cap::Recommend);
         if xi.user_id == user.id {
             allow = true;
             admin = true;
@@ -337,9 +357,11 @@ async fn xi_view(
     }
     let left = if allow {
         let url = view_url(id);
-        let _ = db_update_click(&ctx, id).await;
+        if owner.is_none() {
+            ctx.visits.hit(Kind::Xi, id, &visitor);
+        }
         let cms = comment::list_comment(&ctx, xi.id, url.as_str(), login, admin, "xi").await?;
-        content_html(&xi, cms, &ctx, admin, sudo)
+        content_html(&xi, cms, star, &ctx, admin, sudo)
     } else {
         tip("权限错误")
     };
@@ -367,7 +389,14 @@ async fn xi_view(
     Ok(html.page(&ctx))
 }
 
-fn content_html(xi: &Xi, comment: Markup, ctx: &WebContext, admin: bool, sudo: bool) -> Markup {
+fn content_html(
+    xi: &Xi,
+    comment: Markup,
+    star: Markup,
+    ctx: &WebContext,
+    admin: bool,
+    sudo: bool,
+) -> Markup {
     html! {
         div class="container" {
             div class="row justify-content-center" {
@@ -409,12 +438,18 @@ fn content_html(xi: &Xi, comment: Markup, ctx: &WebContext, admin: bool, sudo: b
                             }
                             div class="col mx-3" {
                                 "点击：" (xi.click)
+                                @if admin {
+                                    a class="ms-2" href={"/my/visit/xi/" (xi.id)} {"统计"}
+                                }
+                            }
+                            div class="col mx-3" {
+                                "星标：" (star)
                             }
                         }
                         div class="row row-cols-1  row-cols-md-4" {
                             @if let Some(ref tags) = xi.tags {
                                 div class="col mx-3" {
-                                    "Tags: " (tags)
+                                    "Tags: " (tag::tag_links(tags))
                                 }
                             }
                             @if admin {
@@ -487,7 +522,7 @@ async fn xi_good(
     let user = check_owner(xi.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 1).await?;
         if result_ok {
             main = tip("推荐成功");
@@ -508,7 +543,7 @@ async fn xi_good_cancel(
     let user = check_owner(xi.user_id, &session).await?;
 
     let mut main = tip("错误");
-    if is_sudo_role(user.role) {
+    if user.can(Cap::Recommend) {
         let result_ok = db_good(&ctx, id, 0).await?;
         if result_ok {
             main = tip("取消推荐成功");
@@ -542,7 +577,7 @@ async fn xi_my_cat(
         .admin(true)
         .cat(cat_id)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role));
+        .sudo(user.can(Cap::Recommend));
     let (total, data) = db_list(&list).await?;
     let main = list.show(total, data);
     let title = format!("我的 {} 微博", cat_name);
@@ -563,7 +598,7 @@ async fn xi_my_cat2(
     let user = get_user_from(&session).await?;
     let Query(pagination) = pagination.unwrap_or_default();
     let page = pagination.page;
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let cat_id = ctx.config.xi.category.id(cat.as_str());
     let cat_name = ctx
         .config
@@ -626,7 +661,7 @@ async fn xi_pub_cat(
         .ok_or_else(|| AppError::InvalidArg("cat name error".to_string()))?;
     let page_link = format!("/xi/cat/{}", cat.as_str());
     let cid = cat.as_str();
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let left = html!(
         div {
             @for ty in &ctx.config.xi.content_type {
@@ -692,7 +727,7 @@ async fn xi_my_cat_type(
         .cat(cat_id)
         .size(10)
         .admin(true)
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .i_type(type_id)
         .pager(Some(path.as_str()));
     let (total, data) = db_list(&list).await?;
@@ -730,14 +765,15 @@ async fn xi_pub_cat_type(
         .name(tid.as_str())
         .ok_or_else(|| AppError::InvalidArg("type name error".to_string()))?;
     let page_link = format!("/xi/cat/{}/{}", cat, tid);
-    let sudo = check_sudo(&session).await;
+    let sudo = check_cap(&session, Cap::Recommend).await;
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .cat(cat_id)
         .size(10)
         .admin(false)
         .sudo(sudo)
         .i_type(type_id)
-        .pager(Some(page_link.as_str()));
+        .pager(Some(page_link.as_str()))
+        .sort(pagination.sort);
     let (total, data) = db_list(&list).await?;
     let left = list.show(total, data);
     let list = List::new(&ctx, ListBy::AllPublic, page)
@@ -829,7 +865,7 @@ async fn xi_search(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::AllPublic, page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.map_or(0, |u| u.role)))
+        .sudo(user.map_or(false, |u| u.can(Cap::Recommend)))
         .admin(false)
         .web_search_key(Some(key));
     let (total, data) = db_list(&list).await?;
@@ -852,7 +888,7 @@ async fn xi_search_my(
     let key = search.key.as_ref().unwrap().as_str();
     let list = List::new(&ctx, ListBy::UserId(user.id), page)
         .pager(Some(path.as_str()))
-        .sudo(is_sudo_role(user.role))
+        .sudo(user.can(Cap::Recommend))
         .title_search_key(Some(key))
         .admin(true);
     let (total, data) = db_list(&list).await?;
@@ -11,6 +11,8 @@ pub enum AppError {
     NotFound(&'static str),
     #[error(" to `{0}` ")]
     InvalidLogin(String),
+    #[error("Unauthorized")]
+    Unauthorized,
     #[error(" database error `{0}` ")]
     Database(String),
     #[error(" arg error `{0}` ")]
@@ -31,6 +33,7 @@ impl IntoResponse for AppError {
                 let url = HeaderValue::try_from(str).expect("URI isn't a valid header value");
                 (StatusCode::TEMPORARY_REDIRECT, [(LOCATION, url)]).into_response()
             }
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()).into_response(),
             AppError::Database(_) => {
                 (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()).into_response()
             }
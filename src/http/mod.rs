@@ -2,11 +2,12 @@ pub mod email;
 pub mod error;
 pub mod types;
 
-use anyhow::{Context, Ok};
+use anyhow::Ok;
 use axum::{
     error_handling::HandleErrorLayer,
     extract::DefaultBodyLimit,
     http::{Method, StatusCode, Uri},
+    middleware,
     response::Redirect,
     BoxError, Router,
 };
@@ -22,6 +23,9 @@ use tower_http::{services::ServeDir, trace::TraceLayer};
 use tracing;
 
 use crate::config::WebConfig;
+use crate::fun::rerender::Rerender;
+use crate::fun::visit::{self, Visits};
+use crate::fun::{publish, user};
 
 pub type Result<T, E = error::AppError> = std::result::Result<T, E>;
 
@@ -29,25 +33,82 @@ pub type Result<T, E = error::AppError> = std::result::Result<T, E>;
 pub struct WebContext {
     pub config: Arc<WebConfig>,
     pub db: PgPool,
+    pub visits: Visits,
+    pub rerender: Rerender,
 }
 
 #[derive(Clone)]
 struct AppState {}
 
+impl WebContext {
+    pub fn new(config: WebConfig, db: PgPool) -> Self {
+        let visits = Visits::new(&config.visit);
+        WebContext {
+            config: Arc::new(config),
+            db,
+            visits,
+            rerender: Rerender::default(),
+        }
+    }
+}
+
 pub async fn serve(config: WebConfig, db: PgPool, port: u16) -> anyhow::Result<()> {
+    let ctx = WebContext::new(config, db);
+    let app = router(ctx.clone()).await?;
+    tokio::spawn(visit::flush_task(ctx.clone()));
+    tokio::spawn(publish::publish_task(ctx.clone()));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::debug!("listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
+
+    // 退出前写入还没有保存的访问统计
+    visit::flush(&ctx).await?;
+    Ok(())
+}
+
+/// Ctrl-C 或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = Box::pin(async {
+        let _ = tokio::signal::ctrl_c().await;
+    });
+    #[cfg(unix)]
+    let terminate = Box::pin(async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            std::result::Result::Ok(mut s) => {
+                s.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    });
+    #[cfg(not(unix))]
+    let terminate = Box::pin(std::future::pending::<()>());
+    futures_util::future::select(ctrl_c, terminate).await;
+}
+
+/// 所有路由和中间件
+pub async fn app(config: WebConfig, db: PgPool) -> anyhow::Result<Router> {
+    router(WebContext::new(config, db)).await
+}
+
+/// 用已有的 WebContext，访问统计等和调用的一方共用
+pub async fn router(ctx: WebContext) -> anyhow::Result<Router> {
     let session_config = SessionConfig::default()
         .with_table_name("sessions")
         .with_max_age(None)
         .with_session_name("huxifun");
 
-    let session_store = SessionStore::<SessionPgPool>::new(Some(db.clone().into()), session_config)
-        .await
-        .unwrap();
-
-    let ctx = WebContext {
-        config: Arc::new(config),
-        db,
-    };
+    let session_store =
+        SessionStore::<SessionPgPool>::new(Some(ctx.db.clone().into()), session_config).await?;
 
     let app = Router::<WebContext>::new()
         .merge(crate::fun::router())
@@ -58,10 +119,28 @@ pub async fn serve(config: WebConfig, db: PgPool, port: u16) -> anyhow::Result<(
         .merge(crate::fun::message::router())
         .merge(crate::fun::image::router())
         .merge(crate::fun::comment::router())
+        .merge(crate::fun::star::router())
+        .merge(crate::fun::tag::router())
+        .merge(crate::fun::search::router())
+        .merge(crate::fun::revision::router())
+        .merge(crate::fun::publish::router())
+        .merge(crate::fun::visit::router())
+        .merge(crate::fun::feed::router())
+        .merge(crate::fun::sitemap::router())
+        .merge(crate::fun::admin::router())
+        .merge(crate::api::router())
         .nest_service("/css", ServeDir::new("htdocs/css"))
         .nest_service("/js", ServeDir::new("htdocs/js"))
         .nest_service("/img", ServeDir::new("htdocs/img"))
-        .with_state(ctx)
+        .with_state(ctx.clone())
+        .layer(middleware::from_fn_with_state(
+            ctx.clone(),
+            user::refresh_user,
+        ))
+        .layer(middleware::from_fn_with_state(
+            ctx,
+            user::token::bearer_user,
+        ))
         .layer(SessionLayer::new(session_store))
         .layer(TraceLayer::new_for_http())
         .layer(DefaultBodyLimit::max(1024 * 1000))
@@ -71,15 +150,7 @@ pub async fn serve(config: WebConfig, db: PgPool, port: u16) -> anyhow::Result<(
                 .timeout(Duration::from_secs(30)),
         );
 
-    let app = app.fallback(handler_404);
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    tracing::debug!("listening on {}", addr);
-
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-
-    Ok(())
+    Ok(app.fallback(handler_404))
 }
 
 async fn handle_timeout_error(
@@ -18,6 +18,23 @@ impl IntoResponse for Page {
     }
 }
 
+/// XML 响应，用于订阅和 sitemap
+pub struct Xml {
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl IntoResponse for Xml {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(self.content_type),
+        );
+        (headers, self.body).into_response()
+    }
+}
+
 struct Debug<T: fmt::Debug>(T);
 
 impl<T: fmt::Debug> Render for Debug<T> {
@@ -4,8 +4,10 @@ use clap::Parser;
 use sqlx::postgres::PgPoolOptions;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use huxi_server::config::{WebArgs, WebConfig};
-use huxi_server::http;
+use huxi_server::config::{Command, WebArgs, WebConfig};
+use huxi_server::fun::{rerender, user};
+use huxi_server::http::WebContext;
+use huxi_server::{db, http};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -28,7 +30,34 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("could not connect to database_url")?;
 
-    http::serve(config, db, args.www_port).await?;
+    let serve = match args.command {
+        Some(Command::Migrate) => return migrate(&db).await,
+        Some(Command::User(cmd)) => {
+            let ctx = WebContext::new(config, db);
+            return user::cli::run(&ctx, cmd).await;
+        }
+        Some(Command::Rerender(args)) => {
+            let ctx = WebContext::new(config, db);
+            return rerender::cli::run(&ctx, args).await;
+        }
+        Some(Command::Serve(serve)) => serve,
+        None => args.serve.context("www_port is required")?,
+    };
+    if serve.auto_migrate {
+        migrate(&db).await?;
+    }
+    http::serve(config, db, serve.www_port).await?;
 
     Ok(())
 }
+
+async fn migrate(db: &sqlx::PgPool) -> anyhow::Result<()> {
+    let applied = db::migrate(db).await.context("database migration failed")?;
+    if applied.is_empty() {
+        println!("database is up to date");
+    }
+    for version in applied {
+        println!("applied migration {}", version);
+    }
+    Ok(())
+}
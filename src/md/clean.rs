@@ -0,0 +1,135 @@
+//! 按内容类型的白名单清理 HTML
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use ammonia::Builder;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::config::WebConfig;
+
+use super::Content;
+
+/// 评论和信件默认可以用的标签
+const COMMENT_TAGS: &[&str] = &[
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "hr",
+    "i",
+    "kbd",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "sub",
+    "sup",
+    "ul",
+];
+
+/// 微博不用大标题，标题在 title 里
+const XI_EXCLUDE: &[&str] = &["h1", "h2"];
+
+/// 内容总是要清除的标签，不能放进白名单
+const NEVER: &[&str] = &["script", "style"];
+
+/// 可以保留的 class，其他的去掉，免得用网站的样式遮住页面
+const CLASS_PREFIX: &[&str] = &["anchor", "language-", "footnote"];
+
+pub fn clean(config: &WebConfig, content: Content, html: &str) -> String {
+    let mut builder = Builder::default();
+    builder
+        .tags(tags(config, content))
+        .add_generic_attributes(["class"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("li", ["id"])
+        .add_tag_attributes("sup", ["id"])
+        .link_rel(None)
+        .attribute_filter(attribute);
+    for h in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(h, ["id"]);
+    }
+    let html = builder.clean(html).to_string();
+    nofollow(&html, &config.host.domain)
+}
+
+/// 配置了白名单时用配置的，否则用默认的
+fn tags(config: &WebConfig, content: Content) -> HashSet<&str> {
+    let configured = match content {
+        Content::Article => &config.markdown.article_tags,
+        Content::Xi => &config.markdown.xi_tags,
+        Content::Comment => &config.markdown.comment_tags,
+    };
+    let mut tags: HashSet<&str> = match configured {
+        Some(list) => list.iter().map(String::as_str).collect(),
+        None => match content {
+            Content::Comment => COMMENT_TAGS.iter().copied().collect(),
+            _ => {
+                let mut tags = Builder::default().clone_tags();
+                tags.insert("input");
+                if content == Content::Xi {
+                    tags.retain(|t| !XI_EXCLUDE.contains(t));
+                }
+                tags
+            }
+        },
+    };
+    tags.retain(|t| !NEVER.contains(t));
+    tags
+}
+
+fn attribute<'u>(element: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    match (element, attribute) {
+        (_, "class") => {
+            let classes: Vec<&str> = value
+                .split_whitespace()
+                .filter(|c| CLASS_PREFIX.iter().any(|p| c.starts_with(p)))
+                .collect();
+            if classes.is_empty() {
+                None
+            } else {
+                Some(classes.join(" ").into())
+            }
+        }
+        ("input", "type") if value != "checkbox" => None,
+        _ => Some(value.into()),
+    }
+}
+
+static A_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<a\s(?:[^>"]|"[^"]*")*>"#).unwrap());
+static HREF: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\shref="([^"]*)""#).unwrap());
+
+/// 外部链接加上 rel="nofollow noopener"
+fn nofollow(html: &str, domain: &str) -> String {
+    A_TAG
+        .replace_all(html, |c: &regex::Captures| {
+            let a = &c[0];
+            match HREF.captures(a) {
+                Some(h) if is_external(&h[1], domain) => {
+                    format!(r#"{} rel="nofollow noopener">"#, &a[..a.len() - 1])
+                }
+                _ => a.to_string(),
+            }
+        })
+        .into_owned()
+}
+
+fn is_external(url: &str, domain: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    let rest = match ["http://", "https://", "//"]
+        .iter()
+        .find(|p| lower.starts_with(*p))
+    {
+        Some(p) => &lower[p.len()..],
+        None => return false,
+    };
+    let host = rest.split(['/', '?', '#', ':']).next().unwrap_or_default();
+    !host.eq_ignore_ascii_case(domain)
+}
@@ -0,0 +1,319 @@
+//! 图，Graphviz 用 layout-rs 生成，Mermaid 调用配置的命令（mermaid-cli 的 mmdc）
+//!
+//! Mermaid 要启动外部程序，渲染前用 prepare 异步生成好，渲染时按代码查找。
+
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use ammonia::Builder;
+use layout::backends::svg::SVGWriter;
+use layout::gv::{DotParser, GraphBuilder};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+use regex::Regex;
+use tokio::process::Command;
+
+use crate::config::Markdown;
+
+use super::escape;
+
+/// Mermaid 命令最多等这么久
+const TIMEOUT: Duration = Duration::from_secs(20);
+/// 一次最多生成这么多 Mermaid 图，多的按代码显示
+const MAX_MERMAID: usize = 10;
+
+/// 渲染前生成好的 Mermaid 图，按代码查找
+#[derive(Debug, Default)]
+pub struct Diagrams(HashMap<String, Result<String, String>>);
+
+/// 生成几段 Markdown 中 mermaid 代码块的图，没有配置命令时什么也不做
+pub async fn prepare(config: &Markdown, texts: &[&str]) -> Diagrams {
+    let mut diagrams = Diagrams::default();
+    if config.mermaid.trim().is_empty() {
+        return diagrams;
+    }
+    for text in texts {
+        for code in mermaid_blocks(text) {
+            if diagrams.0.len() >= MAX_MERMAID {
+                return diagrams;
+            }
+            if let Entry::Vacant(entry) = diagrams.0.entry(code) {
+                let svg = mermaid(&config.mermaid, entry.key()).await;
+                entry.insert(svg);
+            }
+        }
+    }
+    diagrams
+}
+
+fn mermaid_blocks(md: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut code: Option<String> = None;
+    for event in Parser::new_ext(md, super::options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if lang(&info) == "mermaid" =>
+            {
+                code = Some(String::new())
+            }
+            Event::Text(t) => {
+                if let Some(code) = code.as_mut() {
+                    code.push_str(&t);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => blocks.extend(code.take()),
+            _ => {}
+        }
+    }
+    blocks
+}
+
+fn lang(info: &str) -> String {
+    info.split([',', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// 不是图、没有启用或者 Mermaid 没有生成时返回 None，按代码显示
+pub fn render(
+    config: &Markdown,
+    diagrams: Option<&Diagrams>,
+    lang: &str,
+    code: &str,
+) -> Option<String> {
+    let lang = self::lang(lang);
+    let svg = match lang.as_str() {
+        "dot" | "graphviz" if config.graphviz => {
+            graphviz(code).map(|svg| prefix_ids(&svg, &svg_id(code)))
+        }
+        "mermaid" => diagrams?.0.get(code)?.clone(),
+        _ => return None,
+    };
+    Some(match svg.and_then(check) {
+        Ok(svg) => format!("<div class=\"diagram\">{}</div>", svg),
+        Err(e) => failed(&lang, code, &e),
+    })
+}
+
+/// 同一页有几个图时 id 不能重复，按内容生成，重新渲染时不变
+fn svg_id(code: &str) -> String {
+    // FNV-1a
+    let hash = code.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("svg-{:08x}", hash as u32)
+}
+
+static ID_REF: Lazy<Regex> = Lazy::new(|| Regex::new(r##"(id="|href="#|url\(#)"##).unwrap());
+
+/// layout-rs 的 id 是固定的，加上前缀
+fn prefix_ids(svg: &str, id: &str) -> String {
+    ID_REF
+        .replace_all(svg, |c: &regex::Captures| format!("{}{}-", &c[1], id))
+        .into_owned()
+}
+
+fn graphviz(code: &str) -> Result<String, String> {
+    let code = code.to_string();
+    // layout-rs 遇到不支持的写法可能 panic
+    std::panic::catch_unwind(move || {
+        let graph = DotParser::new(&code).process()?;
+        let mut builder = GraphBuilder::new();
+        builder.visit_graph(&graph);
+        let mut visual = builder.get();
+        let mut svg = SVGWriter::new();
+        visual.do_it(false, false, false, &mut svg);
+        Ok(svg.finalize())
+    })
+    .unwrap_or_else(|_| Err("无法布局".to_string()))
+}
+
+async fn mermaid(command: &str, code: &str) -> Result<String, String> {
+    let name = uuid::Uuid::new_v4();
+    let input = std::env::temp_dir().join(format!("huxi-{}.mmd", name));
+    let output = std::env::temp_dir().join(format!("huxi-{}.svg", name));
+    tokio::fs::write(&input, code)
+        .await
+        .map_err(|e| e.to_string())?;
+    let result = match run(command, &input, &output, &svg_id(code)).await {
+        Ok(()) => tokio::fs::read_to_string(&output)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_file(&input).await;
+    let _ = tokio::fs::remove_file(&output).await;
+    result
+}
+
+async fn run(command: &str, input: &Path, output: &Path, id: &str) -> Result<(), String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or("没有配置命令")?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .arg("-i")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .arg("--svgId")
+        .arg(id)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("{}: {}", program, e))?;
+    match tokio::time::timeout(TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("{}: {}", program, status)),
+        Ok(Err(e)) => Err(format!("{}: {}", program, e)),
+        Err(_) => {
+            let _ = child.kill().await;
+            Err(format!("{}: 超时", program))
+        }
+    }
+}
+
+/// SVG 中可以用的标签，foreignObject 里是 Mermaid 的文字标签
+const SVG_TAGS: &[&str] = &[
+    "svg",
+    "g",
+    "defs",
+    "marker",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "text",
+    "tspan",
+    "textPath",
+    "title",
+    "desc",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "clipPath",
+    "use",
+    "foreignObject",
+    "div",
+    "span",
+    "p",
+    "br",
+    "b",
+    "i",
+    "em",
+    "strong",
+];
+
+/// SVG 中可以用的属性，没有 style 和事件
+const SVG_ATTRIBUTES: &[&str] = &[
+    "id",
+    "class",
+    "viewBox",
+    "preserveAspectRatio",
+    "width",
+    "height",
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "dx",
+    "dy",
+    "d",
+    "points",
+    "transform",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "stroke",
+    "stroke-width",
+    "stroke-opacity",
+    "stroke-dasharray",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "opacity",
+    "clip-path",
+    "marker-start",
+    "marker-mid",
+    "marker-end",
+    "markerWidth",
+    "markerHeight",
+    "markerUnits",
+    "refX",
+    "refY",
+    "orient",
+    "offset",
+    "stop-color",
+    "stop-opacity",
+    "gradientUnits",
+    "startOffset",
+    "text-anchor",
+    "dominant-baseline",
+    "alignment-baseline",
+    "font-family",
+    "font-size",
+    "font-weight",
+    "font-style",
+];
+
+/// 只要 <svg> 部分，按白名单清理，脚本、样式和动画都去掉
+fn check(svg: String) -> Result<String, String> {
+    let start = svg.find("<svg").ok_or("没有生成 SVG")?;
+    let svg = Builder::empty()
+        .tags(SVG_TAGS.iter().copied().collect())
+        .generic_attributes(SVG_ATTRIBUTES.iter().copied().collect())
+        .tag_attributes(HashMap::new())
+        .add_tag_attributes("textPath", ["href"])
+        .add_tag_attributes("use", ["href"])
+        .attribute_filter(svg_attribute)
+        .clean(&svg[start..])
+        .to_string();
+    if svg.starts_with("<svg") {
+        Ok(svg)
+    } else {
+        Err("没有生成 SVG".to_string())
+    }
+}
+
+/// 链接和 url() 只能指向图里的 id
+fn svg_attribute<'u>(_: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    let lower = value.to_ascii_lowercase();
+    let local = match attribute {
+        "href" => lower.starts_with('#'),
+        _ => lower.match_indices("url(").all(|(i, _)| {
+            lower[i + 4..]
+                .trim_start_matches([' ', '"', '\''])
+                .starts_with('#')
+        }),
+    };
+    if local {
+        Some(value.into())
+    } else {
+        None
+    }
+}
+
+/// 生成失败时显示代码和错误
+fn failed(lang: &str, code: &str, error: &str) -> String {
+    let mut html = format!("<pre><code class=\"hljs language-{}\">", lang);
+    escape(&mut html, code);
+    html.push_str("</code></pre><p class=\"diagram-error\">图没有生成：");
+    escape(&mut html, error);
+    html.push_str("</p>");
+    html
+}
@@ -0,0 +1,170 @@
+//! 站内内容的短代码
+//!
+//! `[[hu:123]]`、`[[book:45]]`、`[[xi:9]]` 显示成链接到内容页的标题卡片，
+//! `[[image:17 width=700]]` 显示图片库里的图片。引用的内容在渲染前从数据库查好放在 Embeds 里，
+//! 查不到的保留原文。代码里的短代码不处理。
+
+use std::collections::HashMap;
+
+use maud::html;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::config::WebConfig;
+use crate::fun::kind::Kind;
+
+/// 图片最大的宽度
+const MAX_WIDTH: u32 = 4096;
+
+/// 短代码引用的内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Content(Kind, i32),
+    Image(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcode {
+    pub target: Target,
+    pub width: Option<u32>,
+}
+
+/// 查到的内容
+#[derive(Debug, Clone)]
+pub enum Found {
+    Card { title: String, user_name: String },
+    Image { title: String, file: String },
+}
+
+#[derive(Debug, Default)]
+pub struct Embeds(HashMap<Target, Found>);
+
+impl Embeds {
+    pub fn insert(&mut self, target: Target, found: Found) {
+        self.0.insert(target, found);
+    }
+
+    pub fn get(&self, target: &Target) -> Option<&Found> {
+        self.0.get(target)
+    }
+}
+
+/// 一段文字按短代码分开
+pub enum Piece<'t> {
+    Text(&'t str),
+    Code(&'t str, Result<Shortcode, String>),
+}
+
+/// 文字中的短代码，[[ ]] 里不是 hu、book、xi、image 开头的当作普通文字
+pub fn split(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    let mut from = 0;
+    while let Some(start) = rest[from..].find("[[").map(|i| i + from) {
+        let end = match rest[start..].find("]]") {
+            Some(i) => start + i + 2,
+            None => break,
+        };
+        let raw = &rest[start..end];
+        match parse(&raw[2..raw.len() - 2]) {
+            Some(code) => {
+                if start > 0 {
+                    pieces.push(Piece::Text(&rest[..start]));
+                }
+                pieces.push(Piece::Code(raw, code));
+                rest = &rest[end..];
+                from = 0;
+            }
+            None => from = start + 1,
+        }
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    pieces
+}
+
+fn parse(inner: &str) -> Option<Result<Shortcode, String>> {
+    if inner.contains('\n') {
+        return None;
+    }
+    let (prefix, rest) = inner.split_once(':')?;
+    let kind = match prefix.trim() {
+        "image" => None,
+        prefix => Some(Kind::from_path(prefix)?),
+    };
+    let mut words = rest.split_whitespace();
+    let id = match words.next().and_then(|s| s.parse::<i32>().ok()) {
+        Some(id) if id > 0 => id,
+        _ => return Some(Err("编号不对".to_string())),
+    };
+    let mut width = None;
+    for word in words {
+        match (kind, word.split_once('=')) {
+            (None, Some(("width", n))) => match n.parse::<u32>() {
+                Ok(n) if (1..=MAX_WIDTH).contains(&n) => width = Some(n),
+                _ => return Some(Err(format!("宽度应该是 1 到 {}", MAX_WIDTH))),
+            },
+            _ => return Some(Err(format!("不认识的参数 {}", word))),
+        }
+    }
+    let target = match kind {
+        Some(kind) => Target::Content(kind, id),
+        None => Target::Image(id),
+    };
+    Some(Ok(Shortcode { target, width }))
+}
+
+/// Markdown 中所有的短代码，代码和代码块里的除外
+pub fn shortcodes(md: &str) -> Vec<(String, Result<Shortcode, String>)> {
+    let mut found = Vec::new();
+    let mut text = String::new();
+    let mut in_code = false;
+    for event in Parser::new_ext(md, super::options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Text(t) if !in_code => {
+                text.push_str(&t);
+                continue;
+            }
+            _ => {}
+        }
+        collect(&text, &mut found);
+        text.clear();
+    }
+    collect(&text, &mut found);
+    found
+}
+
+fn collect(text: &str, found: &mut Vec<(String, Result<Shortcode, String>)>) {
+    for piece in split(text) {
+        if let Piece::Code(raw, code) = piece {
+            found.push((raw.to_string(), code));
+        }
+    }
+}
+
+/// 查不到内容时的提示
+pub fn missing(target: &Target) -> String {
+    match target {
+        Target::Content(kind, _) => format!("找不到公布的{}", kind.name()),
+        Target::Image(_) => "找不到图片".to_string(),
+    }
+}
+
+pub fn render(config: &WebConfig, code: &Shortcode, found: &Found) -> String {
+    match (code.target, found) {
+        (Target::Content(kind, id), Found::Card { title, user_name }) => html! {
+            a class="embed-card" href=(kind.view_url(id)) {
+                span class="embed-kind" {(kind.name())}
+                span class="embed-title" {(title)}
+                span class="embed-author" {(user_name)}
+            }
+        },
+        (_, Found::Image { title, file }) => html! {
+            img class="embed-image" src={(config.image.public_url) "/" (file)} alt=(title) width=[code.width];
+        },
+        _ => html! {},
+    }
+    .into_string()
+}
@@ -0,0 +1,166 @@
+//! 代码高亮
+//!
+//! 用 syntect 的语法分析，输出 highlight.js 的 class，可以直接用 htdocs/css/hl 里的样式。
+
+use once_cell::sync::Lazy;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use super::escape;
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+/// 常用的写法，syntect 按扩展名和名字找不到的
+const ALIASES: &[(&str, &str)] = &[
+    ("shell", "bash"),
+    ("console", "bash"),
+    ("zsh", "bash"),
+    ("ts", "js"),
+    ("typescript", "js"),
+    ("jsx", "js"),
+    ("tsx", "js"),
+    ("vue", "html"),
+    ("golang", "go"),
+];
+
+/// syntect 的 scope 对应的 highlight.js class，按顺序找第一个
+const CLASSES: &[(&str, &str)] = &[
+    ("comment", "comment"),
+    ("string.regexp", "regexp"),
+    ("string", "string"),
+    ("constant.numeric", "number"),
+    ("constant.character", "string"),
+    ("constant.language", "literal"),
+    ("constant", "literal"),
+    ("keyword.operator", "operator"),
+    ("keyword", "keyword"),
+    ("storage.type.primitive", "type"),
+    ("storage", "keyword"),
+    ("support.type", "type"),
+    ("support.class", "type"),
+    ("support.function", "built_in"),
+    ("entity.name.tag", "name"),
+    ("entity.other.attribute-name", "attr"),
+    ("entity.name.section", "section"),
+    ("entity.other.inherited-class", "title"),
+    ("entity.name", "title"),
+    ("variable.function", "title"),
+    ("variable.parameter", "params"),
+    ("variable.language", "variable"),
+    ("meta.annotation", "meta"),
+    ("meta.attribute", "meta"),
+    ("meta.preprocessor", "meta"),
+    ("meta.decorator", "meta"),
+    ("markup.heading", "section"),
+    ("markup.bold", "strong"),
+    ("markup.italic", "emphasis"),
+    ("markup.inserted", "addition"),
+    ("markup.deleted", "deletion"),
+    ("markup.underline.link", "link"),
+    ("markup.quote", "quote"),
+    ("markup.raw", "code"),
+];
+
+/// 代码块，语言不认识时只转义
+pub fn highlight(lang: &str, code: &str) -> String {
+    // info 可以是 rust,ignore 或者 rust title=x
+    let lang: String = lang
+        .split([',', ' '])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '#'))
+        .collect();
+    let mut out = String::from("<pre><code class=\"hljs");
+    if !lang.is_empty() {
+        out.push_str(" language-");
+        out.push_str(&lang);
+    }
+    out.push_str("\">");
+    match find(&lang).and_then(|syntax| spans(syntax, code)) {
+        Some(html) => out.push_str(&html),
+        None => escape(&mut out, code),
+    }
+    out.push_str("</code></pre>");
+    out
+}
+
+fn find(lang: &str) -> Option<&'static SyntaxReference> {
+    if lang.is_empty() {
+        return None;
+    }
+    let lower = lang.to_ascii_lowercase();
+    let token = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == lower)
+        .map_or(lower.as_str(), |(_, to)| *to);
+    SYNTAXES.find_syntax_by_token(token)
+}
+
+fn spans(syntax: &SyntaxReference, code: &str) -> Option<String> {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut out = Spans::default();
+    for line in LinesWithEndings::from(code) {
+        let ops = state.parse_line(line, &SYNTAXES).ok()?;
+        let mut last = 0;
+        for (i, op) in ops {
+            out.push(&line[last..i], &stack);
+            stack.apply(&op).ok()?;
+            last = i;
+        }
+        out.push(&line[last..], &stack);
+    }
+    Some(out.finish())
+}
+
+/// 相邻的同一个 class 合并成一个 span
+#[derive(Default)]
+struct Spans {
+    html: String,
+    open: Option<&'static str>,
+}
+
+impl Spans {
+    fn push(&mut self, text: &str, stack: &ScopeStack) {
+        if text.is_empty() {
+            return;
+        }
+        let class = class(stack).filter(|_| !text.trim().is_empty());
+        if class != self.open {
+            self.close();
+            if let Some(class) = class {
+                self.html.push_str("<span class=\"hljs-");
+                self.html.push_str(class);
+                self.html.push_str("\">");
+                self.open = Some(class);
+            }
+        }
+        escape(&mut self.html, text);
+    }
+
+    fn close(&mut self) {
+        if self.open.take().is_some() {
+            self.html.push_str("</span>");
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.close();
+        self.html
+    }
+}
+
+/// 从里往外找第一个有对应 class 的 scope
+fn class(stack: &ScopeStack) -> Option<&'static str> {
+    stack.as_slice().iter().rev().find_map(|scope| {
+        let name = scope.build_string();
+        CLASSES
+            .iter()
+            .find(|(prefix, _)| {
+                name == *prefix
+                    || (name.starts_with(prefix) && name.as_bytes()[prefix.len()] == b'.')
+            })
+            .map(|(_, class)| *class)
+    })
+}
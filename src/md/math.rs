@@ -0,0 +1,28 @@
+//! 公式，用 KaTeX 转成 MathML
+
+use katex::{Opts, OutputType};
+
+use super::escape;
+
+/// 写错的公式原样显示，鼠标放上去显示错误
+pub fn render(tex: &str, display: bool) -> String {
+    let opts = Opts::builder()
+        .display_mode(display)
+        .output_type(OutputType::Mathml)
+        .throw_on_error(true)
+        .build();
+    let result = opts
+        .map_err(|e| e.to_string())
+        .and_then(|opts| katex::render_with_opts(tex, &opts).map_err(|e| e.to_string()));
+    match result {
+        Ok(html) => html,
+        Err(e) => {
+            let mut html = String::from("<code class=\"math-error\" title=\"");
+            escape(&mut html, &e);
+            html.push_str("\">");
+            escape(&mut html, tex);
+            html.push_str("</code>");
+            html
+        }
+    }
+}
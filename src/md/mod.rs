@@ -0,0 +1,412 @@
+//! Markdown 渲染
+//!
+//! 用户写的内容都经过这里：pulldown_cmark 转成 HTML，标题加上锚点，
+//! 按内容类型的白名单用 ammonia 清理，外部链接加上 rel="nofollow noopener"。
+//! 代码块高亮、公式转成 MathML、Mermaid 和 Graphviz 的图转成 SVG 都在服务器上做，
+//! 清理以后再放回去。脚注按引用的顺序放在最后。文章的标题够多时在前面加上目录。
+//! 文章和微博可以用短代码引用站内的内容，见 embed。
+
+mod clean;
+pub mod diagram;
+pub mod embed;
+mod highlight;
+mod math;
+
+use std::collections::HashSet;
+
+use maud::html;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+
+use crate::config::WebConfig;
+use diagram::Diagrams;
+use embed::{Embeds, Piece};
+
+/// 目录包含几级标题
+const TOC_DEPTH: usize = 3;
+
+/// 内容类型，决定可以用哪些 HTML 标签
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+    /// 文章、好书和网站的说明文档
+    Article,
+    /// 微博
+    Xi,
+    /// 评论和信件
+    Comment,
+}
+
+/// 渲染一段 Markdown，不加目录
+pub fn to_html(config: &WebConfig, content: Content, md: &str) -> String {
+    Markdown::new(config, content).html(md)
+}
+
+#[derive(Clone, Copy)]
+pub struct Markdown<'a> {
+    config: &'a WebConfig,
+    content: Content,
+    toc: bool,
+    embeds: Option<&'a Embeds>,
+    diagrams: Option<&'a Diagrams>,
+}
+
+impl<'a> Markdown<'a> {
+    pub fn new(config: &'a WebConfig, content: Content) -> Self {
+        Markdown {
+            config,
+            content,
+            toc: false,
+            embeds: None,
+            diagrams: None,
+        }
+    }
+
+    /// 标题不少于 markdown.toc_min 个时在前面加上目录
+    pub fn toc(mut self, toc: bool) -> Self {
+        self.toc = toc;
+        self
+    }
+
+    /// 替换短代码，没有设置时短代码保留原文
+    pub fn embeds(mut self, embeds: &'a Embeds) -> Self {
+        self.embeds = Some(embeds);
+        self
+    }
+
+    /// 用 diagram::prepare 生成好的 Mermaid 图，没有设置时按代码显示
+    pub fn diagrams(mut self, diagrams: &'a Diagrams) -> Self {
+        self.diagrams = Some(diagrams);
+        self
+    }
+
+    pub fn html(&self, md: &str) -> String {
+        // 用户写的占位符不算
+        let md = md.replace([BLOCK_START, BLOCK_END], "");
+        let mut collect = Collect::default();
+        let mut events = self.events(Parser::new_ext(&md, options()), &mut collect);
+        footnotes(&mut events, &mut collect);
+        let mut raw = String::new();
+        html::push_html(&mut raw, events.into_iter());
+        let body = clean::clean(self.config, self.content, &raw);
+        // 一次换完，块里（比如引用的标题）有占位符也不会再换
+        let body = PLACEHOLDER.replace_all(&body, |c: &regex::Captures| {
+            c[1].parse::<usize>()
+                .ok()
+                .and_then(|i| collect.blocks.get(i))
+                .cloned()
+                .unwrap_or_default()
+        });
+        let headings = &collect.headings;
+        if self.toc && !headings.is_empty() && headings.len() >= self.config.markdown.toc_min {
+            toc_html(headings) + &body
+        } else {
+            body.into_owned()
+        }
+    }
+
+    /// 标题加上 id 和锚点，脚注编号，代码、公式和图换成占位符，清理以后再换回来
+    fn events<'e>(
+        &self,
+        iter: impl Iterator<Item = Event<'e>>,
+        collect: &mut Collect<'e>,
+    ) -> Vec<Event<'e>> {
+        let config = &self.config.markdown;
+        let mut iter = iter.peekable();
+        let mut out = Vec::new();
+        while let Some(event) = iter.next() {
+            match event {
+                Event::Start(Tag::Heading {
+                    level,
+                    id,
+                    classes,
+                    attrs,
+                }) => {
+                    let mut inner = Vec::new();
+                    let mut text = String::new();
+                    for event in iter.by_ref() {
+                        match &event {
+                            Event::End(TagEnd::Heading(_)) => break,
+                            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) => {
+                                text.push_str(t)
+                            }
+                            _ => {}
+                        }
+                        inner.push(event);
+                    }
+                    // {#id} 写的 id 也只保留字母数字，加上 h- 免得和页面上的 id（#start 等）重复
+                    let id = format!("h-{}", slug(id.as_deref().unwrap_or(&text)));
+                    let id = unique(id, &mut collect.used);
+                    out.push(Event::Start(Tag::Heading {
+                        level,
+                        id: Some(id.clone().into()),
+                        classes,
+                        attrs,
+                    }));
+                    out.extend(self.events(inner.into_iter(), collect));
+                    out.push(Event::InlineHtml(
+                        format!(r##"<a class="anchor" href="#{}">#</a>"##, id).into(),
+                    ));
+                    out.push(Event::End(TagEnd::Heading(level)));
+                    collect.headings.push(Heading {
+                        level: level as usize,
+                        id,
+                        text,
+                    });
+                }
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let lang = match &kind {
+                        CodeBlockKind::Fenced(info) => info.to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    let mut code = Vec::new();
+                    for event in iter.by_ref() {
+                        if let Event::End(TagEnd::CodeBlock) = event {
+                            break;
+                        }
+                        code.push(event);
+                    }
+                    let text: String = code
+                        .iter()
+                        .filter_map(|e| match e {
+                            Event::Text(t) => Some(t.as_ref()),
+                            _ => None,
+                        })
+                        .collect();
+                    // Mermaid 要运行外部命令，评论和信件谁都能写，只生成 Graphviz 图
+                    let diagrams = match self.content {
+                        Content::Comment => None,
+                        _ => self.diagrams,
+                    };
+                    let diagram = diagram::render(config, diagrams, &lang, &text);
+                    let html = diagram.or_else(|| {
+                        if config.highlight {
+                            Some(highlight::highlight(&lang, &text))
+                        } else {
+                            None
+                        }
+                    });
+                    match html {
+                        Some(html) => out.push(collect.block(html)),
+                        None => {
+                            out.push(Event::Start(Tag::CodeBlock(kind)));
+                            out.extend(code);
+                            out.push(Event::End(TagEnd::CodeBlock));
+                        }
+                    }
+                }
+                Event::InlineMath(tex) if config.math => {
+                    out.push(collect.block(math::render(&tex, false)))
+                }
+                Event::DisplayMath(tex) if config.math => {
+                    out.push(collect.block(math::render(&tex, true)))
+                }
+                Event::FootnoteReference(name) => {
+                    let (n, first) = collect.footnote(&name);
+                    let id = slug(&name);
+                    let html = if first {
+                        format!(
+                            r##"<sup class="footnote-ref" id="fnref-{0}"><a href="#fn-{0}">{1}</a></sup>"##,
+                            id, n
+                        )
+                    } else {
+                        format!(
+                            r##"<sup class="footnote-ref"><a href="#fn-{}">{}</a></sup>"##,
+                            id, n
+                        )
+                    };
+                    out.push(Event::InlineHtml(html.into()));
+                }
+                Event::Start(Tag::FootnoteDefinition(name)) => {
+                    let mut inner = Vec::new();
+                    for event in iter.by_ref() {
+                        if let Event::End(TagEnd::FootnoteDefinition) = event {
+                            break;
+                        }
+                        inner.push(event);
+                    }
+                    let inner = self.events(inner.into_iter(), collect);
+                    collect.definitions.push((name.to_string(), inner));
+                }
+                Event::Text(text) => match self.embeds {
+                    Some(embeds) => {
+                        // [[ 和 ]] 会分成几段文字
+                        let mut text = text.into_string();
+                        while let Some(Event::Text(_)) = iter.peek() {
+                            if let Some(Event::Text(next)) = iter.next() {
+                                text.push_str(&next);
+                            }
+                        }
+                        self.shortcodes(embeds, &text, &mut out, collect);
+                    }
+                    None => out.push(Event::Text(text)),
+                },
+                event => out.push(event),
+            }
+        }
+        out
+    }
+
+    /// 查到的内容换成占位符，其他的保留原文
+    fn shortcodes<'e>(
+        &self,
+        embeds: &Embeds,
+        text: &str,
+        out: &mut Vec<Event<'e>>,
+        collect: &mut Collect<'e>,
+    ) {
+        for piece in embed::split(text) {
+            let (raw, code) = match piece {
+                Piece::Text(text) => (text, None),
+                Piece::Code(raw, code) => (raw, code.ok()),
+            };
+            let found = code
+                .as_ref()
+                .and_then(|c| Some((c, embeds.get(&c.target)?)));
+            match found {
+                Some((code, found)) => {
+                    out.push(collect.block(embed::render(self.config, code, found)))
+                }
+                None => out.push(Event::Text(raw.to_string().into())),
+            }
+        }
+    }
+}
+
+/// GFM 的脚注，定义写在哪里都放到最后
+fn options() -> Options {
+    let mut options = Options::all();
+    options.remove(Options::ENABLE_OLD_FOOTNOTES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options
+}
+
+/// 按引用的顺序把脚注放在最后，没有引用的不显示
+fn footnotes<'e>(out: &mut Vec<Event<'e>>, collect: &mut Collect<'e>) {
+    if collect.footnotes.is_empty() {
+        return;
+    }
+    out.push(Event::Html("<div class=\"footnotes\">\n<ol>\n".into()));
+    for name in &collect.footnotes {
+        let id = slug(name);
+        out.push(Event::Html(format!("<li id=\"fn-{}\">\n", id).into()));
+        if let Some(i) = collect.definitions.iter().position(|d| &d.0 == name) {
+            out.extend(collect.definitions.swap_remove(i).1);
+        }
+        out.push(Event::Html(
+            format!(
+                "<a class=\"footnote-backref\" href=\"#fnref-{}\">↩</a>\n</li>\n",
+                id
+            )
+            .into(),
+        ));
+    }
+    out.push(Event::Html("</ol>\n</div>\n".into()));
+}
+
+/// 渲染时记下的标题、脚注和清理以后再放回去的块
+#[derive(Default)]
+struct Collect<'e> {
+    headings: Vec<Heading>,
+    used: HashSet<String>,
+    blocks: Vec<String>,
+    /// 按第一次引用的顺序
+    footnotes: Vec<String>,
+    definitions: Vec<(String, Vec<Event<'e>>)>,
+}
+
+impl<'e> Collect<'e> {
+    /// 换成占位符
+    fn block(&mut self, html: String) -> Event<'e> {
+        let event = Event::InlineHtml(placeholder(self.blocks.len()).into());
+        self.blocks.push(html);
+        event
+    }
+
+    /// 脚注的编号和是不是第一次引用
+    fn footnote(&mut self, name: &str) -> (usize, bool) {
+        match self.footnotes.iter().position(|f| f == name) {
+            Some(i) => (i + 1, false),
+            None => {
+                self.footnotes.push(name.to_string());
+                (self.footnotes.len(), true)
+            }
+        }
+    }
+}
+
+struct Heading {
+    level: usize,
+    id: String,
+    text: String,
+}
+
+/// 占位符用私用区的字符，清理时当作文字保留
+const BLOCK_START: char = '\u{E000}';
+const BLOCK_END: char = '\u{E001}';
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new("\u{E000}([0-9]+)\u{E001}").unwrap());
+
+fn placeholder(i: usize) -> String {
+    format!("{}{}{}", BLOCK_START, i, BLOCK_END)
+}
+
+/// 标题的 id：小写的字母数字（包括中文），空白改成 -
+fn slug(text: &str) -> String {
+    let mut s = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' || c == '-' {
+            s.push(c);
+        } else if c.is_whitespace() && !s.ends_with('-') {
+            s.push('-');
+        }
+    }
+    let s = s.trim_matches('-');
+    if s.is_empty() {
+        "section".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// 重复的 id 后面加上 -1、-2
+fn unique(id: String, used: &mut HashSet<String>) -> String {
+    let mut n = 0;
+    let mut s = id.clone();
+    while !used.insert(s.clone()) {
+        n += 1;
+        s = format!("{}-{}", id, n);
+    }
+    s
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn toc_html(headings: &[Heading]) -> String {
+    let top = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    html! {
+        nav class="toc" {
+            details open {
+                summary {"目录"}
+                ul class="list-unstyled" {
+                    @for h in headings.iter().filter(|h| h.level < top + TOC_DEPTH) {
+                        li class={"toc-" (h.level - top + 1)} {
+                            a href={"#" (h.id)} {(h.text)}
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_string()
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::Client;
+use huxi_server::fun::user::db::db_set_role;
+use huxi_server::fun::user::Role;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+async fn api(db: &PgPool) -> Client {
+    let mut c = common::login(db).await;
+    let token = common::token(&mut c, "api").await;
+    Client::new(db.clone())
+        .await
+        .header("authorization", &format!("Bearer {}", token))
+}
+
+fn parse(body: &str) -> Value {
+    serde_json::from_str(body).unwrap_or_else(|e| panic!("{e}: {body}"))
+}
+
+fn input(title: &str) -> Value {
+    json!({
+        "title": title,
+        "i_public": 1,
+        "i_type": 1,
+        "i_category": 1,
+        "body": "body *md*",
+    })
+}
+
+/// 列表只有公布的，新建、修改、删除以后能看到结果
+#[sqlx::test(fixtures("users", "hu"))]
+async fn crud(db: PgPool) {
+    let mut api = api(&db).await;
+
+    let r = api.get("/api/v1/hu?size=2").await;
+    assert_eq!(r.status, StatusCode::OK, "{}", r.body);
+    let page = parse(&r.body);
+    assert_eq!(
+        (page["total"].as_i64(), page["size"].as_i64()),
+        (Some(3), Some(2))
+    );
+    assert_eq!(page["items"].as_array().unwrap().len(), 2);
+    let mine = parse(&api.get("/api/v1/hu?mine=true").await.body);
+    assert_eq!(mine["total"], 3);
+
+    let r = api.get("/api/v1/hu/1").await;
+    assert_eq!(r.status, StatusCode::OK, "{}", r.body);
+    assert_eq!(parse(&r.body)["title"], "it's a title");
+
+    let r = api
+        .json(Method::POST, "/api/v1/hu", Some(input("from api")))
+        .await;
+    assert_eq!(r.status, StatusCode::CREATED, "{}", r.body);
+    let hu = parse(&r.body);
+    let id = hu["id"].as_i64().unwrap();
+    assert_eq!(hu["user_id"], 1);
+    assert!(hu["html"].as_str().unwrap().contains("<em>md</em>"), "{hu}");
+
+    let r = api.json(Method::POST, "/api/v1/hu", Some(input(" "))).await;
+    assert_eq!(r.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(parse(&r.body)["messages"], json!(["请输入标题"]));
+
+    let uri = format!("/api/v1/hu/{}", id);
+    let r = api.json(Method::PUT, &uri, Some(input("changed"))).await;
+    assert_eq!(r.status, StatusCode::OK, "{}", r.body);
+    assert_eq!(parse(&r.body)["title"], "changed");
+    assert_eq!(parse(&api.get(&uri).await.body)["title"], "changed");
+
+    let r = api.json(Method::DELETE, &uri, None).await;
+    assert_eq!(r.status, StatusCode::NO_CONTENT, "{}", r.body);
+    assert_eq!(api.get(&uri).await.status, StatusCode::NOT_FOUND);
+}
+
+/// 没有的文章都是 404
+#[sqlx::test(fixtures("users", "hu"))]
+async fn not_found(db: PgPool) {
+    let mut api = api(&db).await;
+    for method in [Method::GET, Method::PUT, Method::DELETE] {
+        let body = (method == Method::PUT).then(|| input("none"));
+        let r = api.json(method.clone(), "/api/v1/hu/999", body).await;
+        assert_eq!(r.status, StatusCode::NOT_FOUND, "{method}");
+        assert_eq!(parse(&r.body)["error"], "not found", "{method}");
+    }
+}
+
+/// 没有登录不能写，没有权限的令牌不能新建，也不能改别人的文章
+#[sqlx::test(fixtures("users", "hu"))]
+async fn rejected_writes(db: PgPool) {
+    let mut anon = Client::new(db.clone()).await;
+    let r = anon
+        .json(Method::POST, "/api/v1/hu", Some(input("anon")))
+        .await;
+    assert_eq!(r.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        anon.get("/api/v1/hu?mine=true").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let mut api = api(&db).await;
+    let ctx = common::ctx(db.clone());
+    db_set_role(&ctx, 1, Role::Reader as i16).await.unwrap();
+
+    let r = api
+        .json(Method::POST, "/api/v1/hu", Some(input("reader")))
+        .await;
+    assert_eq!(r.status, StatusCode::FORBIDDEN, "{}", r.body);
+    assert_eq!(parse(&r.body)["error"], "forbidden");
+    let r = api
+        .json(Method::PUT, "/api/v1/hu/4", Some(input("taken")))
+        .await;
+    assert_eq!(r.status, StatusCode::FORBIDDEN, "{}", r.body);
+    let r = api.json(Method::DELETE, "/api/v1/hu/4", None).await;
+    assert_eq!(r.status, StatusCode::FORBIDDEN, "{}", r.body);
+    let r = api.json(Method::POST, "/api/v1/hu/1/good", None).await;
+    assert_eq!(r.status, StatusCode::FORBIDDEN, "{}", r.body);
+
+    let (title, n): (String, i64) =
+        sqlx::query_as("select (select title from hu where hu_id = 4), (select count(*) from hu)")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!((title.as_str(), n), ("snake_case", 4));
+}
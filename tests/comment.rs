@@ -0,0 +1,117 @@
+mod common;
+
+use common::Client;
+use huxi_server::fun::comment::PENDING;
+use huxi_server::fun::message::db::{NOTICE, SYSTEM_USER};
+use huxi_server::fun::user::db::db_set_role;
+use huxi_server::fun::user::Role;
+use sqlx::PgPool;
+
+/// 修改评论和发表评论一样检查，不能改成广告
+#[sqlx::test(fixtures("users", "hu"))]
+async fn edit_is_screened(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    db_set_role(&ctx, 1, Role::Reader as i16).await.unwrap();
+    // 别人的文章下已经公布的评论
+    let id: i32 = sqlx::query_scalar(
+        "insert into hu_comment (user_id, user_name, obj_id, i_public, body, html)
+         values (1, 'huxi', 4, 1, 'nice', '<p>nice</p>') returning id",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+
+    let mut client = Client::new(db.clone()).await;
+    let to = client
+        .post(
+            "/user/login",
+            &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+        )
+        .await
+        .location;
+    assert_eq!(to.as_deref(), Some("/my/hx"));
+
+    let uri = format!("/my/hu/comment/edit/{}", id);
+    let id = id.to_string();
+    let res = client
+        .post(
+            &uri,
+            &[("id", &id), ("url", "/hu/4"), ("body", "still nice")],
+        )
+        .await;
+    assert!(res.body.contains("修改成功"), "{}", res.body);
+    let i_public: i16 = sqlx::query_scalar("select i_public from hu_comment")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(i_public, 1);
+
+    let spam = "https://a.example https://b.example https://c.example https://d.example";
+    let res = client
+        .post(&uri, &[("id", &id), ("url", "/hu/4"), ("body", spam)])
+        .await;
+    assert!(res.body.contains("审核通过以后显示"), "{}", res.body);
+    let (i_public, html): (i16, String) = sqlx::query_as("select i_public, html from hu_comment")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(i_public, PENDING);
+    assert!(html.contains("d.example"), "{html}");
+}
+
+/// 评论的通知以网站的名义发，不在评论人的发信箱里，不能回复
+#[sqlx::test(fixtures("users", "hu"))]
+async fn notify_comes_from_the_site(db: PgPool) {
+    // 文章 4 的作者
+    sqlx::query(
+        "insert into users (name, email, password, i_role)
+         select 'other', 'other@example.com', password, 2 from users where name = 'huxi'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    let mut client = Client::new(db.clone()).await;
+    client
+        .post(
+            "/user/login",
+            &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+        )
+        .await;
+    client
+        .post(
+            "/my/hu/comment/add",
+            &[
+                ("oid", "4"),
+                ("url", "/hu/view/4/index.html"),
+                ("body", "nice"),
+            ],
+        )
+        .await;
+
+    // 通知在后台发送
+    let mut row = None;
+    for _ in 0..50 {
+        row = sqlx::query_as::<_, (uuid::Uuid, i32, String, i32, i16, i16)>(
+            "select id, user_id, user_name, to_user_id, i_type, out_public from message",
+        )
+        .fetch_optional(&db)
+        .await
+        .unwrap();
+        if row.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let (id, from, from_name, to, i_type, out_public) = row.expect("no notice");
+    assert_eq!((from, to), (SYSTEM_USER, 2));
+    assert_eq!(from_name, common::config().host.name);
+    assert_eq!((i_type, out_public), (NOTICE, 0));
+
+    let outbox = client.get("/my/outbox").await;
+    assert!(!outbox.body.contains("有新评论"), "{}", outbox.body);
+    // 评论人不能看也不能回复
+    let r = client
+        .post(&format!("/my/message/reply/{}", id), &[("body", "hi")])
+        .await;
+    assert!(!r.status.is_redirection(), "{:?}", r.location);
+}
@@ -0,0 +1,154 @@
+#![allow(dead_code)]
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use huxi_server::config::WebConfig;
+use huxi_server::http::{self, WebContext};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+pub fn config() -> WebConfig {
+    let mut config: WebConfig = toml::from_str(include_str!("../../examples/config.toml")).unwrap();
+    // 测试里发邮件立即失败
+    config.email.stmp_server = "localhost".to_string();
+    config
+}
+
+pub fn ctx(db: PgPool) -> WebContext {
+    WebContext::new(config(), db)
+}
+
+/// 会破坏拼接 SQL 的搜索词
+pub const HOSTILE: [&str; 8] = [
+    "it's",
+    "'",
+    "''",
+    "'; drop table hu; --",
+    "' or '1'='1",
+    "1) or (1=1",
+    "%_\\",
+    "\"; select pg_sleep(5); --",
+];
+
+pub struct Response {
+    pub status: StatusCode,
+    pub location: Option<String>,
+    pub body: String,
+}
+
+/// 带 cookie 的测试客户端
+pub struct Client {
+    app: Router,
+    cookies: Vec<String>,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl Client {
+    pub async fn new(db: PgPool) -> Self {
+        Client {
+            app: http::app(config(), db).await.unwrap(),
+            cookies: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// 和测试共用 ctx，可以看到访问统计等
+    pub async fn with_ctx(ctx: WebContext) -> Self {
+        Client {
+            app: http::router(ctx).await.unwrap(),
+            cookies: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// 每个请求都带上的 header
+    pub fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    pub async fn get(&mut self, uri: &str) -> Response {
+        self.send(Request::get(uri), Body::empty()).await
+    }
+
+    pub async fn post(&mut self, uri: &str, form: &[(&str, &str)]) -> Response {
+        let body = form
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let req =
+            Request::post(uri).header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        self.send(req, Body::from(body)).await
+    }
+
+    /// 接口请求，body 是 JSON
+    pub async fn json(
+        &mut self,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> Response {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+        self.send(req, body).await
+    }
+
+    async fn send(&mut self, mut req: axum::http::request::Builder, body: Body) -> Response {
+        if !self.cookies.is_empty() {
+            req = req.header(header::COOKIE, self.cookies.join("; "));
+        }
+        for (name, value) in &self.headers {
+            req = req.header(*name, value);
+        }
+        let res = self
+            .app
+            .clone()
+            .oneshot(req.body(body).unwrap())
+            .await
+            .unwrap();
+        for c in res.headers().get_all(header::SET_COOKIE) {
+            let pair = c.to_str().unwrap().split(';').next().unwrap().to_string();
+            let name = pair.split('=').next().unwrap().to_string();
+            self.cookies.retain(|x| !x.starts_with(&format!("{name}=")));
+            self.cookies.push(pair);
+        }
+        let status = res.status();
+        let location = res
+            .headers()
+            .get(header::LOCATION)
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        Response {
+            status,
+            location,
+            body: String::from_utf8_lossy(&bytes).to_string(),
+        }
+    }
+}
+
+/// 用 fixture 里的 huxi 登录
+pub async fn login(db: &PgPool) -> Client {
+    let mut c = Client::new(db.clone()).await;
+    c.post(
+        "/user/login",
+        &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+    )
+    .await;
+    c
+}
+
+/// 新建令牌，新令牌只在新建的页面上显示一次
+pub async fn token(c: &mut Client, name: &str) -> String {
+    let page = c.post("/my/tokens", &[("name", name)]).await;
+    let marker = "<code class=\"user-select-all\">";
+    let start = page.body.find(marker).expect("no token") + marker.len();
+    let end = start + page.body[start..].find('<').unwrap();
+    page.body[start..end].to_string()
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::*;
+use huxi_server::fun::embed;
+use sqlx::PgPool;
+
+#[sqlx::test(fixtures("users", "hu"))]
+async fn broken_shortcodes_are_reported_after_save(db: PgPool) {
+    let ctx = ctx(db.clone());
+    sqlx::query("insert into image (user_id, title, file) values (1, 'pic', 'a.jpg')")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    // 3 是草稿
+    let text = "[[hu:1]] [[hu:3]] [[xi:1]] [[image:1 width=99999]] [[hu:1]]";
+    let broken = embed::broken(&ctx, 1, &[text]).await.unwrap();
+    assert_eq!(
+        broken,
+        [
+            "[[image:1 width=99999]]：宽度应该是 1 到 4096",
+            "[[hu:3]]：找不到公布的文章",
+            "[[xi:1]]：找不到公布的微博",
+        ]
+    );
+    assert!(embed::broken(&ctx, 1, &["[[hu:2]] [[image:1]]"])
+        .await
+        .unwrap()
+        .is_empty());
+
+    let mut c = Client::new(db.clone()).await;
+    c.post(
+        "/user/login",
+        &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+    )
+    .await;
+    let form = |body| {
+        [
+            ("title", "embed"),
+            ("i_public", "1"),
+            ("i_type", "1"),
+            ("i_category", "1"),
+            ("body", body),
+        ]
+    };
+    // 照常保存，显示原文，提示有问题的引用
+    let r = c.post("/my/hu/add", &form("[[hu:99]]")).await;
+    assert!(r.body.contains("添加成功"), "{}", r.body);
+    assert!(r.body.contains("[[hu:99]]：找不到公布的文章"), "{}", r.body);
+    let html: String = sqlx::query_scalar("select html from hu where title = 'embed'")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(html, "<p>[[hu:99]]</p>\n");
+    sqlx::query("delete from hu where title = 'embed'")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let r = c
+        .post("/my/hu/add", &form("[[hu:2]]\n\n[[image:1 width=300]]"))
+        .await;
+    assert!(r.body.contains("添加成功"), "{}", r.body);
+    let html: String = sqlx::query_scalar("select html from hu where title = 'embed'")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert!(html.contains("href=\"/hu/view/2/index.html\""), "{html}");
+    assert!(
+        html.contains("/a.jpg\" alt=\"pic\" width=\"300\">"),
+        "{html}"
+    );
+}
+
+/// 只能引用自己上传的图片
+#[sqlx::test(fixtures("users", "hu"))]
+async fn images_of_other_users_are_broken(db: PgPool) {
+    let ctx = ctx(db.clone());
+    sqlx::query("insert into image (user_id, title, file) values (2, 'theirs', 'b.jpg')")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let broken = embed::broken(&ctx, 1, &["[[image:1]]"]).await.unwrap();
+    assert_eq!(broken, ["[[image:1]]：找不到图片"]);
+    assert!(embed::broken(&ctx, 2, &["[[image:1]]"])
+        .await
+        .unwrap()
+        .is_empty());
+
+    let mut c = Client::new(db.clone()).await;
+    c.post(
+        "/user/login",
+        &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+    )
+    .await;
+    let r = c
+        .post(
+            "/my/hu/add",
+            &[
+                ("title", "embed"),
+                ("i_public", "1"),
+                ("i_type", "1"),
+                ("i_category", "1"),
+                ("body", "[[image:1]]"),
+            ],
+        )
+        .await;
+    assert!(r.body.contains("[[image:1]]：找不到图片"), "{}", r.body);
+    let html: String = sqlx::query_scalar("select html from hu where title = 'embed'")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert!(!html.contains("b.jpg"), "{html}");
+}
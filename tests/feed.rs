@@ -0,0 +1,62 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::Client;
+use huxi_server::fun::kind::Kind;
+use sqlx::PgPool;
+
+/// 解析 Atom，返回每个 entry 的 id 和标题
+fn entries(xml: &str) -> Vec<(String, String)> {
+    let doc = roxmltree::Document::parse(xml).unwrap_or_else(|e| panic!("{e}: {xml}"));
+    let child = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let feed = doc.root_element();
+    assert!(feed.has_tag_name(("http://www.w3.org/2005/Atom", "feed")));
+    feed.children()
+        .filter(|n| n.has_tag_name("entry"))
+        .map(|e| (child(e, "id"), child(e, "title")))
+        .collect()
+}
+
+/// 订阅是合法的 XML，标题和内容里的特殊字符要转义，只有公布的内容
+#[sqlx::test(fixtures("users", "hu"))]
+async fn feeds_are_well_formed_and_public(db: PgPool) {
+    sqlx::query("update hu set title = $1, html = $2, brief_html = $3 where hu_id = 1")
+        .bind("a < b & \"c\"")
+        .bind("<p>x &amp; y</p><script>]]></script>")
+        .bind("")
+        .execute(&db)
+        .await
+        .unwrap();
+    let www = common::config().host.www;
+    let url = |id: i32| format!("{}{}", www, Kind::Hu.view_url(id));
+    let mut client = Client::new(db).await;
+
+    let r = client.get("/hu/feed.xml").await;
+    assert_eq!(r.status, StatusCode::OK);
+    let all = entries(&r.body);
+    let ids: Vec<&str> = all.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(ids, [url(4), url(2), url(1)]);
+    assert_eq!(all[2].1, "a < b & \"c\"");
+    assert!(!r.body.contains("draft"), "{}", r.body);
+
+    // 分类和作者的订阅也不包括草稿
+    let r = client.get("/hu/cat/typescript/feed.xml").await;
+    assert_eq!(entries(&r.body), [(url(1), "a < b & \"c\"".to_string())]);
+    let r = client.get("/user/1/feed.xml").await;
+    let ids: Vec<String> = entries(&r.body).into_iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, [url(2), url(1)]);
+
+    for uri in ["/xi/feed.xml", "/book/feed.xml"] {
+        assert!(entries(&client.get(uri).await.body).is_empty(), "{uri}");
+    }
+    assert_eq!(
+        client.get("/hu/cat/nope/feed.xml").await.status,
+        StatusCode::NOT_FOUND
+    );
+}
@@ -0,0 +1,5 @@
+insert into hu (user_id, user_name, i_public, i_type, i_category, title, body) values
+  (1, 'huxi', 1, 1, 1, 'it''s a title', 'body one'),
+  (1, 'huxi', 1, 1, 2, '100% sure', 'body two'),
+  (1, 'huxi', 0, 2, 1, 'draft', 'body three'),
+  (2, 'other', 1, 2, 2, 'snake_case', 'body four');
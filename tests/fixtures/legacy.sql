@@ -0,0 +1,51 @@
+-- 用旧的 pgsql/setup.sql 建好的数据库，book 还没有后来加的字段
+create or replace function set_updated_at()
+    returns trigger as
+$$
+begin
+    NEW.updated_at = now();
+    return NEW;
+end;
+$$ language plpgsql;
+
+CREATE TABLE book (
+  book_id serial PRIMARY KEY,
+  user_id integer NOT NULL,
+  user_name text not null,
+  i_public smallint not null DEFAULT 0,
+  i_type smallint not null,
+  i_category smallint not null,
+  i_good smallint not null default 0,
+  title text not null,
+  author text not null,
+  brief text,
+  brief_html text,
+  body text not null,
+  html text,
+  body2 text,
+  html2 text,
+  log text,
+  log_html text,
+  press text,
+  url text,
+  tags text,
+  click integer not null DEFAULT 0,
+  star integer not null DEFAULT 0,
+  good smallint not null DEFAULT 0,
+  created_at  timestamptz not null default now(),
+  updated_at timestamptz,
+  good_at timestamptz
+);
+CREATE TRIGGER set_updated_at
+    BEFORE UPDATE
+    ON book
+    FOR EACH ROW
+    WHEN (OLD is distinct from NEW)
+EXECUTE FUNCTION set_updated_at();
+
+alter table book add column
+  search_ti tsvector GENERATED ALWAYS AS (
+      to_tsvector('simple', coalesce(title, '') || coalesce(body, ''))) STORED;
+
+insert into book (user_id, user_name, i_public, i_type, i_category, title, author, body, tags) values
+  (1, 'huxi', 1, 1, 1, 'old book', 'someone', 'still here', '#Rust, rust；a b c d e f g h i j k');
@@ -0,0 +1,4 @@
+-- 密码 huxi1234
+insert into users (name, email, password, i_role) values
+  ('huxi', 'huxi@example.com',
+   '$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$8aJ0bDcPbpxms4Tt39xfc1IUBqPWbbMKTAr3ps5GeFM', 5);
@@ -0,0 +1,211 @@
+mod common;
+
+use huxi_server::db::filter::{escape_like, offset, Filter};
+use huxi_server::db::search::Backend;
+use huxi_server::fun::message::{self, db::ListBy as MessageBy};
+use huxi_server::fun::widget::list::{DbList, List, ListBy};
+use huxi_server::fun::{book, hu, search, xi};
+use huxi_server::http::WebContext;
+use sqlx::PgPool;
+
+fn total(db: &DbList) -> usize {
+    match db {
+        DbList::Hu(rows) => rows.len(),
+        DbList::Xi(rows) => rows.len(),
+        DbList::Book(rows) => rows.len(),
+    }
+}
+
+#[test]
+fn hostile_keys_never_reach_sql() {
+    for key in common::HOSTILE {
+        let filter = Filter::new("hu")
+            .eq("user_id", 1)
+            .web_search(Backend::Jieba, Some(key))
+            .title_like(Some(key));
+        let count = filter.count();
+        let select = filter.select("hu_id, title", "hu_id desc", 20, 0);
+        for sql in [count.sql(), select.sql()] {
+            assert!(!sql.contains(key), "{key:?} leaked into {sql}");
+            assert!(!sql.contains('\''), "quote in {sql}");
+        }
+        assert_eq!(
+            count.sql(),
+            "select count(*) as total from hu where user_id=$1 \
+             and search_ti @@ websearch_to_tsquery($2::regconfig, $3) and lower(title) like $4"
+        );
+        assert!(select
+            .sql()
+            .ends_with("order by hu_id desc limit $5 offset $6"));
+    }
+}
+
+#[test]
+fn empty_keys_are_ignored() {
+    let filter = Filter::new("xi")
+        .eq_opt("i_type", None::<u8>)
+        .web_search(Backend::Jieba, Some("  "))
+        .title_like(None);
+    assert_eq!(filter.count().sql(), "select count(*) as total from xi");
+}
+
+#[test]
+fn like_wildcards_are_escaped() {
+    assert_eq!(escape_like("100%"), "100\\%");
+    assert_eq!(escape_like("a_b\\c"), "a\\_b\\\\c");
+    assert_eq!(escape_like("it's"), "it's");
+}
+
+#[test]
+fn offset_starts_at_page_one() {
+    assert_eq!(offset(1, 20), 0);
+    assert_eq!(offset(3, 20), 40);
+    assert_eq!(offset(0, 20), 0);
+}
+
+#[sqlx::test(fixtures("hu"))]
+async fn hostile_keys_run_against_db(db: PgPool) {
+    let ctx = common::ctx(db);
+    for key in common::HOSTILE {
+        let list = List::new(&ctx, ListBy::AllPublic, 1)
+            .web_search_key(Some(key))
+            .title_search_key(Some(key));
+        hu::db::db_list(&list).await.unwrap();
+    }
+    let list = List::new(&ctx, ListBy::All, 1);
+    let (total, _) = hu::db::db_list(&list).await.unwrap();
+    assert_eq!(total, 4);
+}
+
+#[sqlx::test(fixtures("hu"))]
+async fn title_search_matches_literally(db: PgPool) {
+    let ctx = common::ctx(db);
+
+    let list = List::new(&ctx, ListBy::AllPublic, 1).title_search_key(Some("it's"));
+    let (total, rows) = hu::db::db_list(&list).await.unwrap();
+    assert_eq!((total, self::total(&rows)), (1, 1));
+
+    let list = List::new(&ctx, ListBy::AllPublic, 1).title_search_key(Some("%"));
+    let (total, _) = hu::db::db_list(&list).await.unwrap();
+    assert_eq!(total, 1);
+
+    let list = List::new(&ctx, ListBy::AllPublic, 1).title_search_key(Some("_"));
+    let (total, _) = hu::db::db_list(&list).await.unwrap();
+    assert_eq!(total, 1);
+}
+
+#[sqlx::test(fixtures("hu"))]
+async fn filters_and_paging_are_bound(db: PgPool) {
+    let ctx = common::ctx(db);
+
+    let list = List::new(&ctx, ListBy::AllPublic, 1).cat(Some(1));
+    assert_eq!(hu::db::db_list(&list).await.unwrap().0, 1);
+
+    let list = List::new(&ctx, ListBy::UserId(1), 1).i_type(Some(1));
+    assert_eq!(hu::db::db_list(&list).await.unwrap().0, 2);
+
+    let list = List::new(&ctx, ListBy::AllPublic, 2).size(2);
+    let (total, rows) = hu::db::db_list(&list).await.unwrap();
+    assert_eq!((total, self::total(&rows)), (3, 1));
+}
+
+#[sqlx::test(fixtures("hu"))]
+async fn every_search_backend_works(db: PgPool) {
+    for backend in [
+        Backend::Jieba,
+        Backend::Simple,
+        Backend::English,
+        Backend::Trgm,
+    ] {
+        let mut config = common::config();
+        config.search.backend = backend;
+        let ctx = WebContext::new(config, db.clone());
+
+        let list = List::new(&ctx, ListBy::AllPublic, 1).web_search_key(Some("body"));
+        let (total, _) = hu::db::db_list(&list).await.unwrap();
+        assert_eq!(total, 3, "{backend:?}");
+
+        let hits = search::db::db_search(&ctx, "body", None, Some("hu"), None, 20, 0)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 3, "{backend:?}");
+        assert!(hits.iter().all(|h| h
+            .snippet
+            .as_deref()
+            .unwrap()
+            .contains(search::db::START_SEL)));
+
+        for key in common::HOSTILE {
+            let list = List::new(&ctx, ListBy::AllPublic, 1).web_search_key(Some(key));
+            hu::db::db_list(&list).await.unwrap();
+            search::db::db_facets(&ctx, key, Some(1)).await.unwrap();
+        }
+    }
+}
+
+/// 微博、好书和信件的列表也按字面匹配标题，搜索词不会进 SQL
+#[sqlx::test(fixtures("hu"))]
+async fn other_lists_match_literally(db: PgPool) {
+    for title in ["it's a title", "100% sure", "snake"] {
+        sqlx::query(
+            "insert into xi (user_id, user_name, i_public, i_category, title, body, html)
+             values (1, 'huxi', 1, 1, $1, 'body', '')",
+        )
+        .bind(title)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into book (user_id, user_name, i_public, i_type, i_category, title, author, body)
+             values (1, 'huxi', 1, 1, 1, $1, 'author', 'body')",
+        )
+        .bind(title)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "insert into message (user_id, user_name, to_user_id, to_user_name, title, body, html)
+             values (2, 'other', 1, 'huxi', $1, 'body', '')",
+        )
+        .bind(title)
+        .execute(&db)
+        .await
+        .unwrap();
+    }
+    let ctx = common::ctx(db);
+
+    for (key, expected) in [("it's", 1), ("%", 1), ("_", 0)] {
+        let list = List::new(&ctx, ListBy::AllPublic, 1).title_search_key(Some(key));
+        let (total, rows) = xi::db::db_list(&list).await.unwrap();
+        assert_eq!(
+            (total, self::total(&rows)),
+            (expected, expected as usize),
+            "{key}"
+        );
+        let (total, rows) = book::db::db_list(&list).await.unwrap();
+        assert_eq!(
+            (total, self::total(&rows)),
+            (expected, expected as usize),
+            "{key}"
+        );
+
+        let list = message::List::new(&ctx, MessageBy::UserInbox(1), 1)
+            .size(20)
+            .title_search_key(Some(key));
+        let (total, rows) = message::db::db_list(&list).await.unwrap();
+        assert_eq!((total, rows.len()), (expected, expected as usize), "{key}");
+    }
+
+    for key in common::HOSTILE {
+        let list = List::new(&ctx, ListBy::AllPublic, 1)
+            .web_search_key(Some(key))
+            .title_search_key(Some(key));
+        xi::db::db_list(&list).await.unwrap();
+        book::db::db_list(&list).await.unwrap();
+        let list =
+            message::List::new(&ctx, MessageBy::UserOutbox(2), 1).title_search_key(Some(key));
+        message::db::db_list(&list).await.unwrap();
+    }
+    let list = message::List::new(&ctx, MessageBy::UserOutbox(2), 1).size(20);
+    assert_eq!(message::db::db_list(&list).await.unwrap().0, 3);
+}
@@ -0,0 +1,315 @@
+mod common;
+
+use huxi_server::fun::kind::Kind;
+use huxi_server::md::embed::{self, Embeds, Found, Target};
+use huxi_server::md::{self, Content, Markdown};
+
+const XSS: [&str; 6] = [
+    "<script>alert(1)</script>",
+    "<img src=x onerror=alert(1)>",
+    "[x](javascript:alert(1))",
+    "<a href=\"javascript:alert(1)\">x</a>",
+    "<iframe src=\"https://evil.example\"></iframe>",
+    "<div style=\"position:fixed\" onclick=\"alert(1)\">x</div>",
+];
+
+#[test]
+fn html_is_always_sanitized() {
+    let config = common::config();
+    for content in [Content::Article, Content::Xi, Content::Comment] {
+        for src in XSS {
+            let html = md::to_html(&config, content, src);
+            for bad in [
+                "<script",
+                "onerror",
+                "javascript:",
+                "<iframe",
+                "style=",
+                "onclick",
+            ] {
+                assert!(!html.contains(bad), "{content:?} {src:?} => {html}");
+            }
+        }
+    }
+}
+
+#[test]
+fn allow_list_per_content() {
+    let mut config = common::config();
+    let src = "![a](/a.png)\n\n| a | b |\n|---|---|\n| 1 | 2 |";
+    let article = md::to_html(&config, Content::Article, src);
+    assert!(
+        article.contains("<img") && article.contains("<table"),
+        "{article}"
+    );
+    let comment = md::to_html(&config, Content::Comment, src);
+    assert!(
+        !comment.contains("<img") && !comment.contains("<table"),
+        "{comment}"
+    );
+
+    config.markdown.comment_tags = Some(vec!["img".to_string(), "script".to_string()]);
+    let comment = md::to_html(&config, Content::Comment, "![a](/a.png)<script>x</script>");
+    assert!(
+        comment.contains("<img") && !comment.contains("<script"),
+        "{comment}"
+    );
+}
+
+#[test]
+fn headings_toc_and_links() {
+    let config = common::config();
+    let src = "# Hello World\n\n## 安装 Rust\n\n## 安装 Rust\n\n### `cargo` 命令\n\n\
+               [out](https://example.com/a) [in](https://www.huxi.fun/hu) [rel](/hu/view/1/index.html)";
+    let html = Markdown::new(&config, Content::Article).toc(true).html(src);
+    assert!(html.starts_with("<nav class=\"toc\">"), "{html}");
+    assert!(html.contains("<h1 id=\"h-hello-world\">"), "{html}");
+    assert!(html.contains("<h2 id=\"h-安装-rust\">"), "{html}");
+    assert!(html.contains("<h2 id=\"h-安装-rust-1\">"), "{html}");
+    assert!(html.contains("href=\"#h-cargo-命令\""), "{html}");
+    assert!(
+        html.contains("<a class=\"anchor\" href=\"#h-hello-world\">"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<a href=\"https://example.com/a\" rel=\"nofollow noopener\">"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<a href=\"https://www.huxi.fun/hu\">"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<a href=\"/hu/view/1/index.html\">"),
+        "{html}"
+    );
+
+    // 标题太少、不要目录时都没有目录
+    let html = Markdown::new(&config, Content::Article)
+        .toc(true)
+        .html("# a\n\n# b");
+    assert!(!html.contains("toc"), "{html}");
+    assert!(!md::to_html(&config, Content::Article, src).contains("<nav"));
+
+    // 不会和页面上的 #start 重复
+    let html = md::to_html(&config, Content::Article, "# start {#start}");
+    assert!(html.contains("<h1 id=\"h-start\">"), "{html}");
+}
+
+#[test]
+fn code_is_highlighted_on_server() {
+    let mut config = common::config();
+    let src = "```rust\nfn main() { println!(\"<b>\"); }\n```";
+    // 评论没有 span 也有高亮
+    let html = md::to_html(&config, Content::Comment, src);
+    assert!(
+        html.starts_with("<pre><code class=\"hljs language-rust\">"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<span class=\"hljs-keyword\">fn</span>"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<span class=\"hljs-string\">&quot;&lt;b&gt;&quot;</span>"),
+        "{html}"
+    );
+
+    // 语言不认识、写了奇怪的 info 时只转义
+    let html = md::to_html(&config, Content::Article, "```x\"><script>\n<script>\n```");
+    assert!(!html.contains("<script"), "{html}");
+
+    config.markdown.highlight = false;
+    let html = md::to_html(&config, Content::Article, src);
+    assert!(
+        html.contains("<code class=\"language-rust\">") && !html.contains("hljs"),
+        "{html}"
+    );
+}
+
+#[test]
+fn math_diagrams_and_footnotes() {
+    let mut config = common::config();
+    let html = md::to_html(&config, Content::Comment, "$e^{i\\pi}$\n\n$$\\frac12$$");
+    assert!(
+        html.contains("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">"),
+        "{html}"
+    );
+    assert!(html.contains("display=\"block\""), "{html}");
+
+    let dot = "```dot\ndigraph { a -> b [label=\"<script>\"]; }\n```";
+    let html = md::to_html(&config, Content::Article, dot);
+    assert!(html.starts_with("<div class=\"diagram\"><svg"), "{html}");
+    assert!(
+        !html.contains("<script") && html.contains("&lt;script&gt;"),
+        "{html}"
+    );
+    // 评论也生成 Graphviz 图
+    let html = md::to_html(&config, Content::Comment, dot);
+    assert!(html.starts_with("<div class=\"diagram\"><svg"), "{html}");
+    let html = md::to_html(&config, Content::Article, "```dot\nnot a graph\n```");
+    assert!(html.contains("class=\"diagram-error\""), "{html}");
+
+    // 没有配置 Mermaid 命令时按代码显示
+    config.markdown.mermaid = String::new();
+    let html = md::to_html(
+        &config,
+        Content::Article,
+        "```mermaid\ngraph TD; A-->B\n```",
+    );
+    assert!(
+        html.contains("language-mermaid") && !html.contains("<svg"),
+        "{html}"
+    );
+
+    // 命令不存在时显示错误，评论里的 mermaid 代码块不会执行命令
+    config.markdown.mermaid = "huxi-no-such-mmdc".to_string();
+    let src = "```mermaid\ngraph TD; A-->B\n```";
+    let diagrams = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(md::diagram::prepare(&config.markdown, &[src]));
+    let html = Markdown::new(&config, Content::Article)
+        .diagrams(&diagrams)
+        .html(src);
+    assert!(html.contains("class=\"diagram-error\""), "{html}");
+    let html = Markdown::new(&config, Content::Comment)
+        .diagrams(&diagrams)
+        .html(src);
+    assert!(
+        html.contains("language-mermaid") && !html.contains("diagram-error"),
+        "{html}"
+    );
+
+    let src = "a[^x] b[^y] c[^x]\n\n[^y]: second\n[^x]: first\n[^z]: unused";
+    let html = md::to_html(&config, Content::Article, src);
+    assert!(
+        html.contains("<sup class=\"footnote-ref\" id=\"fnref-x\"><a href=\"#fn-x\">1</a></sup>"),
+        "{html}"
+    );
+    assert!(html.contains("<a href=\"#fn-y\">2</a>"), "{html}");
+    let first = html.find("<li id=\"fn-x\">").unwrap();
+    let second = html.find("<li id=\"fn-y\">").unwrap();
+    assert!(first < second && !html.contains("unused"), "{html}");
+    assert!(html.contains("href=\"#fnref-x\""), "{html}");
+}
+
+/// 假的 mmdc，输出带脚本的 SVG
+#[cfg(unix)]
+#[test]
+fn mermaid_svg_is_sanitized() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let svg = r##"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><style>body{display:none}</style><script>alert(1)</script><a href="&#106;avascript:alert(1)"><text>a</text></a><set attributeName="href" to="javascript:alert(1)"/><animate attributeName="href" values="javascript:alert(1)"/><image href="https://evil.example/x.png"/><rect width="5" height="5" fill="url(https://evil.example/x)" onclick="alert(1)" style="position:fixed"/><path d="M0 0" fill="url(#ok)" marker-end="url( '#m')"/><use href="https://evil.example/x.svg#a"/><foreignObject><div onmouseover="alert(1)"><iframe src="https://evil.example"></iframe><span>label</span></div></foreignObject></svg>"##;
+    let script = std::env::temp_dir().join(format!("huxi-mmdc-{}.sh", std::process::id()));
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do [ \"$1\" = -o ] && out=$2; shift; done\ncat > \"$out\" <<'EOF'\n{}\nEOF\n",
+            svg
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = common::config();
+    config.markdown.mermaid = script.display().to_string();
+    let src = "```mermaid\ngraph TD; A-->B\n```";
+    let diagrams = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(md::diagram::prepare(&config.markdown, &[src]));
+    let html = Markdown::new(&config, Content::Article)
+        .diagrams(&diagrams)
+        .html(src);
+    std::fs::remove_file(&script).unwrap();
+
+    assert!(html.starts_with("<div class=\"diagram\"><svg"), "{html}");
+    let lower = html.to_ascii_lowercase();
+    for bad in [
+        "<style",
+        "<script",
+        "<a ",
+        "<set",
+        "<animate",
+        "<image",
+        "<iframe",
+        "javascript",
+        "evil",
+        "onclick",
+        "onmouseover",
+        "style=",
+    ] {
+        assert!(!lower.contains(bad), "{bad}: {html}");
+    }
+    assert!(html.contains("fill=\"url(#ok)\""), "{html}");
+    assert!(html.contains("<span>label</span>"), "{html}");
+}
+
+#[test]
+fn shortcodes_render_from_embeds() {
+    let config = common::config();
+    let mut embeds = Embeds::default();
+    embeds.insert(
+        Target::Content(Kind::Hu, 1),
+        Found::Card {
+            title: "<b>title</b>".to_string(),
+            user_name: "huxi".to_string(),
+        },
+    );
+    embeds.insert(
+        Target::Image(17),
+        Found::Image {
+            title: "pic".to_string(),
+            file: "2024/a.jpg".to_string(),
+        },
+    );
+    let src = "see [[hu:1]] and [[image:17 width=700]], [[hu:2]] `[[hu:1]]` [[wiki]]";
+    let html = Markdown::new(&config, Content::Xi)
+        .embeds(&embeds)
+        .html(src);
+    assert!(
+        html.contains("<a class=\"embed-card\" href=\"/hu/view/1/index.html\"><span class=\"embed-kind\">文章</span><span class=\"embed-title\">&lt;b&gt;title&lt;/b&gt;</span>"),
+        "{html}"
+    );
+    let img = format!(
+        "<img class=\"embed-image\" src=\"{}/2024/a.jpg\" alt=\"pic\" width=\"700\">",
+        config.image.public_url
+    );
+    assert!(html.contains(&img), "{html}");
+    assert!(
+        html.contains(", [[hu:2]] <code>[[hu:1]]</code> [[wiki]]"),
+        "{html}"
+    );
+
+    // 标题里的占位符不会换成别的块
+    let mut embeds = Embeds::default();
+    embeds.insert(
+        Target::Content(Kind::Hu, 1),
+        Found::Card {
+            title: "x\u{E000}1\u{E001}".to_string(),
+            user_name: "huxi".to_string(),
+        },
+    );
+    let html = Markdown::new(&config, Content::Article)
+        .embeds(&embeds)
+        .html("[[hu:1]]\n\n```rust\nlet a = 1;\n```");
+    assert_eq!(html.matches("language-rust").count(), 1, "{html}");
+    assert!(!html.contains("x<pre"), "{html}");
+
+    // 评论不处理短代码
+    let html = md::to_html(&config, Content::Comment, "[[hu:1]]");
+    assert_eq!(html, "<p>[[hu:1]]</p>\n");
+
+    let codes: Vec<_> =
+        embed::shortcodes("[[xi:9]]\n\n```\n[[hu:1]]\n```\n[[book:x]] [[image:3 height=2]]")
+            .into_iter()
+            .map(|(raw, code)| (raw, code.is_ok()))
+            .collect();
+    assert_eq!(
+        codes,
+        [
+            ("[[xi:9]]".to_string(), true),
+            ("[[book:x]]".to_string(), false),
+            ("[[image:3 height=2]]".to_string(), false),
+        ]
+    );
+}
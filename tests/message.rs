@@ -0,0 +1,62 @@
+mod common;
+
+use huxi_server::fun::message::db::*;
+use huxi_server::http::WebContext;
+use sqlx::PgPool;
+
+async fn public(ctx: &WebContext, id: &str) -> (i16, i16) {
+    let m = db_get_one(ctx, id).await.unwrap();
+    (m.in_public, m.out_public)
+}
+
+/// 回复让删掉这封信的一方重新看到它，只算对方的回复为未读
+#[sqlx::test(fixtures("users"))]
+async fn reply_restores_message_and_counts_unread(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let input = Input {
+        title: "hello".to_string(),
+        to_user_name: "other".to_string(),
+        body: "first".to_string(),
+    };
+    let uid = db_insert(&ctx, 1, "huxi", &input, 2).await.unwrap();
+    let id = uid.to_string();
+
+    // 收信人删除以后，发信人回复
+    assert!(db_rm(&ctx, &id).await.unwrap());
+    assert_eq!(public(&ctx, &id).await, (0, 1));
+    let sm = db_get_one(&ctx, &id).await.unwrap();
+    db_reply_insert(&ctx, &sm, 1, "huxi", "again")
+        .await
+        .unwrap();
+    assert_eq!(public(&ctx, &id).await, (1, 1));
+    assert_eq!(db_new_reply_total(&ctx, 2).await.unwrap(), 1);
+    assert_eq!(db_new_reply_total(&ctx, 1).await.unwrap(), 0);
+    let unread = db_unread_replies(&ctx, 2, &[uid]).await.unwrap();
+    assert_eq!(unread.get(&uid), Some(&1));
+    assert!(db_unread_replies(&ctx, 1, &[uid]).await.unwrap().is_empty());
+
+    // 发信人从发信箱删除以后，收信人回复
+    sqlx::query("update message set out_public = 0 where id = $1")
+        .bind(uid)
+        .execute(&db)
+        .await
+        .unwrap();
+    let sm = db_get_one(&ctx, &id).await.unwrap();
+    db_reply_insert(&ctx, &sm, 2, "other", "reply")
+        .await
+        .unwrap();
+    assert_eq!(public(&ctx, &id).await, (1, 1));
+    assert_eq!(db_new_reply_total(&ctx, 1).await.unwrap(), 1);
+
+    // 读过以后只清掉对方的回复
+    assert_eq!(db_replies_read(&ctx, uid, 2).await.unwrap(), 1);
+    assert_eq!(db_new_reply_total(&ctx, 2).await.unwrap(), 0);
+    assert_eq!(db_new_reply_total(&ctx, 1).await.unwrap(), 1);
+    let bodies: Vec<String> = db_replies(&ctx, uid)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.body)
+        .collect();
+    assert_eq!(bodies, ["again", "reply"]);
+}
@@ -0,0 +1,81 @@
+use huxi_server::db::{self, MIGRATOR};
+use huxi_server::fun::tag;
+use sqlx::PgPool;
+
+async fn tables(pool: &PgPool) -> Vec<String> {
+    sqlx::query_scalar(
+        "select table_name::text from information_schema.tables
+         where table_schema = 'public' order by 1",
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn fresh_database(pool: PgPool) {
+    let all: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    assert_eq!(db::migrate(&pool).await.unwrap(), all);
+    // 第二次没有要执行的
+    assert!(db::migrate(&pool).await.unwrap().is_empty());
+
+    let tables = tables(&pool).await;
+    for t in ["api_token", "book", "hu", "image", "message", "users", "xi"] {
+        assert!(tables.iter().any(|x| x == t), "missing table {}", t);
+    }
+
+    sqlx::query(
+        "insert into book (user_id, user_name, i_type, i_category, title, author, body, version, price)
+         values (1, 'huxi', 1, 1, 't', 'a', 'b', '2', '10')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+}
+
+#[sqlx::test(migrations = false, fixtures("legacy"))]
+async fn legacy_database(pool: PgPool) {
+    db::migrate(&pool).await.unwrap();
+
+    let (title, version): (String, Option<String>) =
+        sqlx::query_as("select title, version from book")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(title, "old book");
+    assert_eq!(version, None);
+
+    // 原来的触发器还在
+    let updated: Option<chrono::DateTime<chrono::Utc>> =
+        sqlx::query_scalar("update book set price='9' returning updated_at")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(updated.is_some());
+
+    // 已有的标签拆开，和 parse_tags 一样去重，最多 MAX_TAGS 个
+    let names: Vec<String> = sqlx::query_scalar(
+        "select t.name from tagging g join tag t on t.id = g.tag_id
+         where g.kind = 'book' order by t.name",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let mut expected = tag::parse_tags("#Rust, rust；a b c d e f g h i j k");
+    expected.sort();
+    assert_eq!(expected.len(), tag::MAX_TAGS);
+    assert_eq!(names, expected);
+}
+
+#[sqlx::test(migrations = false)]
+async fn rerun_init(pool: PgPool) {
+    // 没有记录在 _sqlx_migrations 里的手工导入，再迁移也不会出错
+    for m in MIGRATOR.iter() {
+        sqlx::raw_sql(&m.sql).execute(&pool).await.unwrap();
+    }
+    assert_eq!(
+        db::migrate(&pool).await.unwrap().len(),
+        MIGRATOR.iter().count()
+    );
+    assert!(tables(&pool).await.iter().any(|t| t == "_sqlx_migrations"));
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::*;
+use sqlx::PgPool;
+
+/// 取消定时发布以后是草稿，只回到本站的页面
+#[sqlx::test(fixtures("users", "hu"))]
+async fn cancel_scheduled(db: PgPool) {
+    sqlx::query(
+        "update hu set i_public = 2, publish_at = now() + interval '1 day' where hu_id = 1",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    let mut c = Client::new(db.clone()).await;
+    c.post(
+        "/user/login",
+        &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+    )
+    .await;
+    let r = c.get("/my/publish/cancel/hu/1?url=/%5Cevil.com").await;
+    assert_eq!(r.location.as_deref(), Some("/my/hu"));
+    let i_public: i16 = sqlx::query_scalar("select i_public from hu where hu_id = 1")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(i_public, 0);
+
+    let r = c.get("/my/publish/cancel/hu/1?url=/my/hu%3Fpage%3D2").await;
+    assert_eq!(r.location.as_deref(), Some("/my/hu?page=2"));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use huxi_server::fun::rerender::{self, db, db::TABLES};
+use sqlx::PgPool;
+
+async fn hu_html(db: &PgPool) -> Vec<(i32, Option<String>, Option<DateTime<Utc>>)> {
+    sqlx::query_as("select hu_id, html, updated_at from hu order by hu_id")
+        .fetch_all(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("users", "hu"))]
+async fn rerender_fills_html_and_keeps_updated_at(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    sqlx::query("update hu set html = '<p>old</p>' where title = 'draft'")
+        .execute(&db)
+        .await
+        .unwrap();
+    let before = hu_html(&db).await;
+    let tables: Vec<_> = TABLES.iter().collect();
+
+    // dry run 只报告，每批一条也能走完
+    let mut batches = 0;
+    let report = rerender::run(&ctx, &tables, true, 1, |_| batches += 1)
+        .await
+        .unwrap();
+    assert!(batches > before.len());
+    let hu = report.tables.iter().find(|t| t.table == "hu").unwrap();
+    assert_eq!((hu.total, hu.scanned), (4, 4));
+    assert_eq!(hu.changed.len(), 4);
+    assert_eq!(hu.changed[0].columns, ["html"]);
+    assert_eq!(hu_html(&db).await, before);
+
+    let report = rerender::run(&ctx, &tables, false, 2, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.changed(), 4);
+    for ((id, html, updated), (_, _, old)) in hu_html(&db).await.into_iter().zip(before) {
+        let html = html.unwrap();
+        assert!(html.starts_with("<p>body "), "{id}: {html}");
+        assert_eq!(updated, old);
+    }
+
+    // 再运行没有变化
+    let report = rerender::run(&ctx, &tables, true, 200, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.changed(), 0);
+    assert!(rerender::tables(&["nope".to_string()]).is_err());
+}
+
+/// 读出以后又修改了 Markdown 的记录不能被旧的 HTML 覆盖
+#[sqlx::test(fixtures("users", "hu"))]
+async fn rerender_skips_rows_modified_meanwhile(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let table = TABLES.iter().find(|t| t.name == "hu").unwrap();
+    let records = db::db_batch(&ctx, table, None, 10).await.unwrap();
+    sqlx::query("update hu set body = 'edited', html = '<p>edited</p>' where hu_id = 1")
+        .execute(&db)
+        .await
+        .unwrap();
+    let updates: Vec<_> = records
+        .iter()
+        .map(|r| (r, vec![Some("<p>new</p>".to_string()); table.columns.len()]))
+        .collect();
+    let skipped = db::db_save(&ctx, table, &updates).await.unwrap();
+    assert_eq!(skipped, ["1"]);
+    let html: Vec<Option<String>> = sqlx::query_scalar("select html from hu order by hu_id")
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(html[0].as_deref(), Some("<p>edited</p>"));
+    assert!(html[1..].iter().all(|h| h.as_deref() == Some("<p>new</p>")));
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::Client;
+use huxi_server::fun::user::db::db_set_role;
+use huxi_server::fun::user::Role;
+use huxi_server::fun::{hu, tag};
+use sqlx::PgPool;
+
+/// 修改记录里有没有公布的内容，只有作者和编辑能看
+#[sqlx::test(fixtures("users", "hu"))]
+async fn revisions_are_for_owner_and_editors(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let mut client = Client::new(db.clone()).await;
+    for uri in ["/revision/hu/1", "/revision/hu/1/diff", "/revision/hu/4"] {
+        assert!(!client.get(uri).await.status.is_success(), "{uri}");
+    }
+
+    let to = client
+        .post(
+            "/user/login",
+            &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+        )
+        .await
+        .location;
+    assert_eq!(to.as_deref(), Some("/my/hx"));
+    assert!(client.get("/revision/hu/4").await.status.is_success());
+
+    db_set_role(&ctx, 1, Role::Author as i16).await.unwrap();
+    assert!(client.get("/revision/hu/1").await.status.is_success());
+    assert!(!client.get("/revision/hu/4").await.status.is_success());
+    let res = client.get("/my/revision/restore/hu/4/1").await;
+    assert!(!res.status.is_success() && res.location.is_none());
+}
+
+/// 删除内容时修改记录、星标、访问次数和标签一起删除
+#[sqlx::test(fixtures("users", "hu"))]
+async fn rm_deletes_related_rows(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    for id in [1, 2] {
+        sqlx::query(
+            "insert into revision (kind, obj_id, user_id, user_name, title, body)
+             values ('hu', $1, 1, 'huxi', 't', 'b')",
+        )
+        .bind(id)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query("insert into user_star (user_id, kind, obj_id) values (1, 'hu', $1)")
+            .bind(id)
+            .execute(&db)
+            .await
+            .unwrap();
+        sqlx::query("insert into visit_day (kind, obj_id, day, views) values ('hu', $1, now(), 3)")
+            .bind(id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let mut conn = db.acquire().await.unwrap();
+        tag::db::db_set_tags(&mut conn, "hu", id, Some("rust"))
+            .await
+            .unwrap();
+    }
+
+    assert!(hu::db::db_rm(&ctx, 1).await.unwrap());
+    for table in ["revision", "user_star", "visit_day", "tagging"] {
+        let sql = format!("select obj_id from {} where kind = 'hu'", table);
+        let ids: Vec<i32> = sqlx::query_scalar(&sql).fetch_all(&db).await.unwrap();
+        assert_eq!(ids, [2], "{table}");
+    }
+    assert!(!hu::db::db_rm(&ctx, 1).await.unwrap());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::Client;
+use sqlx::PgPool;
+
+/// 公布的内容里引用的图片列在页面下面，草稿里的不列
+#[sqlx::test(fixtures("users", "hu"))]
+async fn lists_images_of_public_content(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let img = |file: &str| {
+        format!(
+            r#"<p><img class="embed-image" src="{}/{}" alt=""></p>"#,
+            ctx.config.image.public_url, file
+        )
+    };
+    for (id, html) in [(1, img("a.jpg") + &img("a.jpg")), (3, img("draft.jpg"))] {
+        sqlx::query("update hu set html = $2 where hu_id = $1")
+            .bind(id)
+            .bind(html)
+            .execute(&db)
+            .await
+            .unwrap();
+    }
+
+    let mut client = Client::new(db).await;
+    let res = client.get("/sitemap/hu/1.xml").await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert!(res
+        .body
+        .contains(r#"xmlns:image="http://www.google.com/schemas/sitemap-image/1.1""#));
+    let loc = format!(
+        "<image:loc>{}{}/a.jpg</image:loc>",
+        ctx.config.host.www, ctx.config.image.public_url
+    );
+    assert_eq!(res.body.matches(&loc).count(), 1);
+    assert!(!res.body.contains("draft.jpg"));
+}
+
+/// 页码太大不能溢出
+#[sqlx::test(fixtures("users", "hu"))]
+async fn huge_page_is_not_found(db: PgPool) {
+    let mut client = Client::new(db).await;
+    let res = client.get("/sitemap/hu/9223372036854775807.xml").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = client.get("/sitemap/hu/0.xml").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::*;
+use huxi_server::fun::kind::Kind;
+use huxi_server::fun::star::db;
+use huxi_server::fun::widget::is_local;
+use sqlx::PgPool;
+
+#[test]
+fn only_local_paths_are_allowed() {
+    for url in ["/my/star", "/hu/view/1/index.html#star", "/"] {
+        assert!(is_local(url), "{url}");
+    }
+    for url in [
+        "",
+        "my/star",
+        "//evil.com",
+        "/\\evil.com",
+        "/\t/evil.com",
+        "/\n/evil.com",
+        "https://evil.com",
+    ] {
+        assert!(!is_local(url), "{url:?}");
+    }
+}
+
+#[sqlx::test(fixtures("users", "hu"))]
+async fn cancel_returns_only_to_local_pages(db: PgPool) {
+    let mut c = Client::new(db).await;
+    c.post(
+        "/user/login",
+        &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+    )
+    .await;
+    let cancel = |url: &str| format!("/my/star/cancel/hu/1?url={}", urlencoding::encode(url));
+    let r = c.get(&cancel("/my/star")).await;
+    assert_eq!(r.location.as_deref(), Some("/my/star"));
+    for url in ["//evil.com", "/\\evil.com", "/\t/evil.com"] {
+        let r = c.get(&cancel(url)).await;
+        assert_eq!(
+            r.location.as_deref(),
+            Some("/hu/view/1/index.html#star"),
+            "{url:?}"
+        );
+    }
+}
+
+/// 加星和取消都不算修改内容
+#[sqlx::test(fixtures("users", "hu"))]
+async fn star_keeps_updated_at(db: PgPool) {
+    let ctx = ctx(db.clone());
+    let row = || async {
+        sqlx::query_as::<_, (i32, Option<chrono::DateTime<chrono::Utc>>)>(
+            "select star, updated_at from hu where hu_id = 2",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap()
+    };
+    let (_, before) = row().await;
+    assert!(db::db_star(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert_eq!(row().await, (1, before));
+    assert!(db::db_unstar(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert_eq!(row().await, (0, before));
+}
+
+/// 每人每条只能加一次星，不同的人分开算
+#[sqlx::test(fixtures("users", "hu"))]
+async fn star_once_per_user(db: PgPool) {
+    let ctx = ctx(db.clone());
+    let star = || async {
+        sqlx::query_scalar::<_, i32>("select star from hu where hu_id = 2")
+            .fetch_one(&db)
+            .await
+            .unwrap()
+    };
+    assert!(db::db_star(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert!(!db::db_star(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert_eq!(star().await, 1);
+    assert!(db::db_is_starred(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert!(!db::db_is_starred(&ctx, 2, Kind::Hu, 2).await.unwrap());
+
+    assert!(db::db_star(&ctx, 2, Kind::Hu, 2).await.unwrap());
+    assert_eq!(star().await, 2);
+    // 同一个编号的微博是另一条
+    assert!(!db::db_is_starred(&ctx, 1, Kind::Xi, 2).await.unwrap());
+
+    // 取消两次只减一次
+    assert!(db::db_unstar(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert!(!db::db_unstar(&ctx, 1, Kind::Hu, 2).await.unwrap());
+    assert_eq!(star().await, 1);
+
+    // 别人的草稿不能加星
+    assert!(!db::db_star(&ctx, 2, Kind::Hu, 3).await.unwrap());
+    let rows: i64 = sqlx::query_scalar("select count(*) from user_star where obj_id = 3")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(rows, 0);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::Client;
+use huxi_server::fun::kind::Kind;
+use huxi_server::fun::tag::db::{db_set_tags, db_tag_items};
+use huxi_server::fun::tag::{parse_tags, MAX_LEN, MAX_TAGS};
+use sqlx::PgPool;
+
+#[test]
+fn parse_tags_splits_and_dedups() {
+    assert_eq!(
+        parse_tags("#Rust, rust；Web、数据库;  #  db\tx"),
+        ["rust", "web", "数据库", "db", "x"]
+    );
+    assert!(parse_tags(" ,，、# ").is_empty());
+
+    let long = "长".repeat(MAX_LEN + 1);
+    assert_eq!(parse_tags(&format!("{} ok", long)), ["ok"]);
+    assert_eq!(parse_tags(&"长".repeat(MAX_LEN)).len(), 1);
+
+    let many: Vec<String> = (0..MAX_TAGS + 5).map(|i| format!("t{i}")).collect();
+    let names = parse_tags(&many.join(" "));
+    assert_eq!(names, many[..MAX_TAGS]);
+}
+
+/// 标签页只列出公布的内容，标签名不分大小写，可以带 #
+#[sqlx::test(fixtures("users", "hu"))]
+async fn tag_lists_public_items(db: PgPool) {
+    let mut conn = db.acquire().await.unwrap();
+    for (id, tags) in [(1, "web"), (2, "Rust"), (3, "rust"), (4, "#rust web")] {
+        db_set_tags(&mut conn, Kind::Hu.table(), id, Some(tags))
+            .await
+            .unwrap();
+    }
+    drop(conn);
+    let ctx = common::ctx(db.clone());
+
+    let (total, items) = db_tag_items(&ctx, "rust", 30, 0).await.unwrap();
+    let ids: Vec<i32> = items.iter().map(|i| i.id).collect();
+    assert_eq!(total, 2);
+    assert_eq!(ids.len(), 2);
+    assert!(ids.contains(&2) && ids.contains(&4), "{ids:?}");
+    assert!(items.iter().all(|i| i.kind == "hu"));
+    let (total, items) = db_tag_items(&ctx, "rust", 1, 1).await.unwrap();
+    assert_eq!((total, items.len()), (2, 1));
+    assert_eq!(db_tag_items(&ctx, "none", 30, 0).await.unwrap().0, 0);
+
+    let mut client = Client::new(db).await;
+    let page = client.get("/tag/%23Rust").await.body;
+    assert!(page.contains("共 2 条"), "{page}");
+    assert!(page.contains("100% sure") && page.contains("snake_case"));
+    assert!(!page.contains("draft"), "{page}");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::Client;
+use sqlx::PgPool;
+
+/// 令牌和 session 一样可以用在网页和接口上，撤销以后立即失效
+#[sqlx::test(fixtures("users", "hu"))]
+async fn token_authenticates_until_revoked(db: PgPool) {
+    let mut c = common::login(&db).await;
+    let token = common::token(&mut c, "editor").await;
+    assert!(token.starts_with("hx_"), "{token}");
+    let hash: String = sqlx::query_scalar("select token_hash from api_token")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_ne!(hash, token);
+
+    let bearer = format!("Bearer {}", token);
+    let mut api = Client::new(db.clone())
+        .await
+        .header("authorization", &bearer);
+    // 网页
+    let r = api.get("/my/tokens").await;
+    assert_eq!(r.status, StatusCode::OK);
+    assert!(r.body.contains("editor"), "{}", r.body);
+    let r = api
+        .post(
+            "/my/xi/add",
+            &[
+                ("title", "from token"),
+                ("body", "hello"),
+                ("i_public", "1"),
+                ("i_type", "1"),
+                ("i_category", "1"),
+            ],
+        )
+        .await;
+    assert!(r.body.contains("添加成功"), "{}", r.body);
+    let user_id: i32 = sqlx::query_scalar("select user_id from xi where title = 'from token'")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(user_id, 1);
+    // 接口
+    let r = api.get("/api/v1/hu?mine=true").await;
+    assert_eq!(r.status, StatusCode::OK, "{}", r.body);
+    let used: Option<chrono::DateTime<chrono::Utc>> =
+        sqlx::query_scalar("select last_used_at from api_token")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert!(used.is_some());
+
+    // 令牌不对时不用 session 里的用户
+    let mut wrong = common::login(&db)
+        .await
+        .header("authorization", "Bearer hx_nope");
+    assert_eq!(
+        wrong.get("/my/tokens").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let id: i32 = sqlx::query_scalar("select id from api_token")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    let r = c.get(&format!("/my/tokens/rm/{}", id)).await;
+    assert!(r.body.contains("令牌已撤销"), "{}", r.body);
+    let r = api.get("/my/tokens").await;
+    assert_eq!(r.status, StatusCode::UNAUTHORIZED);
+    assert!(!r.body.contains("editor"));
+    assert_eq!(
+        api.get("/api/v1/hu?mine=true").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+}
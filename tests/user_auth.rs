@@ -0,0 +1,239 @@
+mod common;
+
+use common::Client;
+use huxi_server::fun::user::db::{db_find_user, db_get_user, db_set_role, By, RegInput};
+use huxi_server::fun::user::Role;
+use sqlx::PgPool;
+
+/// 带引号、注释的用户名
+const NAMES: [&str; 6] = [
+    "o'brien",
+    "admin'--",
+    "huxi' or '1'='1",
+    "x'; update users set i_role=5; --",
+    "/* c */ huxi",
+    "\\'; drop table users; --",
+];
+
+async fn register(client: &mut Client, name: &str, email: &str, password: &str) {
+    let page = client.get("/user/reg").await;
+    let marker = "bg-opacity-25 m-2\">";
+    let start = page.body.find(marker).unwrap() + marker.len();
+    let code: String = page.body[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let res = client
+        .post(
+            "/user/reg",
+            &[
+                ("name", name),
+                ("email", email),
+                ("password", password),
+                ("password2", password),
+                ("validate", &code),
+            ],
+        )
+        .await;
+    assert!(res.status.is_success(), "{name}: {}", res.status);
+}
+
+async fn login(client: &mut Client, name: &str, password: &str) -> Option<String> {
+    client
+        .post(
+            "/user/login",
+            &[("name", name), ("password", password), ("from", "")],
+        )
+        .await
+        .location
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn db_get_user_binds_values(db: PgPool) {
+    let ctx = common::ctx(db);
+    for name in NAMES {
+        assert!(db_get_user(&ctx, By::Name(name.to_string()))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_get_user(&ctx, By::Email(name.to_string()))
+            .await
+            .unwrap()
+            .is_none());
+    }
+    let u = db_get_user(&ctx, By::Name("HUXI".to_string()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(u.user_id, 1);
+    let u = db_get_user(&ctx, By::Id(1)).await.unwrap().unwrap();
+    assert_eq!(u.email, "huxi@example.com");
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn login_rejects_injection(db: PgPool) {
+    let mut client = Client::new(db).await;
+    for name in NAMES {
+        let to = login(&mut client, name, "x").await;
+        assert_eq!(to.as_deref(), Some("/user/login?error=true"), "{name}");
+    }
+    let to = login(&mut client, "huxi' --", "huxi1234").await;
+    assert_eq!(to.as_deref(), Some("/user/login?error=true"));
+
+    let to = login(&mut client, "huxi", "huxi1234").await;
+    assert_eq!(to.as_deref(), Some("/my/hx"));
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn register_and_login_with_quoted_names(db: PgPool) {
+    let mut client = Client::new(db.clone()).await;
+    for (i, name) in NAMES.iter().enumerate() {
+        let email = format!("u{i}@example.com");
+        register(&mut client, name, &email, "pass'--").await;
+
+        let (uid, role): (sqlx::types::Uuid, i16) =
+            sqlx::query_as("select uid, i_role from users where name=$1 and email=$2")
+                .bind(name)
+                .bind(&email)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(role, 0);
+        client.get(&format!("/user/reg/v/{uid}")).await;
+
+        let mut other = Client::new(db.clone()).await;
+        assert_eq!(
+            login(&mut other, name, "pass'--").await.as_deref(),
+            Some("/my/hx"),
+            "{name}"
+        );
+        assert_eq!(
+            login(&mut other, &email, "pass'--").await.as_deref(),
+            Some("/my/hx")
+        );
+    }
+    let (total, sudo): (i64, i64) =
+        sqlx::query_as("select count(*), count(*) filter (where i_role >= 5) from users")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!((total, sudo), (NAMES.len() as i64 + 1, 1));
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn password_reset_with_quoted_names(db: PgPool) {
+    let mut client = Client::new(db.clone()).await;
+    for name in NAMES {
+        let res = client.post("/my/pw", &[("name", name)]).await;
+        assert!(res.body.contains("用户不存在"), "{name}");
+    }
+    for id in ["'; drop table users; --", "not-a-uuid"] {
+        let uri = format!("/my/pw/new/{}", urlencoding::encode(id));
+        let res = client.get(&uri).await;
+        assert!(res.body.contains("不存在错误"));
+        let res = client
+            .post(&uri, &[("password", "x"), ("password2", "x")])
+            .await;
+        assert!(res.body.contains("不存在错误"));
+    }
+
+    sqlx::query("update users set name=$1 where user_id=1")
+        .bind(NAMES[1])
+        .execute(&db)
+        .await
+        .unwrap();
+    client.post("/my/pw", &[("name", NAMES[1])]).await;
+    let (id,): (sqlx::types::Uuid,) =
+        sqlx::query_as("select id from reset_pw_req where user_name=$1")
+            .bind(NAMES[1])
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    let res = client
+        .post(
+            &format!("/my/pw/new/{id}"),
+            &[("password", "new'pw"), ("password2", "new'pw")],
+        )
+        .await;
+    assert!(res.body.contains("密码重置成功"));
+
+    assert_eq!(
+        login(&mut client, NAMES[1], "new'pw").await.as_deref(),
+        Some("/my/hx")
+    );
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn find_user_by_id_name_or_email(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    // 以前注册的全是数字的用户名和大小写不同的重复邮箱
+    sqlx::query(
+        "insert into users (name, email, password, i_role) values
+           ('100', 'num@example.com', 'x', 1),
+           ('dup1', 'Dup@example.com', 'x', 1),
+           ('dup2', 'dup@example.com', 'x', 1)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let huxi = db_find_user(&ctx, "1").await.unwrap().unwrap();
+    assert_eq!(huxi.name, "huxi");
+    assert!(db_find_user(&ctx, "100").await.unwrap().is_none());
+    assert!(db_find_user(&ctx, "99999999999").await.unwrap().is_none());
+    let num = db_find_user(&ctx, "num@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(num.name, "100");
+    assert_eq!(
+        db_find_user(&ctx, "HUXI").await.unwrap().unwrap().user_id,
+        huxi.user_id
+    );
+    assert!(db_find_user(&ctx, "dup@example.com").await.is_err());
+
+    let input = RegInput {
+        name: "100".to_string(),
+        email: "n@example.com".to_string(),
+        password: "pw".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(input.check(), ["用户名不能全是数字"]);
+}
+
+#[sqlx::test(fixtures("users"))]
+async fn role_changes_apply_to_logged_in_users(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let mut client = Client::new(db.clone()).await;
+    assert!(login(&mut client, "huxi", "huxi1234").await.is_some());
+    assert!(client.get("/admin/user").await.status.is_success());
+
+    let huxi = db_find_user(&ctx, "huxi").await.unwrap().unwrap();
+    db_set_role(&ctx, huxi.user_id, Role::Reader as i16)
+        .await
+        .unwrap();
+    assert!(!client.get("/admin/user").await.status.is_success());
+    assert!(client.get("/my/hx").await.status.is_success());
+
+    db_set_role(&ctx, huxi.user_id, Role::Disabled as i16)
+        .await
+        .unwrap();
+    let res = client.get("/my/hx").await;
+    assert_eq!(res.location.as_deref(), Some("/user/login"));
+}
+
+#[test]
+fn check_email_format() {
+    let input = |email: &str| RegInput {
+        name: "huxi2".to_string(),
+        email: email.to_string(),
+        password: "huxi1234".to_string(),
+        ..Default::default()
+    };
+    for email in ["a@b.c", "o'brien+x@mail.example.com"] {
+        assert!(input(email).check().is_empty(), "{email}");
+    }
+    for email in ["a", "a@", "@b.c", "a@-b.c", "a b@c.d"] {
+        assert_eq!(input(email).check(), vec!["邮箱格式错误"], "{email}");
+    }
+}
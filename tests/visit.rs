@@ -0,0 +1,113 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use huxi_server::fun::kind::Kind;
+use huxi_server::fun::visit::{self, Visitor};
+use sqlx::PgPool;
+
+async fn click(db: &PgPool, id: i32) -> (i32, Option<DateTime<Utc>>) {
+    sqlx::query_as("select click, updated_at from hu where hu_id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+/// 写入访问次数不算修改内容
+#[sqlx::test(fixtures("users", "hu"))]
+async fn flush_keeps_updated_at(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let (_, before) = click(&db, 1).await;
+    let visitor = Visitor {
+        keys: vec!["s:a".to_string()],
+        bot: false,
+    };
+    assert!(ctx.visits.hit(Kind::Hu, 1, &visitor));
+    assert_eq!(visit::flush(&ctx).await.unwrap(), 1);
+
+    assert_eq!(click(&db, 1).await, (1, before));
+    let views: i32 =
+        sqlx::query_scalar("select views from visit_day where kind = 'hu' and obj_id = 1")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(views, 1);
+}
+
+/// 同一个 session 或 IP 在时间窗口内只算一次，爬虫和没有标识的不算
+#[sqlx::test(fixtures("users", "hu"))]
+async fn hits_are_counted_once_per_window(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    let visitor = |keys: &[&str], bot: bool| Visitor {
+        keys: keys.iter().map(|k| k.to_string()).collect(),
+        bot,
+    };
+    assert!(ctx
+        .visits
+        .hit(Kind::Hu, 1, &visitor(&["s:a", "i:1.1.1.1"], false)));
+    assert!(!ctx
+        .visits
+        .hit(Kind::Hu, 1, &visitor(&["s:a", "i:1.1.1.1"], false)));
+    // 换了 session，IP 还是一样
+    assert!(!ctx
+        .visits
+        .hit(Kind::Hu, 1, &visitor(&["s:b", "i:1.1.1.1"], false)));
+    assert!(ctx
+        .visits
+        .hit(Kind::Hu, 1, &visitor(&["s:c", "i:2.2.2.2"], false)));
+    // 另一条内容分开算
+    assert!(ctx
+        .visits
+        .hit(Kind::Hu, 2, &visitor(&["s:a", "i:1.1.1.1"], false)));
+    assert!(ctx
+        .visits
+        .hit(Kind::Xi, 1, &visitor(&["s:a", "i:1.1.1.1"], false)));
+    assert!(!ctx
+        .visits
+        .hit(Kind::Hu, 1, &visitor(&["s:d", "i:3.3.3.3"], true)));
+    assert!(!ctx.visits.hit(Kind::Hu, 1, &visitor(&[], false)));
+
+    assert_eq!(visit::flush(&ctx).await.unwrap(), 3);
+    assert_eq!(click(&db, 1).await.0, 2);
+    assert_eq!(click(&db, 2).await.0, 1);
+}
+
+/// 作者看自己的文章、爬虫访问都不计数
+#[sqlx::test(fixtures("users", "hu"))]
+async fn owner_and_bots_are_not_counted(db: PgPool) {
+    let ctx = common::ctx(db.clone());
+    // 保存时才生成 HTML
+    sqlx::query("update hu set html = '<p>body one</p>' where hu_id = 1")
+        .execute(&db)
+        .await
+        .unwrap();
+    let url = "/hu/view/1/index.html";
+    let browser = "Mozilla/5.0 (X11; Linux x86_64)";
+
+    let mut bot = common::Client::with_ctx(ctx.clone())
+        .await
+        .header("user-agent", "Googlebot/2.1");
+    bot.get(url).await;
+    // 没有 User-Agent 的也当作爬虫
+    common::Client::with_ctx(ctx.clone()).await.get(url).await;
+    let mut owner = common::Client::with_ctx(ctx.clone())
+        .await
+        .header("user-agent", browser);
+    owner
+        .post(
+            "/user/login",
+            &[("name", "huxi"), ("password", "huxi1234"), ("from", "")],
+        )
+        .await;
+    let r = owner.get(url).await;
+    assert!(r.body.contains("/my/hu/edit/1"), "{}", r.body);
+    assert_eq!(visit::flush(&ctx).await.unwrap(), 0);
+
+    let mut reader = common::Client::with_ctx(ctx.clone())
+        .await
+        .header("user-agent", browser);
+    reader.get(url).await;
+    reader.get(url).await;
+    assert_eq!(visit::flush(&ctx).await.unwrap(), 1);
+    assert_eq!(click(&db, 1).await.0, 1);
+}